@@ -0,0 +1,353 @@
+use std::ops::Range;
+
+use structure::{asi_points, matching_bracket, next_meaningful};
+use tokenizer::{tokenize, Spanned, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// The token at this index of `old` is kept at this index of `new`.
+    Unchanged(usize, usize),
+    /// The token at this index of `old` was removed.
+    Removed(usize),
+    /// The token at this index of `new` was added.
+    Added(usize),
+}
+
+/// Computes a shortest edit script turning `old` into `new`, from the longest
+/// common subsequence of the two. Edits are in order of both sequences.
+///
+/// The common prefix and suffix are matched directly; the rest takes time and
+/// space proportional to the product of the remaining lengths.
+pub fn diff_tokens<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|&(a, b)| a == b).count();
+    let suffix = old[prefix..].iter()
+                              .rev()
+                              .zip(new[prefix..].iter().rev())
+                              .take_while(|&(a, b)| a == b)
+                              .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    // lengths[i * width + j] is the length of the longest common subsequence
+    // of old_middle[i..] and new_middle[j..].
+    let width = new_middle.len() + 1;
+    let mut lengths = vec![0u32; (old_middle.len() + 1) * width];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lengths[i * width + j] = if old_middle[i] == new_middle[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit::Unchanged(i, i)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            edits.push(Edit::Unchanged(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if j < new_middle.len() &&
+                  (i == old_middle.len() ||
+                   lengths[i * width + j + 1] >= lengths[(i + 1) * width + j]) {
+            edits.push(Edit::Added(prefix + j));
+            j += 1;
+        } else {
+            edits.push(Edit::Removed(prefix + i));
+            i += 1;
+        }
+    }
+    for k in 0..suffix {
+        edits.push(Edit::Unchanged(old.len() - suffix + k, new.len() - suffix + k));
+    }
+
+    edits
+}
+
+/// Pairs up the tokens a transformed source kept unchanged from the original,
+/// returning the start offset of each in the original and in the transformed
+/// source. Whitespace and comments are ignored, so this aligns a source with
+/// a reformatted or minified version of it, and can serve as the basis of a
+/// simple source map.
+pub fn token_alignment(orig: &[Spanned], transformed: &[Spanned]) -> Vec<(usize, usize)> {
+    let orig: Vec<_> = orig.iter().filter(|spanned| !spanned.token.is_greyspace()).collect();
+    let transformed: Vec<_> = transformed.iter()
+                                         .filter(|spanned| !spanned.token.is_greyspace())
+                                         .collect();
+    // Tokens are compared by text since whether a word comes out as a
+    // `Keyword` depends on the whitespace around it.
+    let orig_tokens: Vec<_> = orig.iter().map(|spanned| spanned.token.as_str()).collect();
+    let transformed_tokens: Vec<_> = transformed.iter()
+                                                .map(|spanned| spanned.token.as_str())
+                                                .collect();
+
+    diff_tokens(&orig_tokens, &transformed_tokens)
+        .into_iter()
+        .filter_map(|edit| {
+            match edit {
+                Edit::Unchanged(i, j) => Some((orig[i].span.start, transformed[j].span.start)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Counts of meaningful tokens added, removed and kept between two versions
+/// of a source, as computed by `token_churn`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Churn {
+    pub added: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// Measures how much `new` changes `old` by diffing their tokens other than
+/// whitespace and comments, so that reformatting a file or editing its
+/// comments adds no churn.
+pub fn token_churn(old: &str, new: &str) -> Churn {
+    let old_tokens = tokenize(old);
+    let new_tokens = tokenize(new);
+    // Compared by text, as in `token_alignment`.
+    let old_words: Vec<_> = old_tokens.iter()
+                                      .filter(|token| !token.is_greyspace())
+                                      .map(|token| token.as_str())
+                                      .collect();
+    let new_words: Vec<_> = new_tokens.iter()
+                                      .filter(|token| !token.is_greyspace())
+                                      .map(|token| token.as_str())
+                                      .collect();
+
+    let mut churn = Churn::default();
+    for edit in diff_tokens(&old_words, &new_words) {
+        match edit {
+            Edit::Unchanged(..) => churn.unchanged += 1,
+            Edit::Removed(_) => churn.removed += 1,
+            Edit::Added(_) => churn.added += 1,
+        }
+    }
+    churn
+}
+
+// Whether the statement starting at `first` ends with its first top-level
+// block, as declarations and compound statements do.
+fn ends_with_block(tokens: &[Spanned], first: usize) -> bool {
+    let opens_block = |index: usize| {
+        let token = &tokens[index].token;
+        ["function", "class", "if", "for", "while", "try", "switch", "with"]
+            .iter()
+            .any(|&word| token.is_word(word))
+    };
+    let head = match next_meaningful(tokens, first) {
+        Some(next) if tokens[first].token.is_word("export") ||
+                      tokens[first].token.is_word("async") => {
+            match next_meaningful(tokens, next) {
+                Some(after) if tokens[next].token.is_word("default") => after,
+                _ => next,
+            }
+        }
+        _ => first,
+    };
+    tokens[head].token == Token::LeftBrace || opens_block(head)
+}
+
+/// Splits the tokens into top-level statements and declarations, returning
+/// the byte range of each without surrounding whitespace and comments, so
+/// that a diff can be aligned to the functions and statements it changes.
+///
+/// Statements end at `;`, where a semicolon would be inserted, or with the
+/// block closing a function, class or compound statement, unless followed
+/// by `else`, `catch` or `finally`.
+pub fn chunk_by_top_level_statements(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let asi = asi_points(tokens);
+    let mut chunks = Vec::new();
+    let mut first = None;
+    let mut last = 0;
+    let mut index = 0;
+
+    while index < tokens.len() {
+        if tokens[index].token.is_greyspace() {
+            index += 1;
+            continue;
+        }
+        let start = *first.get_or_insert(index);
+
+        let end = match tokens[index].token {
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                matching_bracket(tokens, index).unwrap_or(tokens.len() - 1)
+            }
+            _ => index,
+        };
+        let ends = match tokens[index].token {
+            Token::Semicolon => true,
+            Token::LeftBrace => ends_with_block(tokens, start),
+            _ => false,
+        } || asi.binary_search(&end).is_ok();
+        let continues = next_meaningful(tokens, end).map_or(false, |next| {
+            let next = &tokens[next].token;
+            next.is_word("else") || next.is_word("catch") || next.is_word("finally")
+        });
+
+        if ends && !continues {
+            chunks.push(tokens[start].span.start..tokens[end].span.end);
+            first = None;
+        }
+        last = end;
+        index = end + 1;
+    }
+    // A statement cut off by the end of the input.
+    if let Some(start) = first {
+        chunks.push(tokens[start].span.start..tokens[last].span.end);
+    }
+
+    chunks
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| (hash ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+// Identifiers and keywords share a tag since which one a word becomes
+// depends on the whitespace around it. Punctuators are told apart by text.
+fn kind_tag(token: &Token) -> u8 {
+    match *token {
+        Token::Identifier(_) | Token::Keyword(_) => 1,
+        Token::NumericLiteral(_) => 2,
+        Token::StringLiteral(_) => 3,
+        Token::RegexLiteral(_) => 4,
+        Token::TemplateLiteral(_) => 5,
+        Token::Unknown(_) => 6,
+        Token::PrivateIdentifier(_) => 7,
+        _ => 0,
+    }
+}
+
+/// Hashes the tokens of `input` other than whitespace and comments, so that
+/// reformatting a file or editing its comments keeps its hash. The hash is
+/// 64-bit FNV-1a, which is stable across runs and platforms.
+pub fn token_hash(input: &str) -> u64 {
+    hash_tokens(&tokenize(input))
+}
+
+/// Like `token_hash`, for already tokenized input.
+pub fn hash_tokens(tokens: &[Token]) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+    for token in tokens.iter().filter(|token| !token.is_greyspace()) {
+        hash = fnv1a(hash, &[kind_tag(token)]);
+        hash = fnv1a(hash, token.as_str().as_bytes());
+        // Keeps `ab` from hashing like `a` followed by `b`.
+        hash = fnv1a(hash, &[0xff]);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::{tokenize, tokenize_spanned};
+
+    #[test]
+    fn diff_identical() {
+        let tokens = tokenize("a = 1;");
+        assert_eq!(diff_tokens(&tokens, &tokens),
+                   (0..tokens.len()).map(|i| Edit::Unchanged(i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn diff_replaced_token() {
+        assert_eq!(diff_tokens(&["a", "=", "1"], &["a", "=", "2"]),
+                   vec![Edit::Unchanged(0, 0),
+                        Edit::Unchanged(1, 1),
+                        Edit::Added(2),
+                        Edit::Removed(2)]);
+    }
+
+    #[test]
+    fn diff_insert_and_remove() {
+        assert_eq!(diff_tokens(&["f", "(", "a", ")"], &["f", "(", "a", ",", "b", ")"]),
+                   vec![Edit::Unchanged(0, 0),
+                        Edit::Unchanged(1, 1),
+                        Edit::Unchanged(2, 2),
+                        Edit::Added(3),
+                        Edit::Added(4),
+                        Edit::Unchanged(3, 5)]);
+        assert_eq!(diff_tokens(&["x", "y", "z"], &["z"]),
+                   vec![Edit::Removed(0), Edit::Removed(1), Edit::Unchanged(2, 0)]);
+    }
+
+    #[test]
+    fn token_hash_ignores_formatting() {
+        assert_eq!(token_hash("a=1"), token_hash("a = 1 // c"));
+        assert_eq!(token_hash("if(a){b()}"), token_hash("if (a) {\n    b()\n}"));
+        assert_eq!(token_hash(""), FNV_OFFSET_BASIS);
+    }
+
+    #[test]
+    fn token_hash_detects_changes() {
+        assert!(token_hash("a=1") != token_hash("a=2"));
+        assert!(token_hash("a b") != token_hash("ab"));
+        assert!(token_hash("'a'") != token_hash("a"));
+    }
+
+    #[test]
+    fn churn_ignores_formatting() {
+        let old = "function f(a,b){return a+b}";
+        let new = "// Adds.\nfunction f(a, b) {\n    return a + b;\n}";
+        assert_eq!(token_churn(old, old), Churn { added: 0, removed: 0, unchanged: 13 });
+        assert_eq!(token_churn(old, new), Churn { added: 1, removed: 0, unchanged: 13 });
+        assert_eq!(token_churn("if(a){b()}", "if (a) {\n    b()\n}"),
+                   Churn { added: 0, removed: 0, unchanged: 9 });
+    }
+
+    #[test]
+    fn churn_counts_changes() {
+        assert_eq!(token_churn("x = a + b;", "x = a - b * 2;"),
+                   Churn { added: 3, removed: 1, unchanged: 5 });
+        assert_eq!(token_churn("", "a()"), Churn { added: 3, removed: 0, unchanged: 0 });
+    }
+
+    fn chunks(input: &str) -> Vec<&str> {
+        chunk_by_top_level_statements(&tokenize_spanned(input))
+            .into_iter()
+            .map(|range| &input[range])
+            .collect()
+    }
+
+    #[test]
+    fn chunk_functions_and_declarations() {
+        let input = "function a() {\n    return 1;\n}\n\n\
+                     var x = {b: 2};\n\
+                     // c\n\
+                     function c(d) { if (d) { return; } }\n";
+        let tokens = tokenize_spanned(input);
+        assert_eq!(chunk_by_top_level_statements(&tokens), vec![0..30, 32..47, 53..89]);
+        assert_eq!(chunks(input),
+                   vec!["function a() {\n    return 1;\n}",
+                        "var x = {b: 2};",
+                        "function c(d) { if (d) { return; } }"]);
+    }
+
+    #[test]
+    fn chunk_statements() {
+        assert_eq!(chunks("a = 1\nb = f(function () {})\nc()"),
+                   vec!["a = 1", "b = f(function () {})", "c()"]);
+        assert_eq!(chunks("if (a) {} else {}\ntry {} catch (e) {} finally {} x;"),
+                   vec!["if (a) {} else {}", "try {} catch (e) {} finally {}", "x;"]);
+        assert_eq!(chunks("export default class A {} {}"),
+                   vec!["export default class A {}", "{}"]);
+        assert_eq!(chunks("a;\nb +"), vec!["a;", "b +"]);
+    }
+
+    #[test]
+    fn align_minified() {
+        let orig = tokenize_spanned("function f(a, b) {\n    return a + b; // sum\n}");
+        let minified = tokenize_spanned("function f(a,b){return a+b}");
+        assert_eq!(token_alignment(&orig, &minified),
+                   vec![(0, 0), (9, 9), (10, 10), (11, 11), (12, 12), (14, 13), (15, 14),
+                        (17, 15), (23, 16), (30, 23), (32, 24), (34, 25), (44, 26)]);
+    }
+}
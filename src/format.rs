@@ -0,0 +1,1055 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use structure::{asi_points, next_meaningful, prev_meaningful, statement_semicolons};
+use tokenizer::{tokenize, tokenize_spanned, NumericKind, Spanned, Token};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatStyle {
+    /// Inserted once per open brace at the start of each line.
+    pub indent: String,
+}
+
+impl Default for FormatStyle {
+    fn default() -> FormatStyle {
+        FormatStyle { indent: String::from("    ") }
+    }
+}
+
+fn is_word(token: &Token) -> bool {
+    match *token {
+        Token::Identifier(_) |
+        Token::Keyword(_) |
+        Token::PrivateIdentifier(_) |
+        Token::NumericLiteral(_) |
+        Token::StringLiteral(_) |
+        Token::RegexLiteral(_) |
+        Token::TemplateLiteral(_) => true,
+        _ => false,
+    }
+}
+
+fn is_value_end(token: &Token) -> bool {
+    match *token {
+        Token::Keyword(s) => {
+            s == "this" || s == "null" || s == "true" || s == "false" || s == "super"
+        }
+        Token::Identifier(_) |
+        Token::PrivateIdentifier(_) |
+        Token::StringLiteral(_) |
+        Token::TemplateLiteral(_) |
+        Token::RightParen |
+        Token::RightBracket |
+        Token::RightBrace => true,
+        _ => false,
+    }
+}
+
+fn ends_operand(token: &Token) -> bool {
+    match *token {
+        Token::NumericLiteral(_) |
+        Token::RegexLiteral(_) => true,
+        _ => is_value_end(token),
+    }
+}
+
+fn is_unary_candidate(token: &Token) -> bool {
+    match *token {
+        Token::Plus |
+        Token::Minus |
+        Token::ExclamationMark |
+        Token::BitwiseNot => true,
+        _ => false,
+    }
+}
+
+// Whether `token` keeps going on the line of `prev`, even though `prev`
+// would normally end it.
+fn continues_line(prev: &Token, token: &Token) -> bool {
+    match (prev, token) {
+        (&Token::LeftBrace, &Token::RightBrace) => true,
+        (&Token::RightBrace, &Token::RightParen) |
+        (&Token::RightBrace, &Token::RightBracket) |
+        (&Token::RightBrace, &Token::Semicolon) |
+        (&Token::RightBrace, &Token::Comma) |
+        (&Token::RightBrace, &Token::Dot) => true,
+        (&Token::RightBrace, &Token::Keyword(s)) |
+        (&Token::RightBrace, &Token::Identifier(s)) => {
+            s == "else" || s == "catch" || s == "finally"
+        }
+        _ => false,
+    }
+}
+
+fn space_between(prev: &Token, prev_is_unary: bool, token: &Token) -> bool {
+    if prev_is_unary {
+        return false;
+    }
+
+    match *token {
+        Token::RightParen |
+        Token::RightBracket |
+        Token::Semicolon |
+        Token::Comma |
+        Token::Dot => return false,
+        Token::LeftParen | Token::LeftBracket if is_value_end(prev) => return false,
+        Token::DeIncrement(_) if is_value_end(prev) => return false,
+        _ => {}
+    }
+
+    match *prev {
+        Token::LeftBrace => *token != Token::RightBrace,
+        Token::LeftParen |
+        Token::LeftBracket |
+        Token::Dot |
+        Token::Ellipsis => false,
+        Token::DeIncrement(_) => !is_word(token),
+        _ => true,
+    }
+}
+
+fn is_id_part(c: char) -> bool {
+    c.is_alphanumeric() || c == '$' || c == '_' || c == '\\'
+}
+
+// Whether `last` followed by `first` can start a longer punctuator or a
+// comment, including the HTML-like `<!--` of scripts.
+fn punctuators_merge(last: char, first: char) -> bool {
+    match (last, first) {
+        ('+', '+') | ('-', '-') | ('*', '*') | ('<', '<') | ('>', '>') | ('&', '&') |
+        ('|', '|') | ('?', '?') | ('?', '.') | ('=', '>') | ('/', '/') | ('/', '*') |
+        ('*', '/') | ('<', '!') => true,
+        (_, '=') => "=!<>+-*/%&|^?".contains(last),
+        _ => false,
+    }
+}
+
+/// Whether writing `b` directly after `a` would tokenize differently, so
+/// that a minimal rendering needs whitespace between them. This covers words
+/// running together (`return x`), punctuators merging (`+ +`, `= =`), `.`
+/// after an integer or before a digit, comments forming from slashes, the
+/// HTML-like comments `<!--` and `-->` of scripts, and regex flags. A line
+/// comment is always followed by a line break, so this returns true after
+/// one.
+pub fn needs_separating_space(a: &Token, b: &Token) -> bool {
+    let (last, first) = match (a.as_str().chars().last(), b.as_str().chars().next()) {
+        (Some(last), Some(first)) => (last, first),
+        _ => return false,
+    };
+
+    match *a {
+        Token::LineComment(_) => return true,
+        Token::RegexLiteral(_) => return is_id_part(first),
+        Token::NumericLiteral(s) if first == '.' => {
+            return a.numeric_kind() == Some(NumericKind::Decimal) && !s.contains('.') &&
+                   !s.contains('e') && !s.contains('E');
+        }
+        Token::StringLiteral(_) | Token::TemplateLiteral(_) => return false,
+        _ => {}
+    }
+
+    (is_id_part(last) && is_id_part(first)) ||
+    (last == '.' && (first == '.' || first.is_digit(10))) ||
+    (a.as_str().ends_with("--") && first == '>') ||
+    punctuators_merge(last, first)
+}
+
+/// Rebuilds source from `tokens` with normalized spacing: one space around
+/// binary operators, none inside `()` and `[]`, a space after `,` and a new
+/// line after `{`, `}` and `;`. Other whitespace is discarded, except for
+/// the line breaks automatic semicolon insertion depends on.
+pub fn format_tokens(tokens: &[Token], style: &FormatStyle) -> String {
+    let asi = asi_points(tokens);
+    let mut output = String::new();
+    let mut indent = 0;
+    let mut parens = 0;
+    let mut ternaries = 0;
+    let mut newline_pending = false;
+    let mut at_line_start = true;
+    let mut prev: Option<&Token> = None;
+    let mut prev_is_unary = false;
+    // Whether `prev` ends an operand, making a following `+` or `-` binary.
+    let mut prev_ends_operand = false;
+
+    for (index, token) in tokens.iter().enumerate() {
+        if let Token::Whitespace(_) = *token {
+            continue;
+        }
+        if *token == Token::RightBrace {
+            if indent > 0 {
+                indent -= 1;
+            }
+            newline_pending = newline_pending || prev.is_some();
+        }
+
+        if newline_pending && !prev.map_or(false, |prev| continues_line(prev, token)) {
+            output.push('\n');
+            at_line_start = true;
+        }
+        newline_pending = false;
+
+        let is_unary = is_unary_candidate(token) && !prev_ends_operand;
+
+        if at_line_start {
+            for _ in 0..indent {
+                output.push_str(&style.indent);
+            }
+        } else if let Some(prev) = prev {
+            let colon_without_ternary = *token == Token::Colon && ternaries == 0;
+            if !colon_without_ternary && space_between(prev, prev_is_unary, token) {
+                output.push(' ');
+            }
+        }
+        output.push_str(token.as_str());
+        at_line_start = false;
+
+        match *token {
+            Token::LeftBrace => {
+                indent += 1;
+                newline_pending = true;
+            }
+            Token::RightBrace |
+            Token::LineComment(_) |
+            Token::Shebang(_) => newline_pending = true,
+            Token::Semicolon if parens == 0 => newline_pending = true,
+            Token::LeftParen | Token::LeftBracket => parens += 1,
+            Token::RightParen | Token::RightBracket if parens > 0 => parens -= 1,
+            Token::QuestionMark => ternaries += 1,
+            Token::Colon if ternaries > 0 => ternaries -= 1,
+            _ => {}
+        }
+        if asi.binary_search(&index).is_ok() {
+            newline_pending = true;
+        }
+
+        prev_ends_operand = match *token {
+            // A postfix `++` or `--`.
+            Token::DeIncrement(_) => prev_ends_operand,
+            _ => ends_operand(token),
+        };
+        prev = Some(token);
+        prev_is_unary = is_unary;
+    }
+
+    output
+}
+
+/// Canonicalizes a numeric literal's spelling: base prefixes, hex digits and
+/// exponent markers are lowercased, and leading zeros and separators after a
+/// base prefix are dropped. Decimal leading zeros are kept, since `010` is a
+/// legacy octal literal. Other tokens are returned as written.
+pub fn normalize_numeric<'a>(token: &Token<'a>) -> Cow<'a, str> {
+    let literal = token.as_str();
+    let kind = match token.numeric_kind() {
+        Some(kind) => kind,
+        None => return Cow::Borrowed(literal),
+    };
+
+    let normalized = match kind {
+        NumericKind::Decimal => literal.to_lowercase(),
+        _ => {
+            let body = &literal[2..];
+            let (body, suffix) = if body.ends_with('n') || body.ends_with('N') {
+                (&body[..body.len() - 1], "n")
+            } else {
+                (body, "")
+            };
+            let digits = body.trim_left_matches(|c| c == '0' || c == '_');
+            let digits = if digits.is_empty() && !body.is_empty() { "0" } else { digits };
+            format!("{}{}{}", &literal[..2], digits, suffix).to_lowercase()
+        }
+    };
+
+    if normalized == literal {
+        Cow::Borrowed(literal)
+    } else {
+        Cow::Owned(normalized)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    Single,
+    Double,
+}
+
+impl QuoteStyle {
+    pub fn quote(&self) -> char {
+        match *self {
+            QuoteStyle::Single => '\'',
+            QuoteStyle::Double => '"',
+        }
+    }
+}
+
+/// Requotes a string literal with the quotes of `prefer`, escaping the new
+/// quote character and unescaping the old one. The literal is kept as is
+/// when it already uses those quotes, when requoting would need more
+/// escapes than it removes, or when it is unterminated. Returns `None` for
+/// other tokens.
+pub fn normalize_quotes<'a>(token: &Token<'a>, prefer: QuoteStyle) -> Option<Cow<'a, str>> {
+    let literal = match *token {
+        Token::StringLiteral(s) => s,
+        _ => return None,
+    };
+    let new = prefer.quote();
+    let old = match literal.chars().next() {
+        Some(old) if old != new && literal.len() >= 2 && literal.ends_with(old) => old,
+        _ => return Some(Cow::Borrowed(literal)),
+    };
+    let body = &literal[1..literal.len() - 1];
+
+    let mut requoted = String::with_capacity(literal.len() + 2);
+    let mut added = 0;
+    let mut removed = 0;
+    let mut chars = body.chars();
+    requoted.push(new);
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(escaped) if escaped == old => {
+                    requoted.push(old);
+                    removed += 1;
+                }
+                Some(escaped) => {
+                    requoted.push(c);
+                    requoted.push(escaped);
+                }
+                None => requoted.push(c),
+            }
+        } else {
+            if c == new {
+                requoted.push('\\');
+                added += 1;
+            }
+            requoted.push(c);
+        }
+    }
+    requoted.push(new);
+
+    Some(if added > removed {
+        Cow::Borrowed(literal)
+    } else {
+        Cow::Owned(requoted)
+    })
+}
+
+// Whether `token` before a `+` concatenation would take the first string
+// as its own operand.
+fn binds_left_operand(token: &Token) -> bool {
+    match *token {
+        Token::Plus |
+        Token::Minus |
+        Token::Star |
+        Token::Slash |
+        Token::Modulo |
+        Token::Exponeniation |
+        Token::Dot |
+        Token::OptionalChain |
+        Token::ExclamationMark |
+        Token::BitwiseNot |
+        Token::DeIncrement(_) => true,
+        _ => ["typeof", "void", "delete", "await", "new"].iter().any(|&word| token.is_word(word)),
+    }
+}
+
+// Whether `token` after a `+` concatenation would take the last string as
+// its own operand.
+fn binds_right_operand(token: &Token) -> bool {
+    match *token {
+        Token::Star |
+        Token::Slash |
+        Token::Modulo |
+        Token::Exponeniation |
+        Token::Dot |
+        Token::OptionalChain |
+        Token::LeftBracket |
+        Token::LeftParen |
+        Token::TemplateLiteral(_) => true,
+        _ => false,
+    }
+}
+
+/// Finds runs of string literals joined by `+`, like `"a" +\n "b"`, that a
+/// minifier can fold into a single literal. Runs next to an operator that
+/// would take one of the strings as its own operand, as in `x + "a" + "b"`
+/// or `"a" + "b".length`, are left out. The strings may use different
+/// quotes, so folding them can take requoting with `normalize_quotes`.
+pub fn find_foldable_concats(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let is_string = |index: usize| {
+        match tokens[index].token {
+            Token::StringLiteral(_) => true,
+            _ => false,
+        }
+    };
+    let mut found = Vec::new();
+    let mut index = 0;
+
+    while index < tokens.len() {
+        if !is_string(index) {
+            index += 1;
+            continue;
+        }
+        let first = index;
+        let mut last = index;
+        loop {
+            let plus = match next_meaningful(tokens, last) {
+                Some(plus) if tokens[plus].token == Token::Plus => plus,
+                _ => break,
+            };
+            match next_meaningful(tokens, plus) {
+                Some(next) if is_string(next) => last = next,
+                _ => break,
+            }
+        }
+
+        let free_before = prev_meaningful(tokens, first)
+                              .map_or(true, |prev| !binds_left_operand(&tokens[prev].token));
+        let free_after = next_meaningful(tokens, last)
+                             .map_or(true, |next| !binds_right_operand(&tokens[next].token));
+        if last > first && free_before && free_after {
+            found.push(tokens[first].span.start..tokens[last].span.end);
+        }
+        index = last + 1;
+    }
+
+    found
+}
+
+// Name of a token in `to_sexpr`, and whether its text follows the name.
+fn sexpr_name(token: &Token) -> (&'static str, bool) {
+    match *token {
+        Token::Whitespace(_) => ("ws", true),
+        Token::Shebang(_) => ("shebang", true),
+        Token::LineComment(_) => ("line-comment", true),
+        Token::BlockComment(_) => ("block-comment", true),
+        Token::NumericLiteral(_) => ("num", true),
+        Token::StringLiteral(_) => ("str", true),
+        Token::RegexLiteral(_) => ("regex", true),
+        Token::TemplateLiteral(_) => ("template", true),
+        Token::Identifier(_) => ("ident", true),
+        Token::Keyword(_) => ("keyword", true),
+        Token::PrivateIdentifier(_) => ("private", true),
+        Token::Unknown(_) => ("unknown", true),
+        Token::DeIncrement(_) => ("incdec", true),
+        Token::BitShift(_) => ("shift", true),
+        Token::Relational(_) => ("rel", true),
+        Token::UpdateAssignment(_) => ("update", true),
+        Token::Exponeniation => ("exp", false),
+        Token::Arrow => ("arrow", false),
+        Token::Equal => ("eq", false),
+        Token::LogicalOr => ("or", false),
+        Token::LogicalAnd => ("and", false),
+        Token::NullishCoalescing => ("nullish", false),
+        Token::OptionalChain => ("optional-chain", false),
+        Token::BitwiseOr => ("bit-or", false),
+        Token::BitwiseXOR => ("bit-xor", false),
+        Token::BitwiseAnd => ("bit-and", false),
+        Token::BitwiseNot => ("bit-not", false),
+        Token::Plus => ("plus", false),
+        Token::Minus => ("minus", false),
+        Token::Modulo => ("mod", false),
+        Token::Star => ("star", false),
+        Token::Slash => ("slash", false),
+        Token::Semicolon => ("semicolon", false),
+        Token::LeftParen => ("lparen", false),
+        Token::RightParen => ("rparen", false),
+        Token::LeftBrace => ("lbrace", false),
+        Token::RightBrace => ("rbrace", false),
+        Token::LeftBracket => ("lbracket", false),
+        Token::RightBracket => ("rbracket", false),
+        Token::Dot => ("dot", false),
+        Token::Ellipsis => ("ellipsis", false),
+        Token::Comma => ("comma", false),
+        Token::QuestionMark => ("question", false),
+        Token::Colon => ("colon", false),
+        Token::ExclamationMark => ("bang", false),
+        Token::StatementEnd => ("statement-end", false),
+        Token::JsxFragmentStart => ("jsx-fragment-start", false),
+        Token::JsxFragmentEnd => ("jsx-fragment-end", false),
+        Token::Annotation(_) => ("annotation", true),
+        Token::JsxText(_) => ("jsx-text", true),
+    }
+}
+
+/// Renders tokens as compact s-expressions, such as `(ident "a") (eq)
+/// (num "1")` for `a = 1`, to show their structure in tests and debugging
+/// output where `Display` would just give back the source. Whitespace is
+/// left out; operators that share a variant carry their text, as in
+/// `(rel "===")`.
+pub fn to_sexpr(tokens: &[Token]) -> String {
+    let mut output = String::new();
+    for token in tokens {
+        if let Token::Whitespace(_) = *token {
+            continue;
+        }
+        if !output.is_empty() {
+            output.push(' ');
+        }
+        match sexpr_name(token) {
+            (name, true) => output.push_str(&format!("({} {:?})", name, token.as_str())),
+            (name, false) => output.push_str(&format!("({})", name)),
+        }
+    }
+    output
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+    /// More than one of the above.
+    Mixed,
+}
+
+impl LineEnding {
+    /// The line ending's text, or `None` for `Mixed`.
+    pub fn as_str(&self) -> Option<&'static str> {
+        match *self {
+            LineEnding::Lf => Some("\n"),
+            LineEnding::CrLf => Some("\r\n"),
+            LineEnding::Cr => Some("\r"),
+            LineEnding::Mixed => None,
+        }
+    }
+}
+
+// Records `ending`, returning false once endings are mixed.
+fn record_line_ending(found: &mut Option<LineEnding>, ending: LineEnding) -> bool {
+    match *found {
+        Some(previous) => previous == ending,
+        None => {
+            *found = Some(ending);
+            true
+        }
+    }
+}
+
+/// Finds which line ending `input` uses between tokens and in comments.
+/// Line breaks inside template literals are ignored. Input without line
+/// breaks is reported as `Lf`.
+pub fn detect_line_ending(input: &str) -> LineEnding {
+    let mut found = None;
+    // A `\r` whose ending depends on the next byte, which may be in the next
+    // token since line comments end before the `\n` of a `\r\n`.
+    let mut pending_cr = false;
+
+    for token in tokenize(input) {
+        let text = match token {
+            Token::Whitespace(s) | Token::LineComment(s) | Token::BlockComment(s) => s,
+            _ => "",
+        };
+        for b in text.bytes() {
+            let ending = match (pending_cr, b) {
+                (true, b'\n') => Some(LineEnding::CrLf),
+                (true, _) => Some(LineEnding::Cr),
+                (false, b'\n') => Some(LineEnding::Lf),
+                (false, _) => None,
+            };
+            if let Some(ending) = ending {
+                if !record_line_ending(&mut found, ending) {
+                    return LineEnding::Mixed;
+                }
+            }
+            pending_cr = b == b'\r';
+        }
+        if text.is_empty() && pending_cr {
+            if !record_line_ending(&mut found, LineEnding::Cr) {
+                return LineEnding::Mixed;
+            }
+            pending_cr = false;
+        }
+    }
+
+    if pending_cr && !record_line_ending(&mut found, LineEnding::Cr) {
+        return LineEnding::Mixed;
+    }
+    found.unwrap_or(LineEnding::Lf)
+}
+
+/// Like `longest_line_with_tab_width`, with tabs four columns wide.
+pub fn longest_line(input: &str) -> (usize, usize) {
+    longest_line_with_tab_width(input, 4)
+}
+
+/// Finds the longest line of `input`, returning its 1-based line number and
+/// its length in code points, not counting trailing whitespace. A tab moves
+/// to the next multiple of `tab_width` columns. The first of equally long
+/// lines is reported.
+///
+/// Only whitespace, comments and literals can hold line breaks or tabs, so
+/// other tokens are just counted.
+pub fn longest_line_with_tab_width(input: &str, tab_width: usize) -> (usize, usize) {
+    let tab_width = tab_width.max(1);
+    let mut longest = (1, 0);
+    let mut line = 1;
+    let mut column = 0;
+    // Column after the last character other than whitespace on the line.
+    let mut content_end = 0;
+    // Whether a `\r` was just seen, making a following `\n` part of its line
+    // break, which may be in the next token.
+    let mut after_cr = false;
+
+    for token in tokenize(input) {
+        let text = match token {
+            Token::Whitespace(s) |
+            Token::LineComment(s) |
+            Token::BlockComment(s) |
+            Token::StringLiteral(s) |
+            Token::TemplateLiteral(s) |
+            Token::RegexLiteral(s) |
+            Token::Unknown(s) => s,
+            _ => {
+                column += token.as_str().chars().count();
+                content_end = column;
+                after_cr = false;
+                continue;
+            }
+        };
+
+        for c in text.chars() {
+            match c {
+                '\n' if after_cr => {}
+                '\n' | '\r' | '\u{2028}' | '\u{2029}' => {
+                    if content_end > longest.1 {
+                        longest = (line, content_end);
+                    }
+                    line += 1;
+                    column = 0;
+                    content_end = 0;
+                }
+                '\t' => column += tab_width - column % tab_width,
+                c if c.is_whitespace() => column += 1,
+                _ => {
+                    column += 1;
+                    content_end = column;
+                }
+            }
+            after_cr = c == '\r';
+        }
+    }
+
+    if content_end > longest.1 {
+        longest = (line, content_end);
+    }
+    longest
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemicolonStyle {
+    /// Statements end with `;`.
+    Always,
+    /// Statements rely on automatic semicolon insertion.
+    Never,
+    /// Both styles are used.
+    Mixed,
+}
+
+/// Finds whether statements end with explicit semicolons or rely on
+/// automatic semicolon insertion, from the number of each across the tokens.
+/// Tokens without statement ends are reported as `Always`.
+pub fn semicolon_style(tokens: &[Spanned]) -> SemicolonStyle {
+    let explicit = statement_semicolons(tokens).len();
+    let inserted = asi_points(tokens).len();
+    match (explicit, inserted) {
+        (_, 0) => SemicolonStyle::Always,
+        (0, _) => SemicolonStyle::Never,
+        _ => SemicolonStyle::Mixed,
+    }
+}
+
+/// Finds the indentation of each line starting with a token, returning its
+/// 1-based line number and the whitespace before the token, with tabs and
+/// spaces as written. Blank lines and lines starting within a comment or
+/// literal are left out.
+pub fn line_indentations(input: &str) -> Vec<(usize, &str)> {
+    let mut indentations = Vec::new();
+    let mut line = 1;
+    // Start of the current line while only whitespace has been seen on it.
+    let mut line_start = Some(0);
+
+    for spanned in tokenize_spanned(input) {
+        let is_whitespace = match spanned.token {
+            Token::Whitespace(_) => true,
+            _ => false,
+        };
+        if !is_whitespace {
+            if let Some(start) = line_start.take() {
+                indentations.push((line, &input[start..spanned.span.start]));
+            }
+        }
+
+        let text = spanned.token.as_str();
+        let mut chars = text.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\r' if chars.peek().map(|&(_, next)| next) == Some('\n') => {}
+                '\n' | '\r' | '\u{2028}' | '\u{2029}' => {
+                    line += 1;
+                    if is_whitespace {
+                        line_start = Some(spanned.span.start + index + c.len_utf8());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    indentations
+}
+
+/// Finds the 1-based numbers of lines whose indentation, as found by
+/// `line_indentations`, contains both tabs and spaces.
+pub fn find_mixed_indentation(input: &str) -> Vec<usize> {
+    line_indentations(input)
+        .into_iter()
+        .filter(|&(_, indentation)| indentation.contains('\t') && indentation.contains(' '))
+        .map(|(line, _)| line)
+        .collect()
+}
+
+/// Splits a block comment into its lines, returning the text of each with
+/// the `/*` and `*/` delimiters, the leading whitespace and the ` * `
+/// decoration of doc comments removed, along with the range of that text
+/// within the comment. Lines end at `\n` or `\r\n`. Other tokens have no
+/// lines.
+pub fn block_comment_lines<'a>(token: &Token<'a>) -> Vec<(Range<usize>, &'a str)> {
+    let comment = match *token {
+        Token::BlockComment(s) => s,
+        _ => return Vec::new(),
+    };
+    let end = if comment.len() >= 4 && comment.ends_with("*/") {
+        comment.len() - 2
+    } else {
+        comment.len()
+    };
+    // The stars of `/**` belong to the delimiter.
+    let mut start = 2;
+    while start < end && comment.as_bytes()[start] == b'*' {
+        start += 1;
+    }
+
+    let mut lines = Vec::new();
+    let mut line_start = start;
+    loop {
+        let line_end = comment[line_start..end].find('\n').map_or(end, |pos| line_start + pos);
+        let mut text_end = line_end;
+        if comment[line_start..text_end].ends_with('\r') {
+            text_end -= 1;
+        }
+        let indented = &comment[line_start..text_end];
+        let mut text_start = text_end -
+                             indented.trim_left_matches(|c| c == ' ' || c == '\t').len();
+        if line_start != start && comment[text_start..text_end].starts_with('*') {
+            text_start += 1;
+            if comment[text_start..text_end].starts_with(' ') {
+                text_start += 1;
+            }
+        }
+        lines.push((text_start..text_end, &comment[text_start..text_end]));
+
+        if line_end == end {
+            return lines;
+        }
+        line_start = line_end + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::{tokenize, tokenize_spanned};
+
+    #[test]
+    fn sexpr() {
+        assert_eq!(to_sexpr(&tokenize("a = 1")), r#"(ident "a") (eq) (num "1")"#);
+        assert_eq!(to_sexpr(&tokenize("x=== 'y' // z")),
+                   r#"(ident "x") (rel "===") (str "'y'") (line-comment "// z")"#);
+        assert_eq!(to_sexpr(&tokenize("")), "");
+    }
+
+    fn format(input: &str) -> String {
+        format_tokens(&tokenize(input), &FormatStyle::default())
+    }
+
+    #[test]
+    fn indentation_of_mixed_lines() {
+        let input = "function f(a) {\n\
+                     \tif (a) {\n\
+                     \t    return 1;\n\
+                     \n\
+                     \x20   }\r\n\
+                     \x20 \t// done\n\
+                     }";
+        assert_eq!(line_indentations(input),
+                   vec![(1, ""), (2, "\t"), (3, "\t    "), (5, "    "), (6, "  \t"), (7, "")]);
+    }
+
+    #[test]
+    fn indentation_skips_multiline_tokens() {
+        assert_eq!(line_indentations("  a = `\n  b`;\n/*\n * c\n */ d"),
+                   vec![(1, "  "), (3, "")]);
+        assert_eq!(line_indentations(""), vec![]);
+    }
+
+    #[test]
+    fn mixed_indentation() {
+        let input = "a;\n \tb;\n\t\tc;\n    d;\n\t e;\n`\n \t`;";
+        assert_eq!(find_mixed_indentation(input), vec![2, 5]);
+        assert_eq!(find_mixed_indentation("\t\ta;\n    b;"), vec![]);
+    }
+
+    fn style(input: &str) -> SemicolonStyle {
+        semicolon_style(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn semicolons_always() {
+        assert_eq!(style("var a = 1;\nfor (var i = 0; i < 2; i++) {\n    f(i);\n}\n\
+                          function g() { return {}; }\n"),
+                   SemicolonStyle::Always);
+        assert_eq!(style(""), SemicolonStyle::Always);
+    }
+
+    #[test]
+    fn semicolons_never() {
+        assert_eq!(style("var a = 1\nfor (var i = 0; i < 2; i++) {\n    f(i)\n}\n\
+                          function g() { return {} }\n"),
+                   SemicolonStyle::Never);
+    }
+
+    #[test]
+    fn semicolons_mixed() {
+        assert_eq!(style("a = 1;\nb = 2\n"), SemicolonStyle::Mixed);
+        assert_eq!(style("if (a) { b(); } else { c() }"), SemicolonStyle::Mixed);
+    }
+
+    #[test]
+    fn longest_line_in_sample() {
+        let input = "function f(a) {\n\
+                     \treturn a + 1;   \n\
+                     }\n\
+                     // éééééééééééééééé\n";
+        assert_eq!(longest_line(input), (4, 19));
+        assert_eq!(longest_line_with_tab_width(input, 8), (2, 21));
+    }
+
+    #[test]
+    fn longest_line_edge_cases() {
+        assert_eq!(longest_line(""), (1, 0));
+        assert_eq!(longest_line("ab\r\nabc\rab"), (2, 3));
+        assert_eq!(longest_line("x = `a\nbcdefg`"), (2, 7));
+        assert_eq!(longest_line("a  \t\nb"), (1, 1));
+        assert_eq!(longest_line("ab\tc"), (1, 5));
+    }
+
+    #[test]
+    fn line_ending_lf() {
+        assert_eq!(detect_line_ending("a\nb\n\n/*\n*/"), LineEnding::Lf);
+        assert_eq!(detect_line_ending("a"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn line_ending_crlf() {
+        assert_eq!(detect_line_ending("a\r\nb // c\r\n/*\r\n*/"), LineEnding::CrLf);
+        assert_eq!(LineEnding::CrLf.as_str(), Some("\r\n"));
+    }
+
+    #[test]
+    fn line_ending_cr() {
+        assert_eq!(detect_line_ending("a\rb\r"), LineEnding::Cr);
+    }
+
+    #[test]
+    fn line_ending_mixed() {
+        assert_eq!(detect_line_ending("a\nb\r\nc"), LineEnding::Mixed);
+        assert_eq!(detect_line_ending("a\r\n/* \n */"), LineEnding::Mixed);
+        assert_eq!(LineEnding::Mixed.as_str(), None);
+    }
+
+    #[test]
+    fn normalize_numeric_prefixes() {
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0XfF")), "0xff");
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0B1010")), "0b1010");
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0O17")), "0o17");
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0x0n")), "0x0n");
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0b0n")), "0b0n");
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0o_0n")), "0o0n");
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0X00N")), "0x0n");
+    }
+
+    #[test]
+    fn normalize_numeric_leading_zeros() {
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0x00Ab")), "0xab");
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0b000")), "0b0");
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("0x00_0F")), "0xf");
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("010")), "010");
+    }
+
+    #[test]
+    fn normalize_quotes_requotes() {
+        let double = |s| normalize_quotes(&Token::StringLiteral(s), QuoteStyle::Double).unwrap();
+        assert_eq!(double("'abc'"), "\"abc\"");
+        assert_eq!(double(r#"'it\'s'"#), r#""it's""#);
+        assert_eq!(double(r#"'a \"b\" \n\\'"#), r#""a \"b\" \n\\""#);
+        assert_eq!(normalize_quotes(&Token::StringLiteral(r#""say \"hi\" 'x'""#),
+                                    QuoteStyle::Single).unwrap(),
+                   r#"'say "hi" \'x\''"#);
+    }
+
+    #[test]
+    fn normalize_quotes_keeps_literal() {
+        let double = |s| normalize_quotes(&Token::StringLiteral(s), QuoteStyle::Double).unwrap();
+        assert_eq!(double(r#"'a "b"'"#), r#"'a "b"'"#);
+        assert_eq!(double(r#""a""#), r#""a""#);
+        assert_eq!(double("'abc"), "'abc");
+        assert_eq!(normalize_quotes(&Token::Identifier("a"), QuoteStyle::Single), None);
+    }
+
+    fn needs_space(a: &str, b: &str) -> bool {
+        let (a, b) = (tokenize(a), tokenize(b));
+        needs_separating_space(&a[1], &b[1])
+    }
+
+    #[test]
+    fn separating_space_between_words() {
+        assert!(needs_space("return", "x"));
+        assert!(needs_space("1", "in"));
+        assert!(needs_space("a", "\\u0061"));
+        assert!(needs_space("/a/", "in"));
+        assert!(!needs_space(")", "{"));
+        assert!(!needs_space("a", "("));
+        assert!(!needs_space("'a'", "in"));
+    }
+
+    #[test]
+    fn separating_space_between_punctuators() {
+        assert!(needs_space("+", "+"));
+        assert!(needs_space("+", "++"));
+        assert!(needs_space("=", "=="));
+        assert!(needs_space("=", ">"));
+        assert!(needs_separating_space(&Token::Slash, &Token::RegexLiteral("/a/")));
+        assert!(needs_space("?", "."));
+        assert!(needs_space("--", ">"));
+        assert!(needs_space("<", "!"));
+        assert!(!needs_space("-", ">"));
+        assert!(!needs_space("-", "+"));
+        assert!(!needs_space("!", "!"));
+        assert!(!needs_space(";", "-"));
+    }
+
+    #[test]
+    fn separating_space_around_dots() {
+        assert!(needs_space("1", "."));
+        assert!(needs_space(".", "5"));
+        assert!(needs_space(".", "..."));
+        assert!(!needs_space("1.5", "."));
+        assert!(!needs_space("0x1", "."));
+        assert!(!needs_space("a", "."));
+    }
+
+    fn foldable(input: &str) -> Vec<&str> {
+        find_foldable_concats(&tokenize_spanned(input))
+            .into_iter()
+            .map(|range| &input[range])
+            .collect()
+    }
+
+    #[test]
+    fn foldable_concats() {
+        assert_eq!(foldable(r#"x = "a" + "b";"#), vec![r#""a" + "b""#]);
+        assert_eq!(foldable("f(\"a\" +\n    'b' + \"c\", \"d\")"),
+                   vec!["\"a\" +\n    'b' + \"c\""]);
+        assert_eq!(foldable(r#""a" + "b" + x"#), vec![r#""a" + "b""#]);
+    }
+
+    #[test]
+    fn unfoldable_concats() {
+        assert_eq!(foldable(r#""a" + x + "b""#), Vec::<&str>::new());
+        assert_eq!(foldable(r#"x + "a" + "b""#), Vec::<&str>::new());
+        assert_eq!(foldable(r#""a" + "b".length; typeof "a" + "b""#), Vec::<&str>::new());
+        assert_eq!(foldable(r#""a"; "b" - "c""#), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn block_comment_doc_lines() {
+        let comment = "/**\n * Adds numbers.\r\n *\n *     a + b\n * @param a first\n */";
+        let lines = block_comment_lines(&Token::BlockComment(comment));
+        assert_eq!(lines.iter().map(|&(_, text)| text).collect::<Vec<_>>(),
+                   vec!["", "Adds numbers.", "", "    a + b", "@param a first", ""]);
+        assert_eq!(lines[1].0, 7..20);
+        for (range, text) in lines {
+            assert_eq!(&comment[range], text);
+        }
+    }
+
+    #[test]
+    fn block_comment_plain_lines() {
+        assert_eq!(block_comment_lines(&Token::BlockComment("/* a */")), vec![(3..5, "a ")]);
+        assert_eq!(block_comment_lines(&Token::BlockComment("/*a\n  b*/")),
+                   vec![(2..3, "a"), (6..7, "b")]);
+        assert_eq!(block_comment_lines(&Token::BlockComment("/**/")), vec![(2..2, "")]);
+        assert_eq!(block_comment_lines(&Token::BlockComment("/* a")), vec![(3..4, "a")]);
+        assert_eq!(block_comment_lines(&Token::LineComment("// a")), vec![]);
+    }
+
+    #[test]
+    fn normalize_numeric_unchanged() {
+        assert!(match normalize_numeric(&Token::NumericLiteral("1.5e3")) {
+            Cow::Borrowed("1.5e3") => true,
+            _ => false,
+        });
+        assert_eq!(normalize_numeric(&Token::NumericLiteral("1E3")), "1e3");
+        assert_eq!(normalize_numeric(&Token::Identifier("ABC")), "ABC");
+    }
+
+    #[test]
+    fn format_function() {
+        assert_eq!(format("function f(a,b){return a+b}"),
+                   "function f(a, b) {\n    return a + b\n}");
+    }
+
+    #[test]
+    fn format_statements() {
+        assert_eq!(format("a=[ 1,2 ];b( c ) ;"), "a = [1, 2];\nb(c);");
+    }
+
+    #[test]
+    fn format_unary_and_update() {
+        assert_eq!(format("a= -b;i ++;x=!y"), "a = -b;\ni++;\nx = !y");
+    }
+
+    #[test]
+    fn format_keeps_asi_line_breaks() {
+        assert_eq!(format("a = b\nc = d"), "a = b\nc = d");
+        assert_eq!(format("return\na"), "return\na");
+        assert_eq!(format("a = b\n(c)"), "a = b(c)");
+    }
+
+    #[test]
+    fn format_binary_after_values() {
+        assert_eq!(format("this-1"), "this - 1");
+        assert_eq!(format("a++-b"), "a++ - b");
+        assert_eq!(format("x = true-1; y = null+1"), "x = true - 1;\ny = null + 1");
+        assert_eq!(format("a = -b; c = ++d"), "a = -b;\nc = ++d");
+    }
+
+    #[test]
+    fn format_unbalanced_braces() {
+        assert_eq!(format("}a"), "}\na");
+    }
+
+    #[test]
+    fn format_blocks() {
+        assert_eq!(format("if (a) {} else {b=c?d:e;}"),
+                   "if (a) {} else {\n    b = c ? d : e;\n}");
+    }
+
+    #[test]
+    fn format_custom_indent() {
+        let style = FormatStyle { indent: String::from("\t") };
+        assert_eq!(format_tokens(&tokenize("x={a:{b:1}}"), &style),
+                   "x = {\n\ta: {\n\t\tb: 1\n\t}\n}");
+    }
+}
@@ -0,0 +1,31 @@
+#![feature(test)]
+#![feature(plugin)]
+
+#![plugin(clippy)]
+
+extern crate test;
+extern crate crossbeam;
+extern crate memchr;
+#[cfg(feature = "smallvec")]
+extern crate smallvec;
+
+pub mod tokenizer;
+pub mod diff;
+pub mod format;
+pub mod lint;
+pub mod parallel;
+pub mod prelude;
+pub mod search;
+pub mod shebang;
+pub mod stats;
+pub mod strings;
+pub mod structure;
+pub mod visit;
+
+pub use tokenizer::{tokenize, tokenize_spanned, tokenize_with_options, Spanned, Token,
+                    TokenOp, TokenizeError, TokenizeOptions};
+
+#[cfg(test)]
+mod spec;
+#[cfg(test)]
+mod test_support;
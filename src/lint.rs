@@ -0,0 +1,1288 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use strings::string_value;
+use structure::{asi_points, chain_end, chain_start, find_destructuring_patterns, has_line_break,
+                is_object_literal, matching_bracket, meaningful, next_meaningful,
+                prev_meaningful};
+use tokenizer::{is_contextual_keyword, is_keyword, tokenize_spanned, Operator, Spanned, Token,
+                TokenOp};
+
+struct FunctionScope {
+    is_async: bool,
+    // Bracket depth of the scope's contents; the scope ends once a closing
+    // bracket takes the depth below this.
+    depth: usize,
+    // Arrow functions with an expression body also end at a `,` or `;` at
+    // their own depth.
+    is_expression: bool,
+}
+
+/// Finds `await` tokens that aren't lexically inside an async function body.
+///
+/// Function bodies are recognized after `function`, `=>` and, when preceded
+/// by `async`, any `{` (which covers async methods). This is a heuristic: a
+/// non-async method body is treated like a plain block and so inherits the
+/// asyncness of its surroundings, and top-level `await` is always reported
+/// even though modules allow it.
+pub fn find_await_outside_async(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let tokens = meaningful(tokens);
+    let mut found = Vec::new();
+    let mut scopes: Vec<FunctionScope> = Vec::new();
+    let mut depth = 0;
+    let mut async_seen = false;
+    let mut pending_body: Option<bool> = None;
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        let token = &spanned.token;
+        match *token {
+            Token::LeftBrace |
+            Token::LeftParen |
+            Token::LeftBracket => {
+                depth += 1;
+                if *token == Token::LeftBrace {
+                    let inherited = scopes.last().map_or(false, |scope| scope.is_async);
+                    let is_async = pending_body.unwrap_or(async_seen || inherited);
+                    scopes.push(FunctionScope {
+                        is_async: is_async,
+                        depth: depth,
+                        is_expression: false,
+                    });
+                    pending_body = None;
+                    async_seen = false;
+                }
+            }
+            Token::RightBrace |
+            Token::RightParen |
+            Token::RightBracket => {
+                if depth > 0 {
+                    depth -= 1;
+                }
+                while scopes.last().map_or(false, |scope| scope.depth > depth) {
+                    scopes.pop();
+                }
+            }
+            Token::Comma | Token::Semicolon => {
+                while scopes.last().map_or(false, |scope| scope.is_expression && scope.depth == depth) {
+                    scopes.pop();
+                }
+                if *token == Token::Semicolon {
+                    async_seen = false;
+                }
+            }
+            Token::Arrow => {
+                let is_async = async_seen;
+                async_seen = false;
+                match tokens.get(index + 1) {
+                    Some(next) if next.token == Token::LeftBrace => pending_body = Some(is_async),
+                    _ => {
+                        scopes.push(FunctionScope {
+                            is_async: is_async,
+                            depth: depth,
+                            is_expression: true,
+                        })
+                    }
+                }
+            }
+            _ if token.is_word("async") => async_seen = true,
+            _ if token.is_word("function") => {
+                pending_body = Some(async_seen);
+                async_seen = false;
+            }
+            _ if token.is_word("await") => {
+                if !scopes.last().map_or(false, |scope| scope.is_async) {
+                    found.push(spanned.span.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    found
+}
+
+fn starts_with_slash(token: &Token) -> bool {
+    match *token {
+        Token::Slash |
+        Token::UpdateAssignment(Operator::Divide) |
+        Token::RegexLiteral(_) => true,
+        _ => false,
+    }
+}
+
+/// Finds slashes where the tokenizer had to guess between division and a
+/// regular expression: after `)` (`if (a) /re/` vs `(a) / b`), after `}`
+/// (a block vs an object literal) and after contextual keywords like
+/// `await` or `yield`, which may or may not be operators. A slash after `]`
+/// or any other identifier is always division, so isn't reported.
+///
+/// Each range covers the slash itself, even when it starts a regex literal.
+pub fn find_ambiguous_slashes(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let tokens = meaningful(tokens);
+    let mut found = Vec::new();
+
+    for pair in tokens.windows(2) {
+        let ambiguous = match pair[0].token {
+            Token::RightParen | Token::RightBrace => true,
+            Token::Identifier(s) | Token::Keyword(s) => is_contextual_keyword(s) || s == "yield",
+            _ => false,
+        };
+        if ambiguous && starts_with_slash(&pair[1].token) {
+            let start = pair[1].span.start;
+            found.push(start..start + 1);
+        }
+    }
+
+    found
+}
+
+/// Finds `a?.b ?? fallback` expressions, where an optional chain is
+/// defaulted with nullish coalescing. Each range runs from the start of the
+/// optional chain to the end of the fallback, which is taken to be a single
+/// member access or call chain.
+pub fn find_null_safe_accesses(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        if spanned.token != Token::NullishCoalescing {
+            continue;
+        }
+        let (left, right) = match (prev_meaningful(tokens, index), next_meaningful(tokens, index)) {
+            (Some(left), Some(right)) => (left, right),
+            _ => continue,
+        };
+
+        let start = chain_start(tokens, left);
+        if tokens[start..left].iter().any(|spanned| spanned.token == Token::OptionalChain) {
+            let end = chain_end(tokens, right);
+            found.push(tokens[start].span.start..tokens[end].span.end);
+        }
+    }
+
+    found
+}
+
+// Index of the last token of the statement starting at `start`: the closing
+// brace of a block, or else the first `;` outside brackets. Without a `;`,
+// the statement runs until an unmatched closing bracket or the end.
+fn statement_end(tokens: &[Spanned], start: usize) -> usize {
+    if tokens[start].token == Token::LeftBrace {
+        return matching_bracket(tokens, start).unwrap_or(tokens.len() - 1);
+    }
+
+    let mut index = start;
+    let mut end = start;
+    while index < tokens.len() {
+        match tokens[index].token {
+            Token::Semicolon => return index,
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                index = match matching_bracket(tokens, index) {
+                    Some(close) => close,
+                    None => return tokens.len() - 1,
+                };
+            }
+            Token::RightParen | Token::RightBracket | Token::RightBrace => return end,
+            _ => {}
+        }
+        if !tokens[index].token.is_greyspace() {
+            end = index;
+        }
+        index += 1;
+    }
+    end
+}
+
+// The operators seen so far in an expression, up to the next token that
+// separates its operands from those of another.
+#[derive(Default)]
+struct LogicalOperands {
+    nullish: Vec<Range<usize>>,
+    has_logical: bool,
+}
+
+impl LogicalOperands {
+    // Reports the `??` operators if they were mixed with `||` or `&&`.
+    fn finish(&mut self, found: &mut Vec<Range<usize>>) {
+        if self.has_logical {
+            found.append(&mut self.nullish);
+        } else {
+            self.nullish.clear();
+        }
+        self.has_logical = false;
+    }
+}
+
+/// Finds `??` operators mixed with `||` or `&&` in the same expression
+/// without parentheses, as in `a ?? b || c`, which is a syntax error. The
+/// range of each such `??` is returned.
+pub fn find_nullish_mixing_errors(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let asi = asi_points(tokens);
+    let mut found = Vec::new();
+    let mut levels = vec![LogicalOperands::default()];
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        match spanned.token {
+            Token::NullishCoalescing => {
+                levels.last_mut().unwrap().nullish.push(spanned.span.clone());
+            }
+            Token::LogicalOr | Token::LogicalAnd => levels.last_mut().unwrap().has_logical = true,
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                levels.push(LogicalOperands::default());
+            }
+            Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                levels.last_mut().unwrap().finish(&mut found);
+                if levels.len() > 1 {
+                    levels.pop();
+                }
+            }
+            // Conditional and assignment operators bind looser than `??`,
+            // and these others end the expression.
+            Token::Comma |
+            Token::Semicolon |
+            Token::QuestionMark |
+            Token::Colon |
+            Token::Arrow |
+            Token::Equal |
+            Token::UpdateAssignment(_) => levels.last_mut().unwrap().finish(&mut found),
+            _ => {}
+        }
+        if asi.binary_search(&index).is_ok() {
+            levels.last_mut().unwrap().finish(&mut found);
+        }
+    }
+    for level in &mut levels {
+        level.finish(&mut found);
+    }
+
+    found.sort_by_key(|range| range.start);
+    found
+}
+
+/// Finds `with` statements, which strict mode disallows. Each range runs
+/// from `with` to the end of the statement's body.
+pub fn find_with_statements(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        if !spanned.token.is_word("with") {
+            continue;
+        }
+        // `o.with(x)` is a method call.
+        let is_property = match prev_meaningful(tokens, index).map(|prev| &tokens[prev].token) {
+            Some(&Token::Dot) | Some(&Token::OptionalChain) => true,
+            _ => false,
+        };
+        let head = match next_meaningful(tokens, index) {
+            Some(head) if !is_property && tokens[head].token == Token::LeftParen => head,
+            _ => continue,
+        };
+
+        let body = matching_bracket(tokens, head).and_then(|close| next_meaningful(tokens, close));
+        let end = match body {
+            Some(body) => statement_end(tokens, body),
+            None => tokens.len() - 1,
+        };
+        found.push(spanned.span.start..tokens[end].span.end);
+    }
+
+    found
+}
+
+/// Finds calls that run code built at runtime: `eval(...)` and
+/// `new Function(...)`. Each range runs from `eval` or `new` to the closing
+/// parenthesis of the call, or to the end when it is unclosed.
+pub fn find_dynamic_eval(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        let callee = if spanned.token.is_word("new") {
+            match next_meaningful(tokens, index) {
+                Some(next) if tokens[next].token == Token::Identifier("Function") => next,
+                _ => continue,
+            }
+        } else if spanned.token == Token::Identifier("eval") {
+            index
+        } else {
+            continue;
+        };
+        let open = match next_meaningful(tokens, callee) {
+            Some(open) if tokens[open].token == Token::LeftParen => open,
+            _ => continue,
+        };
+
+        let end = matching_bracket(tokens, open).unwrap_or(tokens.len() - 1);
+        found.push(spanned.span.start..tokens[end].span.end);
+    }
+
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HazardKind {
+    /// A `with` statement, as found by `find_with_statements`.
+    With,
+    /// An `eval(...)` or `new Function(...)` call, as found by
+    /// `find_dynamic_eval`.
+    DynamicEval,
+    /// An assignment to, or increment or decrement of, `arguments` or
+    /// `eval`.
+    RestrictedAssignment,
+}
+
+/// A construct that strict mode disallows or makes behave differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hazard {
+    pub kind: HazardKind,
+    pub span: Range<usize>,
+}
+
+// Spans of `arguments` and `eval` where they are assigned to, as in
+// `arguments = []` or `++eval`.
+fn find_restricted_assignments(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        match spanned.token {
+            Token::Identifier("arguments") | Token::Identifier("eval") => {}
+            _ => continue,
+        }
+        let prev = prev_meaningful(tokens, index);
+        let is_property = match prev.map(|prev| &tokens[prev].token) {
+            Some(&Token::Dot) | Some(&Token::OptionalChain) => true,
+            _ => false,
+        };
+        // A `++` on an earlier line is a postfix operator on what precedes it.
+        let is_prefixed = prev.map_or(false, |prev| {
+            match tokens[prev].token {
+                Token::DeIncrement(_) => {
+                    !tokens[prev + 1..index].iter().any(|between| has_line_break(&between.token))
+                }
+                _ => false,
+            }
+        });
+        let is_assigned = next_meaningful(tokens, index).map_or(false, |next| {
+            match tokens[next].token {
+                Token::DeIncrement(_) => true,
+                ref token => token.is_assignment_operator(),
+            }
+        });
+        if !is_property && (is_prefixed || is_assigned) {
+            found.push(spanned.span.clone());
+        }
+    }
+
+    found
+}
+
+/// Finds everything in the way of moving code to strict mode in one pass:
+/// `with` statements, `eval(...)` and `new Function(...)` calls, and
+/// assignments to `arguments` or `eval`, in order of their start.
+pub fn find_sloppy_hazards(tokens: &[Spanned]) -> Vec<Hazard> {
+    let found = find_with_statements(tokens)
+                    .into_iter()
+                    .map(|span| (HazardKind::With, span))
+                    .chain(find_dynamic_eval(tokens)
+                               .into_iter()
+                               .map(|span| (HazardKind::DynamicEval, span)))
+                    .chain(find_restricted_assignments(tokens)
+                               .into_iter()
+                               .map(|span| (HazardKind::RestrictedAssignment, span)));
+    let mut hazards: Vec<Hazard> = found.map(|(kind, span)| Hazard { kind: kind, span: span })
+                                        .collect();
+    hazards.sort_by_key(|hazard| hazard.span.start);
+    hazards
+}
+
+/// Finds what shouldn't ship in a production build: `console.<method>(...)`
+/// calls and `debugger` statements. Each range runs from `console` to the
+/// closing parenthesis of the call, or covers `debugger` and its semicolon.
+pub fn find_debug_statements(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        let is_property = match prev_meaningful(tokens, index).map(|prev| &tokens[prev].token) {
+            Some(&Token::Dot) | Some(&Token::OptionalChain) => true,
+            _ => false,
+        };
+        if is_property {
+            continue;
+        }
+
+        if spanned.token.is_word("debugger") {
+            let end = match next_meaningful(tokens, index) {
+                Some(next) if tokens[next].token == Token::Semicolon => next,
+                _ => index,
+            };
+            found.push(spanned.span.start..tokens[end].span.end);
+        } else if spanned.token == Token::Identifier("console") {
+            let dot = match next_meaningful(tokens, index) {
+                Some(dot) if tokens[dot].token == Token::Dot => dot,
+                _ => continue,
+            };
+            let method = match next_meaningful(tokens, dot) {
+                Some(method) if tokens[method].token.op() == TokenOp::Identifier => method,
+                _ => continue,
+            };
+            let open = match next_meaningful(tokens, method) {
+                Some(open) if tokens[open].token == Token::LeftParen => open,
+                _ => continue,
+            };
+            let end = matching_bracket(tokens, open).unwrap_or(tokens.len() - 1);
+            found.push(spanned.span.start..tokens[end].span.end);
+        }
+    }
+
+    found
+}
+
+/// Finds plain assignments in the condition of an `if`, `while` or `for`
+/// statement, as in `if (a = b)`, which are usually a mistyped comparison.
+/// Only the test between the semicolons of a `for` is checked. Assignments
+/// nested in parentheses, as in `while ((a = next()))`, are taken to be
+/// intentional. Each range is that of the `=`.
+pub fn find_assignment_in_condition(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        let is_for = spanned.token.is_word("for");
+        if !is_for && !spanned.token.is_word("if") && !spanned.token.is_word("while") {
+            continue;
+        }
+        let is_property = match prev_meaningful(tokens, index).map(|prev| &tokens[prev].token) {
+            Some(&Token::Dot) | Some(&Token::OptionalChain) => true,
+            _ => false,
+        };
+        let open = match next_meaningful(tokens, index) {
+            Some(open) if !is_property && tokens[open].token == Token::LeftParen => open,
+            _ => continue,
+        };
+        let close = match matching_bracket(tokens, open) {
+            Some(close) => close,
+            None => continue,
+        };
+
+        // Top-level `=`s, along with how many semicolons precede each.
+        let mut semicolons = 0;
+        let mut equals = Vec::new();
+        let mut inner = open + 1;
+        while inner < close {
+            match tokens[inner].token {
+                Token::Semicolon => semicolons += 1,
+                Token::Equal => equals.push((semicolons, inner)),
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                    inner = matching_bracket(tokens, inner).unwrap_or(close);
+                }
+                _ => {}
+            }
+            inner += 1;
+        }
+        // A `for` without two semicolons is a `for`-`in` or `for`-`of`.
+        let test = if is_for { 1 } else { 0 };
+        if is_for && semicolons != 2 {
+            continue;
+        }
+        found.extend(equals.into_iter()
+                           .filter(|&(part, _)| part == test)
+                           .map(|(_, equal)| tokens[equal].span.clone()));
+    }
+
+    found
+}
+
+/// Finds places where a line break makes automatic semicolon insertion end a
+/// statement early: `return`, `throw`, `break` and `continue` followed on a
+/// later line by what was meant as their operand or label, as in
+/// `return\n  value;`, and `++` or `--` starting a line after an operand,
+/// which makes them apply to the next line instead. Each range is that of
+/// the keyword or operator.
+pub fn find_return_asi_hazards(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let line_break_between = |first: usize, second: usize| {
+        tokens[first + 1..second].iter().any(|spanned| has_line_break(&spanned.token))
+    };
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        let token = &spanned.token;
+        let prev = prev_meaningful(tokens, index).map(|prev| (prev, &tokens[prev].token));
+        let hazard = if let Token::DeIncrement(_) = *token {
+            match prev {
+                Some((prev, &Token::Identifier(s))) => {
+                    !is_keyword(s) && line_break_between(prev, index)
+                }
+                Some((prev, &Token::PrivateIdentifier(_))) |
+                Some((prev, &Token::RightParen)) |
+                Some((prev, &Token::RightBracket)) => line_break_between(prev, index),
+                _ => false,
+            }
+        } else if ["return", "throw", "break", "continue"].iter().any(|&w| token.is_word(w)) {
+            let is_property = match prev {
+                Some((_, &Token::Dot)) | Some((_, &Token::OptionalChain)) => true,
+                _ => false,
+            };
+            let next = next_meaningful(tokens, index).map(|next| (next, &tokens[next].token));
+            match next {
+                Some((_, &Token::Semicolon)) | Some((_, &Token::RightBrace)) | None => false,
+                Some((next, _)) => !is_property && line_break_between(index, next),
+            }
+        } else {
+            false
+        };
+        if hazard {
+            found.push(spanned.span.clone());
+        }
+    }
+
+    found
+}
+
+// Index of the last token of the statement starting at `start`, which
+// ends at a `;`, where a semicolon would be inserted, or before an
+// unmatched closing bracket.
+fn simple_statement_end(tokens: &[Spanned], start: usize, asi: &[usize]) -> usize {
+    let mut index = start;
+    loop {
+        let end = match tokens[index].token {
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                matching_bracket(tokens, index).unwrap_or(tokens.len() - 1)
+            }
+            _ => index,
+        };
+        if tokens[end].token == Token::Semicolon || asi.binary_search(&end).is_ok() {
+            return end;
+        }
+        match next_meaningful(tokens, end) {
+            Some(next) => {
+                match tokens[next].token {
+                    Token::RightParen | Token::RightBracket | Token::RightBrace => return end,
+                    _ => index = next,
+                }
+            }
+            None => return end,
+        }
+    }
+}
+
+// Index of the closing brace of the function or class declaration starting
+// at `start`, if one does.
+fn declaration_end(tokens: &[Spanned], start: usize) -> Option<usize> {
+    let mut index = start;
+    if tokens[index].token.is_word("async") {
+        index = match next_meaningful(tokens, index) {
+            Some(next) if tokens[next].token.is_word("function") => next,
+            _ => return None,
+        };
+    }
+    if !tokens[index].token.is_word("function") && !tokens[index].token.is_word("class") {
+        return None;
+    }
+    loop {
+        index = match next_meaningful(tokens, index) {
+            Some(next) => next,
+            None => return None,
+        };
+        match tokens[index].token {
+            Token::LeftParen | Token::LeftBracket => {
+                index = match matching_bracket(tokens, index) {
+                    Some(close) => close,
+                    None => return None,
+                };
+            }
+            Token::LeftBrace => return matching_bracket(tokens, index),
+            Token::RightBrace | Token::Semicolon => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Finds code that can never run because it follows a `return`, `throw`,
+/// `break` or `continue` statement in the same block, as in
+/// `{ return x; y(); }`. Each range runs from the first unreachable
+/// statement to the end of the block, or to the next `case` or `default`
+/// of a `switch`. Function and class declarations directly after the jump
+/// are skipped, since they may be used before it. A jump that is the body
+/// of an `if`, `else` or loop, as in `if (a) return;`, doesn't count.
+pub fn find_unreachable_after_jump(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let asi = asi_points(tokens);
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        let token = &spanned.token;
+        if !["return", "throw", "break", "continue"].iter().any(|&word| token.is_word(word)) {
+            continue;
+        }
+        let starts_statement = match prev_meaningful(tokens, index) {
+            None => true,
+            Some(prev) => {
+                match tokens[prev].token {
+                    Token::LeftBrace | Token::RightBrace | Token::Semicolon | Token::Colon => true,
+                    _ => asi.binary_search(&prev).is_ok(),
+                }
+            }
+        };
+        if !starts_statement {
+            continue;
+        }
+
+        let mut next = next_meaningful(tokens, simple_statement_end(tokens, index, &asi));
+        while let Some(end) = next.and_then(|start| declaration_end(tokens, start)) {
+            next = next_meaningful(tokens, end);
+        }
+        let first = match next {
+            Some(first) => first,
+            None => continue,
+        };
+
+        let mut last = None;
+        let mut current = Some(first);
+        while let Some(index) = current {
+            let token = &tokens[index].token;
+            let end = match *token {
+                Token::RightParen | Token::RightBracket | Token::RightBrace => break,
+                _ if token.is_word("case") || token.is_word("default") => break,
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                    matching_bracket(tokens, index).unwrap_or(tokens.len() - 1)
+                }
+                _ => index,
+            };
+            last = Some(end);
+            current = next_meaningful(tokens, end);
+        }
+        if let Some(last) = last {
+            found.push(tokens[first].span.start..tokens[last].span.end);
+        }
+    }
+
+    found
+}
+
+/// Finds `break` and `continue` statements with a label, as in
+/// `break outer;`. A line break after the keyword ends the statement, so
+/// `break\nouter;` has no label. Each range runs from the keyword to the
+/// end of the label.
+pub fn find_labeled_jumps(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        if !spanned.token.is_word("break") && !spanned.token.is_word("continue") {
+            continue;
+        }
+        let is_property = match prev_meaningful(tokens, index).map(|prev| &tokens[prev].token) {
+            Some(&Token::Dot) | Some(&Token::OptionalChain) => true,
+            _ => false,
+        };
+        let label = match next_meaningful(tokens, index) {
+            Some(next) if !is_property => next,
+            _ => continue,
+        };
+        let is_label = match tokens[label].token {
+            Token::Identifier(s) => !is_keyword(s),
+            _ => false,
+        };
+        let line_break = tokens[index + 1..label].iter().any(|spanned| {
+            has_line_break(&spanned.token)
+        });
+        if is_label && !line_break {
+            found.push(spanned.span.start..tokens[label].span.end);
+        }
+    }
+
+    found
+}
+
+/// Finds blocks with nothing but whitespace and comments inside, like an
+/// empty `catch {}` or function body. Empty object literals are skipped.
+/// Each range covers the braces.
+pub fn find_empty_blocks(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        if spanned.token != Token::LeftBrace || is_object_literal(tokens, index) {
+            continue;
+        }
+        if let Some(close) = matching_bracket(tokens, index) {
+            if next_meaningful(tokens, index) == Some(close) {
+                found.push(spanned.span.start..tokens[close].span.end);
+            }
+        }
+    }
+
+    found
+}
+
+// The name a property key token stands for, if it is a plain key.
+fn key_name(token: &Token) -> Option<String> {
+    match *token {
+        Token::Identifier(s) | Token::Keyword(s) | Token::NumericLiteral(s) => Some(s.to_owned()),
+        Token::StringLiteral(s) => string_value(s, false).ok().map(|value| value.into_owned()),
+        _ => None,
+    }
+}
+
+/// Finds keys given more than once in the same object literal, as in
+/// `{a: 1, a: 2}`, returning the span of each repeated key. Only keys
+/// followed by `:` count, so shorthand properties, methods and accessors are
+/// ignored, as are computed keys like `[a]`. Identifier and string keys
+/// naming the same property match. Destructuring patterns are skipped.
+pub fn find_duplicate_keys(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let patterns = find_destructuring_patterns(tokens);
+    let mut found = Vec::new();
+
+    for (open, spanned) in tokens.iter().enumerate() {
+        if spanned.token != Token::LeftBrace || !is_object_literal(tokens, open) ||
+           patterns.iter().any(|pattern| pattern.start == spanned.span.start) {
+            continue;
+        }
+        let close = match matching_bracket(tokens, open) {
+            Some(close) => close,
+            None => tokens.len(),
+        };
+
+        let mut keys = HashSet::new();
+        let mut property_start = true;
+        let mut index = open + 1;
+        while index < close {
+            let token = &tokens[index].token;
+            if token.is_greyspace() {
+                index += 1;
+                continue;
+            }
+            if property_start {
+                let is_key = next_meaningful(tokens, index).map_or(false, |next| {
+                    tokens[next].token == Token::Colon
+                });
+                if let (true, Some(name)) = (is_key, key_name(token)) {
+                    if !keys.insert(name) {
+                        found.push(tokens[index].span.clone());
+                    }
+                }
+            }
+            property_start = *token == Token::Comma;
+            index = match *token {
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                    matching_bracket(tokens, index).unwrap_or(close)
+                }
+                _ => index,
+            } + 1;
+        }
+    }
+
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectiveKind {
+    /// `eslint-disable`, lasting until a matching `eslint-enable` or the end
+    /// of the file.
+    Disable,
+    /// `eslint-enable`.
+    Enable,
+    /// `eslint-disable-next-line`.
+    DisableNextLine,
+    /// `eslint-disable-line`, applying to the line the comment is on.
+    DisableLine,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintDirective<'a> {
+    pub kind: DirectiveKind,
+    /// The rules named by the directive; empty when it applies to all rules.
+    pub rules: Vec<&'a str>,
+    /// Span of the comment holding the directive.
+    pub span: Range<usize>,
+}
+
+fn parse_directive(text: &str) -> Option<(DirectiveKind, Vec<&str>)> {
+    let text = text.trim();
+    let (name, rest) = match text.find(char::is_whitespace) {
+        Some(pos) => (&text[..pos], &text[pos..]),
+        None => (text, ""),
+    };
+    let kind = match name {
+        "eslint-disable" => DirectiveKind::Disable,
+        "eslint-enable" => DirectiveKind::Enable,
+        "eslint-disable-next-line" => DirectiveKind::DisableNextLine,
+        "eslint-disable-line" => DirectiveKind::DisableLine,
+        _ => return None,
+    };
+
+    // Anything after `--` is a description of why the directive is there.
+    let rules = match rest.find("--") {
+        Some(pos) => &rest[..pos],
+        None => rest,
+    };
+    let rules = rules.split(',').map(str::trim).filter(|rule| !rule.is_empty()).collect();
+    Some((kind, rules))
+}
+
+/// Finds `eslint-disable`, `eslint-enable`, `eslint-disable-next-line` and
+/// `eslint-disable-line` directives in the comments of `input`, along with
+/// the comma separated rules each one names.
+pub fn extract_lint_directives(input: &str) -> Vec<LintDirective> {
+    tokenize_spanned(input)
+        .into_iter()
+        .filter_map(|spanned| {
+            spanned.token.comment_text().and_then(parse_directive).map(|(kind, rules)| {
+                LintDirective {
+                    kind: kind,
+                    rules: rules,
+                    span: spanned.span,
+                }
+            })
+        })
+        .collect()
+}
+
+// Cyrillic and Greek letters that look like ASCII ones.
+const CONFUSABLES: &'static [char] = &[
+    // Cyrillic а е о р с у х і ј ѕ һ ԁ ԛ ԝ
+    '\u{430}', '\u{435}', '\u{43E}', '\u{440}', '\u{441}', '\u{443}', '\u{445}',
+    '\u{456}', '\u{458}', '\u{455}', '\u{4BB}', '\u{501}', '\u{51B}', '\u{51D}',
+    // Cyrillic А В Е К М Н О Р С Т Х У І Ј Ѕ
+    '\u{410}', '\u{412}', '\u{415}', '\u{41A}', '\u{41C}', '\u{41D}', '\u{41E}',
+    '\u{420}', '\u{421}', '\u{422}', '\u{425}', '\u{423}', '\u{406}', '\u{408}',
+    '\u{405}',
+    // Greek ο α ν ι
+    '\u{3BF}', '\u{3B1}', '\u{3BD}', '\u{3B9}',
+    // Greek Α Β Ε Ζ Η Ι Κ Μ Ν Ο Ρ Τ Υ Χ
+    '\u{391}', '\u{392}', '\u{395}', '\u{396}', '\u{397}', '\u{399}', '\u{39A}',
+    '\u{39C}', '\u{39D}', '\u{39F}', '\u{3A1}', '\u{3A4}', '\u{3A5}', '\u{3A7}',
+];
+
+fn is_confusable(c: char) -> bool {
+    // Fullwidth Latin letters.
+    (c >= '\u{FF21}' && c <= '\u{FF3A}') || (c >= '\u{FF41}' && c <= '\u{FF5A}') ||
+    CONFUSABLES.contains(&c)
+}
+
+/// Finds identifiers containing non-ASCII letters that look like ASCII ones,
+/// such as a Cyrillic `а` standing in for `a`, which can make an identifier
+/// pass for a different one. This uses a small built-in table of Cyrillic,
+/// Greek and fullwidth look-alikes rather than the full Unicode confusables
+/// list.
+pub fn find_confusable_identifiers(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    tokens.iter()
+          .filter(|spanned| {
+              match spanned.token {
+                  Token::Identifier(s) | Token::PrivateIdentifier(s) => {
+                      s.chars().any(is_confusable)
+                  }
+                  _ => false,
+              }
+          })
+          .map(|spanned| spanned.span.clone())
+          .collect()
+}
+
+/// Counts the tokens that add a decision point to the code: `if`, `for`,
+/// `while`, `case`, `catch`, the short-circuiting `&&`, `||` and `??` and
+/// their assignment forms, and the `?` of a conditional expression. Adding one
+/// gives a cheap estimate of cyclomatic complexity.
+pub fn branch_token_count(tokens: &[Token]) -> usize {
+    tokens.iter()
+          .filter(|token| {
+              match **token {
+                  Token::LogicalAnd |
+                  Token::LogicalOr |
+                  Token::NullishCoalescing |
+                  Token::QuestionMark |
+                  Token::UpdateAssignment(Operator::LogicalAnd) |
+                  Token::UpdateAssignment(Operator::LogicalOr) |
+                  Token::UpdateAssignment(Operator::NullishCoalescing) => true,
+                  _ => {
+                      ["if", "for", "while", "case", "catch"]
+                          .iter()
+                          .any(|&word| token.is_word(word))
+                  }
+              }
+          })
+          .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::{tokenize, tokenize_spanned};
+
+    fn await_outside_async(input: &str) -> Vec<Range<usize>> {
+        find_await_outside_async(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn ambiguous_slash_after_brace() {
+        assert_eq!(find_ambiguous_slashes(&tokenize_spanned("} /re/")), vec![2..3]);
+    }
+
+    #[test]
+    fn ambiguous_slash_after_paren() {
+        assert_eq!(find_ambiguous_slashes(&tokenize_spanned("a) /re/")), vec![3..4]);
+        assert_eq!(find_ambiguous_slashes(&tokenize_spanned("if (a) /=re/")), vec![7..8]);
+    }
+
+    #[test]
+    fn ambiguous_slash_after_contextual_keyword() {
+        assert_eq!(find_ambiguous_slashes(&tokenize_spanned("await /re/")), vec![6..7]);
+    }
+
+    #[test]
+    fn unambiguous_division() {
+        assert_eq!(find_ambiguous_slashes(&tokenize_spanned("a / b")), vec![]);
+        assert_eq!(find_ambiguous_slashes(&tokenize_spanned("a[0] / b")), vec![]);
+        assert_eq!(find_ambiguous_slashes(&tokenize_spanned("x = /re/")), vec![]);
+    }
+
+    #[test]
+    fn confusable_identifiers() {
+        assert_eq!(find_confusable_identifiers(&tokenize_spanned("var p\u{430}ypal = 1;")),
+                   vec![4..11]);
+        assert_eq!(find_confusable_identifiers(&tokenize_spanned("\u{FF41}.b")), vec![0..3]);
+    }
+
+    #[test]
+    fn plain_identifiers_not_confusable() {
+        assert_eq!(find_confusable_identifiers(&tokenize_spanned("var paypal = 1;")), vec![]);
+        assert_eq!(find_confusable_identifiers(&tokenize_spanned("var \u{3C0} = '\u{430}';")),
+                   vec![]);
+    }
+
+    #[test]
+    fn null_safe_access() {
+        assert_eq!(find_null_safe_accesses(&tokenize_spanned("a?.b ?? c")), vec![0..9]);
+        assert_eq!(find_null_safe_accesses(&tokenize_spanned("x = a?.b.c ?? d.e;")),
+                   vec![4..17]);
+        assert_eq!(find_null_safe_accesses(&tokenize_spanned("f(a?.[0]) ?? g()")), vec![0..16]);
+    }
+
+    #[test]
+    fn nullish_without_optional_chain() {
+        assert_eq!(find_null_safe_accesses(&tokenize_spanned("a.b ?? c")), vec![]);
+        assert_eq!(find_null_safe_accesses(&tokenize_spanned("a?.b || c")), vec![]);
+    }
+
+    #[test]
+    fn with_block() {
+        assert_eq!(find_with_statements(&tokenize_spanned("with (o) { x }")), vec![0..14]);
+        assert_eq!(find_with_statements(&tokenize_spanned("a;with(o){ f({}) }b")), vec![2..18]);
+    }
+
+    #[test]
+    fn with_single_statement() {
+        assert_eq!(find_with_statements(&tokenize_spanned("with (o) x;\ny")), vec![0..11]);
+        assert_eq!(find_with_statements(&tokenize_spanned("if (a) { with (o) f(x) }")),
+                   vec![9..22]);
+    }
+
+    #[test]
+    fn with_lookalikes() {
+        assert_eq!(find_with_statements(&tokenize_spanned("width (o) { x }")), vec![]);
+        assert_eq!(find_with_statements(&tokenize_spanned("s.with(1, 'a'); 'with (o) {}'")),
+                   vec![]);
+    }
+
+    #[test]
+    fn dynamic_eval() {
+        assert_eq!(find_dynamic_eval(&tokenize_spanned("eval(x)")), vec![0..7]);
+        assert_eq!(find_dynamic_eval(&tokenize_spanned("f = new Function(\"a\", \"b\");")),
+                   vec![4..26]);
+        assert_eq!(find_dynamic_eval(&tokenize_spanned("a; eval (f(x)")), vec![3..13]);
+    }
+
+    #[test]
+    fn dynamic_eval_lookalikes() {
+        assert_eq!(find_dynamic_eval(&tokenize_spanned("myEval(x)")), vec![]);
+        assert_eq!(find_dynamic_eval(&tokenize_spanned("s = \"eval\"; // eval(x)")), vec![]);
+        assert_eq!(find_dynamic_eval(&tokenize_spanned("eval; new Functions(x)")), vec![]);
+    }
+
+    fn hazards(input: &str) -> Vec<(HazardKind, &str)> {
+        find_sloppy_hazards(&tokenize_spanned(input))
+            .into_iter()
+            .map(|hazard| (hazard.kind, &input[hazard.span]))
+            .collect()
+    }
+
+    #[test]
+    fn sloppy_hazards() {
+        let input = concat!("with (o) { x = 1; }\n",
+                            "var f = new Function(\"return 1\");\n",
+                            "eval(code);\n",
+                            "arguments = [];\n",
+                            "eval += 1;\n",
+                            "++arguments;");
+        assert_eq!(hazards(input),
+                   vec![(HazardKind::With, "with (o) { x = 1; }"),
+                        (HazardKind::DynamicEval, "new Function(\"return 1\")"),
+                        (HazardKind::DynamicEval, "eval(code)"),
+                        (HazardKind::RestrictedAssignment, "arguments"),
+                        (HazardKind::RestrictedAssignment, "eval"),
+                        (HazardKind::RestrictedAssignment, "arguments")]);
+    }
+
+    #[test]
+    fn no_sloppy_hazards() {
+        let input = concat!("'use strict';\n",
+                            "function f(a) { return arguments[0] === a && o.eval == 1; }\n",
+                            "o.arguments = 1; s.with(x); a++\narguments.length;");
+        assert_eq!(hazards(input), vec![]);
+    }
+
+    fn debug_statements(input: &str) -> Vec<&str> {
+        find_debug_statements(&tokenize_spanned(input))
+            .into_iter()
+            .map(|range| &input[range])
+            .collect()
+    }
+
+    #[test]
+    fn console_calls_and_debugger() {
+        assert_eq!(debug_statements("console.log(x)"), vec!["console.log(x)"]);
+        assert_eq!(debug_statements("if (a) { debugger; }"), vec!["debugger;"]);
+        assert_eq!(debug_statements("console.error('a', f(b));\ndebugger\nc()"),
+                   vec!["console.error('a', f(b))", "debugger"]);
+    }
+
+    #[test]
+    fn debug_statement_lookalikes() {
+        assert!(debug_statements("myconsole.log(x); a.console.log(y)").is_empty());
+        assert!(debug_statements("x = console; console.log; o.debugger = 1").is_empty());
+        assert!(debug_statements("s = 'console.log(x)'; // debugger;").is_empty());
+    }
+
+    fn assignments(input: &str) -> Vec<Range<usize>> {
+        find_assignment_in_condition(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn assignment_in_condition() {
+        assert_eq!(assignments("if (a = b) {}"), vec![6..7]);
+        assert_eq!(assignments("while(x.y = f(1)) g();"), vec![10..11]);
+        assert_eq!(assignments("do {} while (a = b)"), vec![15..16]);
+        assert_eq!(assignments("for (i = 0; i = n; i = i + 1) {}"), vec![14..15]);
+    }
+
+    #[test]
+    fn comparison_in_condition() {
+        assert_eq!(assignments("if (a == b) {}"), vec![]);
+        assert_eq!(assignments("if (a === b || c >= d) {} else if (!e) {}"), vec![]);
+        assert_eq!(assignments("if (f(a = 1, [b] = c)) {}"), vec![]);
+        assert_eq!(assignments("for (let [a = 1] of b) {} for (x in o) {}"), vec![]);
+        assert_eq!(assignments("o.if(a = b); a = b;"), vec![]);
+    }
+
+    #[test]
+    fn parenthesized_assignment_in_condition() {
+        assert_eq!(assignments("while ((a = next())) {}"), vec![]);
+        assert_eq!(assignments("if ((m = re.exec(s)) !== null) {}"), vec![]);
+    }
+
+    #[test]
+    fn branch_tokens() {
+        let input = "function f(a, b) {\n\
+                     if (a && b) { return 1; }\n\
+                     for (;;) { while (a) { a = a.next ?? null; } }\n\
+                     switch (b) { case 1: case 2: break; default: }\n\
+                     try { g(); } catch(e) {}\n\
+                     return a ? b || 1 : 0;\n\
+                     }";
+        assert_eq!(branch_token_count(&tokenize(input)), 10);
+    }
+
+    #[test]
+    fn branch_tokens_in_literals() {
+        assert_eq!(branch_token_count(&tokenize("s = 'a && b' // if (x)")), 0);
+        assert_eq!(branch_token_count(&tokenize("a?.b; ifs = 1; a ||= b")), 1);
+    }
+
+    fn asi_hazards(input: &str) -> Vec<Range<usize>> {
+        find_return_asi_hazards(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn return_asi_hazard() {
+        assert_eq!(asi_hazards("function f() {\n    return\n        x;\n}"), vec![19..25]);
+        assert_eq!(asi_hazards("throw\nnew Error()"), vec![0..5]);
+        assert_eq!(asi_hazards("for (;;) { break // out\nouter }"), vec![11..16]);
+        assert_eq!(asi_hazards("a\n++b"), vec![2..4]);
+    }
+
+    #[test]
+    fn return_without_asi_hazard() {
+        assert_eq!(asi_hazards("return x"), vec![]);
+        assert_eq!(asi_hazards("{ return\n}\nreturn;\nreturn\n;"), vec![]);
+        assert_eq!(asi_hazards("a.return\nx; a++\nb; a = 1\n++b"), vec![]);
+    }
+
+    fn unreachable(input: &str) -> Vec<&str> {
+        find_unreachable_after_jump(&tokenize_spanned(input))
+            .into_iter()
+            .map(|range| &input[range])
+            .collect()
+    }
+
+    #[test]
+    fn unreachable_after_jump() {
+        assert_eq!(unreachable("function f() { return x; y; }"), vec!["y;"]);
+        assert_eq!(unreachable("{ throw new Error('a'); f(); g() }"), vec!["f(); g()"]);
+        assert_eq!(unreachable("for (;;) { break\nlog(x) }"), vec!["log(x)"]);
+        assert_eq!(unreachable("switch (a) { case 1: return; b++; case 2: c() }"), vec!["b++;"]);
+        assert_eq!(unreachable("{ return; function g() {} class C {} h(); }"), vec!["h();"]);
+    }
+
+    #[test]
+    fn reachable_after_jump() {
+        assert_eq!(unreachable("function f() { return x; }"), Vec::<&str>::new());
+        assert_eq!(unreachable("{ if(a) return; y; }"), Vec::<&str>::new());
+        assert_eq!(unreachable("{ if (a) { return } else return; y }"), Vec::<&str>::new());
+        assert_eq!(unreachable("{ return; async function g() {} }"), Vec::<&str>::new());
+        assert_eq!(unreachable("switch (a) { case 1: break; default: b() }"), Vec::<&str>::new());
+        assert_eq!(unreachable("o.return; x; f(function () { return 1 }); y"),
+                   Vec::<&str>::new());
+    }
+
+    fn labeled_jumps(input: &str) -> Vec<Range<usize>> {
+        find_labeled_jumps(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn labeled_break_and_continue() {
+        assert_eq!(labeled_jumps("outer: for (;;) { break outer; }"), vec![18..29]);
+        assert_eq!(labeled_jumps("loop1: while (a) { continue loop1 }"), vec![19..33]);
+        assert_eq!(labeled_jumps("a: { break /* c */ a }"), vec![5..20]);
+    }
+
+    #[test]
+    fn unlabeled_break_and_continue() {
+        assert_eq!(labeled_jumps("outer: for (;;) { break\nouter; }"), vec![]);
+        assert_eq!(labeled_jumps("for (;;) { break; } while (a) continue\n"), vec![]);
+        assert_eq!(labeled_jumps("switch (a) { case 1: break }"), vec![]);
+        assert_eq!(labeled_jumps("o.break(a); s = 'break a';"), vec![]);
+    }
+
+    fn empty_blocks(input: &str) -> Vec<Range<usize>> {
+        find_empty_blocks(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn empty_blocks_found() {
+        assert_eq!(empty_blocks("try { a() } catch {}"), vec![18..20]);
+        assert_eq!(empty_blocks("if (x) {}"), vec![7..9]);
+        assert_eq!(empty_blocks("function f(){}"), vec![12..14]);
+        assert_eq!(empty_blocks("while (a) { // wait\n}"), vec![10..21]);
+        assert_eq!(empty_blocks("f(() => {})"), vec![8..10]);
+    }
+
+    #[test]
+    fn non_empty_blocks() {
+        assert_eq!(empty_blocks("{ a }"), vec![]);
+        assert_eq!(empty_blocks("x = {}; f({}, [{}]); return {}"), vec![]);
+        assert_eq!(empty_blocks("`${{}}`; '{}'"), vec![]);
+    }
+
+    fn duplicate_keys(input: &str) -> Vec<Range<usize>> {
+        find_duplicate_keys(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn duplicate_object_keys() {
+        assert_eq!(duplicate_keys("o = {a: 1, a: 2}"), vec![11..12]);
+        assert_eq!(duplicate_keys("f({a: 1, 'b': g({a: 2}), \"a\": 3, b: [4]})"),
+                   vec![25..28, 33..34]);
+    }
+
+    #[test]
+    fn distinct_object_keys() {
+        assert_eq!(duplicate_keys("o = {a, b}"), vec![]);
+        assert_eq!(duplicate_keys("o = {[a]: 1, [a]: 2}"), vec![]);
+        assert_eq!(duplicate_keys("o = {a: 1, b: {a: 2}}; {a: 1}; {a: 2}"), vec![]);
+        assert_eq!(duplicate_keys("o = {get a() {}, set a(v) {}, a: c ? a : b}"), vec![]);
+        assert_eq!(duplicate_keys("const {a: x, a: y} = o"), vec![]);
+    }
+
+    fn nullish_mixing(input: &str) -> Vec<Range<usize>> {
+        find_nullish_mixing_errors(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn nullish_mixed_with_logical() {
+        assert_eq!(nullish_mixing("a ?? b || c"), vec![2..4]);
+        assert_eq!(nullish_mixing("x = a && b ?? c;"), vec![11..13]);
+        assert_eq!(nullish_mixing("f((a ?? b) || c, d && (e ?? f || g))"), vec![25..27]);
+    }
+
+    #[test]
+    fn nullish_not_mixed() {
+        assert_eq!(nullish_mixing("(a ?? b) || c"), vec![]);
+        assert_eq!(nullish_mixing("a ?? b ?? c"), vec![]);
+        assert_eq!(nullish_mixing("a ?? (b || c)"), vec![]);
+        assert_eq!(nullish_mixing("x = a ?? b, c || d; e ? f ?? g : h && i"), vec![]);
+        assert_eq!(nullish_mixing("a = b ?? c\nd || e"), vec![]);
+        assert_eq!(nullish_mixing("a?.b || c"), vec![]);
+    }
+
+    fn directives(input: &str) -> Vec<(DirectiveKind, Vec<&str>)> {
+        extract_lint_directives(input).into_iter().map(|d| (d.kind, d.rules)).collect()
+    }
+
+    #[test]
+    fn lint_directive_disable() {
+        assert_eq!(directives("/* eslint-disable */\nfoo()"),
+                   vec![(DirectiveKind::Disable, vec![])]);
+        assert_eq!(directives("/* eslint-disable no-alert, no-console */"),
+                   vec![(DirectiveKind::Disable, vec!["no-alert", "no-console"])]);
+    }
+
+    #[test]
+    fn lint_directive_enable() {
+        assert_eq!(directives("// eslint-enable eqeqeq"),
+                   vec![(DirectiveKind::Enable, vec!["eqeqeq"])]);
+    }
+
+    #[test]
+    fn lint_directive_next_line() {
+        let found = extract_lint_directives("a();\n// eslint-disable-next-line no-console\nb();");
+        assert_eq!(found,
+                   vec![LintDirective {
+                            kind: DirectiveKind::DisableNextLine,
+                            rules: vec!["no-console"],
+                            span: 5..43,
+                        }]);
+    }
+
+    #[test]
+    fn lint_directive_line() {
+        assert_eq!(directives("alert(1); // eslint-disable-line no-alert -- needed for demo"),
+                   vec![(DirectiveKind::DisableLine, vec!["no-alert"])]);
+    }
+
+    #[test]
+    fn lint_directive_mentions() {
+        assert_eq!(directives("// we run eslint-disable in CI\n/* eslint-disabled */"), vec![]);
+        assert_eq!(directives("x = '// eslint-disable'"), vec![]);
+    }
+
+    #[test]
+    fn await_at_top_level() {
+        assert_eq!(await_outside_async("await x"), vec![0..5]);
+    }
+
+    #[test]
+    fn await_in_async_function() {
+        assert_eq!(await_outside_async("async function f(){ await x }"), vec![]);
+        assert_eq!(await_outside_async("async function f() { if (a) { await x } }"), vec![]);
+    }
+
+    #[test]
+    fn await_in_plain_function() {
+        assert_eq!(await_outside_async("function f() { await x }"), vec![15..20]);
+    }
+
+    #[test]
+    fn await_in_async_arrow() {
+        assert_eq!(await_outside_async("async () => { await x }"), vec![]);
+        assert_eq!(await_outside_async("f(async x => await x, await y)"), vec![22..27]);
+    }
+
+    #[test]
+    fn await_after_async_function() {
+        assert_eq!(await_outside_async("async function f() {}\nawait x"), vec![22..27]);
+    }
+}
@@ -1,15 +1,16 @@
-#![feature(test)]
 #![feature(plugin)]
 
 #![plugin(clippy)]
 
-extern crate test;
-extern crate memchr;
+extern crate parsejs;
 
 use std::env;
 use std::fs::File;
 use std::io::Read;
 
+use parsejs::stats::file_stats;
+use parsejs::tokenizer::{tokenize, tokenize_spanned};
+
 fn get_file_content(arg: &str) -> std::io::Result<String> {
     let mut content = String::new();
     let mut file = try!(File::open(arg));
@@ -17,12 +18,40 @@ fn get_file_content(arg: &str) -> std::io::Result<String> {
     Ok(content)
 }
 
-mod tokenizer;
-use tokenizer::tokenize;
+// Prints one line of statistics for each file.
+fn report<I: Iterator<Item = String>>(paths: I) {
+    for path in paths {
+        match get_file_content(&path) {
+            Ok(content) => {
+                let stats = file_stats(&content);
+                println!("{}: lines={} tokens={} identifiers={} hash={:016x}",
+                         path,
+                         stats.lines,
+                         stats.tokens,
+                         stats.identifiers,
+                         stats.hash);
+            }
+            Err(err) => eprintln!("{}: {}", path, err),
+        }
+    }
+}
 
 fn main() {
-    for argument in env::args().skip(1) {
+    if env::args().nth(1).map_or(false, |arg| arg == "--report") {
+        return report(env::args().skip(2));
+    }
+
+    // Prints every token along with its span.
+    let spans = env::args().nth(1).map_or(false, |arg| arg == "--spans");
+
+    for argument in env::args().skip(if spans { 2 } else { 1 }) {
         let content = &get_file_content(&argument).unwrap_or(argument);
+        if spans {
+            for spanned in tokenize_spanned(content) {
+                println!("{:?}", spanned);
+            }
+            continue;
+        }
         let tokens = tokenize(content);
         if tokens.len() < 20 {
             for token in tokens {
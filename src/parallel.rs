@@ -0,0 +1,234 @@
+use crossbeam;
+
+use tokenizer::{tokenize, Token};
+
+// Scans for the start of each line that is outside of comments, strings and
+// templates and starts with a token, returning those closest after each of
+// `chunks - 1` evenly spaced offsets. Regexes aren't tracked since telling
+// them from division takes a tokenizer, so a quote or backtick in a regex
+// can throw the scan off; `tokenize_parallel` checks each point anyway.
+fn split_points(input: &str, chunks: usize) -> Vec<usize> {
+    let bytes = input.as_bytes();
+    let chunk_len = bytes.len() / chunks.max(1);
+    let mut points = Vec::new();
+    if chunk_len == 0 {
+        return points;
+    }
+
+    // `None` is template text, `Some(depth)` the brace depth within a
+    // template substitution.
+    let mut templates: Vec<Option<usize>> = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let b = bytes[index];
+        let in_template_text = match templates.last() {
+            Some(&None) => true,
+            _ => false,
+        };
+
+        if in_template_text {
+            match b {
+                b'\\' => index += 1,
+                b'`' => {
+                    templates.pop();
+                }
+                b'$' if bytes.get(index + 1) == Some(&b'{') => {
+                    templates.push(Some(0));
+                    index += 1;
+                }
+                _ => {}
+            }
+            index += 1;
+            continue;
+        }
+
+        match b {
+            b'/' if bytes.get(index + 1) == Some(&b'/') => {
+                while index < bytes.len() && bytes[index] != b'\n' {
+                    index += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(index + 1) == Some(&b'*') => {
+                index += 2;
+                while index < bytes.len() && !(bytes[index - 1] == b'*' && bytes[index] == b'/') {
+                    index += 1;
+                }
+            }
+            b'"' | b'\'' => {
+                index += 1;
+                while index < bytes.len() && bytes[index] != b && bytes[index] != b'\n' {
+                    if bytes[index] == b'\\' {
+                        index += 1;
+                    }
+                    index += 1;
+                }
+            }
+            b'`' => templates.push(None),
+            b'{' => {
+                if let Some(&mut Some(ref mut depth)) = templates.last_mut() {
+                    *depth += 1;
+                }
+            }
+            b'}' => {
+                match templates.last_mut() {
+                    Some(&mut Some(0)) => {
+                        templates.pop();
+                    }
+                    Some(&mut Some(ref mut depth)) => *depth -= 1,
+                    _ => {}
+                }
+            }
+            b'\n' if templates.is_empty() => {
+                let next = index + 1;
+                let wanted = (points.len() + 1) * chunk_len;
+                // A line starting with `/` could be a regex or division
+                // depending on the previous line, and `#` could be mistaken
+                // for a shebang.
+                let starts_token = next < bytes.len() &&
+                                   !(bytes[next] as char).is_whitespace() &&
+                                   bytes[next] != b'/' &&
+                                   bytes[next] != b'#';
+                if next >= wanted && starts_token && points.len() + 1 < chunks {
+                    points.push(next);
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+
+    points
+}
+
+// Whether tokens of a chunk ending at a split point end the way a full
+// tokenization would: in whitespace up to the line start the split is at.
+fn ends_cleanly(tokens: &[Token]) -> bool {
+    match tokens.last() {
+        Some(&Token::Whitespace(s)) => s.ends_with('\n'),
+        _ => false,
+    }
+}
+
+/// Tokenizes `input` on up to `chunks` threads, giving the same result as
+/// `tokenize`.
+///
+/// The input is split at line starts found by a quick scan that skips
+/// strings, templates and comments. Each chunk is tokenized on its own
+/// thread as if it started a file, which is only right when the split point
+/// is a token boundary of the whole file, so chunks are checked in order and
+/// any chunk that starts at a bad split point is tokenized again from the
+/// last good one.
+pub fn tokenize_parallel(input: &str, chunks: usize) -> Vec<Token> {
+    let mut bounds = vec![0];
+    bounds.extend(split_points(input, chunks));
+    bounds.push(input.len());
+
+    let results: Vec<Vec<Token>> = crossbeam::scope(|scope| {
+        let handles: Vec<_> = bounds.windows(2)
+                                    .map(|window| {
+                                        let chunk = &input[window[0]..window[1]];
+                                        scope.builder().spawn(move || tokenize(chunk))
+                                    })
+                                    .collect();
+        handles.into_iter()
+               .map(|handle| {
+                   match handle {
+                       Ok(handle) => handle.join(),
+                       // Spawning failed, so the chunk is redone below.
+                       Err(_) => Vec::new(),
+                   }
+               })
+               .collect()
+    });
+
+    let mut tokens: Vec<Token> = Vec::new();
+    // Start of the input not yet in `tokens`, always a good split point.
+    let mut start = 0;
+    for (index, piece) in results.into_iter().enumerate() {
+        let end = bounds[index + 1];
+        let mut piece = if bounds[index] == start && !piece.is_empty() {
+            piece
+        } else {
+            tokenize(&input[start..end])
+        };
+        if end != input.len() && !ends_cleanly(&piece) {
+            continue;
+        }
+
+        // Chunks after the first start with the empty whitespace `tokenize`
+        // puts before a leading token, which the previous chunk's trailing
+        // whitespace already covers.
+        if start > 0 && piece.first() == Some(&Token::Whitespace("")) {
+            piece.remove(0);
+        }
+        tokens.extend(piece);
+        start = end;
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+
+    const SAMPLE: &'static str = "function test(a, b) {\n\
+                                  \t// comment with ' and `\n\
+                                  \t/*\n\
+                                  \t * multiline\n\
+                                  \t */\n\
+                                  \tvar s = 'string';\n\
+                                  \tvar t = `template\n\
+                                  spanning ${a + `nested\n\
+                                  lines`}\n\
+                                  lines`;\n\
+                                  \tx = a\n\
+                                  / b / c;\n\
+                                  \ty = /re'gex/g.test(s);\n\
+                                  \treturn this.foobar.TeSt;\n\
+                                  }\n";
+
+    fn large_input() -> String {
+        let mut input = String::new();
+        for _ in 0..200 {
+            input.push_str(SAMPLE);
+        }
+        input
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let input = large_input();
+        let expected = tokenize(&input);
+        for chunks in 1..9 {
+            assert!(tokenize_parallel(&input, chunks) == expected, "{} chunks", chunks);
+        }
+    }
+
+    #[test]
+    fn parallel_small_inputs() {
+        assert_eq!(tokenize_parallel("", 4), tokenize(""));
+        assert_eq!(tokenize_parallel("a\nb", 4), tokenize("a\nb"));
+        assert_eq!(tokenize_parallel("a\n/b/", 2), tokenize("a\n/b/"));
+    }
+
+    #[test]
+    fn split_points_outside_multiline_constructs() {
+        let input = large_input();
+        let points = split_points(&input, 64);
+        assert!(points.len() > 32);
+
+        // Every split point should start a token of the sequential result.
+        let mut starts = Vec::new();
+        let mut offset = 0;
+        for token in tokenize(&input) {
+            starts.push(offset);
+            offset += token.as_str().len();
+        }
+        for point in points {
+            assert!(starts.binary_search(&point).is_ok(), "split at {}", point);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! The most commonly used items, for glob importing.
+//!
+//! ```
+//! use parsejs::prelude::*;
+//!
+//! let tokens = tokenize("let x = /re/g;");
+//! assert_eq!(tokens[7], Token::RegexLiteral("/re/g"));
+//! assert_eq!(tokens[7].op(), TokenOp::RegexLiteral);
+//! ```
+
+pub use tokenizer::{tokenize, tokenize_spanned, tokenize_with_options, Spanned, Token,
+                    TokenOp, TokenizeError, TokenizeOptions};
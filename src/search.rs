@@ -0,0 +1,206 @@
+use std::ops::Range;
+
+use structure::prev_meaningful;
+use tokenizer::{is_keyword, tokenize_spanned, Spanned, Token};
+
+/// Finds every use of `keyword` as a word in `input`, skipping strings,
+/// comments, regexes and property names like `a.function`.
+///
+/// Words are matched whether the tokenizer made them a `Token::Keyword` or,
+/// as happens when they touch punctuation (`function(`), a
+/// `Token::Identifier`.
+pub fn find_keyword_occurrences(input: &str, keyword: &str) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+    let mut after_dot = false;
+
+    for spanned in tokenize_spanned(input) {
+        if spanned.token.is_greyspace() {
+            continue;
+        }
+        if !after_dot && spanned.token.is_word(keyword) {
+            found.push(spanned.span.clone());
+        }
+        after_dot = match spanned.token {
+            Token::Dot | Token::OptionalChain => true,
+            _ => false,
+        };
+    }
+
+    found
+}
+
+// The tag of the template literal at `index`, as an identifier or a chain of
+// property accesses like `styled.div`, if it has one.
+fn template_tag(tokens: &[Spanned], index: usize) -> Option<String> {
+    let mut parts = Vec::new();
+    let mut current = index;
+    loop {
+        let word = match prev_meaningful(tokens, current) {
+            Some(prev) => {
+                match tokens[prev].token {
+                    Token::Identifier(s) if !is_keyword(s) => {
+                        current = prev;
+                        s
+                    }
+                    _ => break,
+                }
+            }
+            None => break,
+        };
+        parts.push(word);
+
+        match prev_meaningful(tokens, current) {
+            Some(dot) if tokens[dot].token == Token::Dot => current = dot,
+            _ => break,
+        }
+    }
+
+    if parts.is_empty() || tokens[current].token == Token::Dot {
+        return None;
+    }
+    parts.reverse();
+    Some(parts.join("."))
+}
+
+/// Finds template literals tagged with one of `tags`, returning the tag and
+/// the span of the template. A member access tag like `styled.div` matches
+/// either its full name or its first part, so `styled` finds every
+/// `styled.*` template.
+pub fn find_tagged_templates(tokens: &[Spanned], tags: &[&str]) -> Vec<(String, Range<usize>)> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        match spanned.token {
+            Token::TemplateLiteral(_) => {}
+            _ => continue,
+        }
+        let tag = match template_tag(tokens, index) {
+            Some(tag) => tag,
+            None => continue,
+        };
+        let matches = {
+            let root = tag.split('.').next().unwrap();
+            tags.iter().any(|&wanted| wanted == tag || wanted == root)
+        };
+        if matches {
+            found.push((tag, spanned.span.clone()));
+        }
+    }
+
+    found
+}
+
+// Byte ranges of the text of the template literal `template` outside of its
+// substitutions, relative to the template.
+fn template_chunks(template: &Token) -> Vec<Range<usize>> {
+    let text = template.as_str();
+    let end = if text.len() >= 2 && text.ends_with('`') {
+        text.len() - 1
+    } else {
+        text.len()
+    };
+    let mut chunks = Vec::new();
+    let mut start = 1;
+    for substitution in template.template_substitution_spans() {
+        chunks.push(start..substitution.start);
+        start = substitution.end;
+    }
+    chunks.push(start..end);
+    chunks
+}
+
+/// Finds template literals tagged with one of `tags`, as
+/// `find_tagged_templates` does, returning the tag and the byte ranges of
+/// the template's text between its substitutions, without the backticks.
+/// There is always one more range than substitutions, some possibly empty,
+/// like the strings a tag function is called with. This lets tools for
+/// embedded languages such as GraphQL or SQL check the static parts of a
+/// query.
+pub fn extract_tagged_template_contents(input: &str,
+                                        tags: &[&str])
+                                        -> Vec<(String, Vec<Range<usize>>)> {
+    let tokens = tokenize_spanned(input);
+    find_tagged_templates(&tokens, tags)
+        .into_iter()
+        .map(|(tag, span)| {
+            let start = span.start;
+            let template = Token::TemplateLiteral(&input[span]);
+            let chunks = template_chunks(&template)
+                             .into_iter()
+                             .map(|chunk| start + chunk.start..start + chunk.end)
+                             .collect();
+            (tag, chunks)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize_spanned;
+
+    fn tagged(input: &str) -> Vec<(String, Range<usize>)> {
+        find_tagged_templates(&tokenize_spanned(input), &["css", "gql", "styled", "String.raw"])
+    }
+
+    #[test]
+    fn keyword_occurrences() {
+        assert_eq!(find_keyword_occurrences("function f() {}\nvar g = function(){}", "function"),
+                   vec![0..8, 24..32]);
+    }
+
+    #[test]
+    fn keyword_occurrences_skip_strings_and_comments() {
+        let input = "x = \"function\"; // function\n/* function */ y = `function`; z = /function/;";
+        assert_eq!(find_keyword_occurrences(input, "function"), vec![]);
+    }
+
+    #[test]
+    fn keyword_occurrences_skip_properties() {
+        assert_eq!(find_keyword_occurrences("a.function; functions; new", "function"), vec![]);
+    }
+
+    #[test]
+    fn tagged_template() {
+        assert_eq!(tagged("const a = css`color: red;`"),
+                   vec![(String::from("css"), 13..26)]);
+        assert_eq!(tagged("q = gql`query{}`"), vec![(String::from("gql"), 7..16)]);
+    }
+
+    #[test]
+    fn tagged_template_member_access() {
+        assert_eq!(tagged("const B = styled.div`margin: 0;`"),
+                   vec![(String::from("styled.div"), 20..32)]);
+        assert_eq!(tagged("String.raw`\\n`"), vec![(String::from("String.raw"), 10..14)]);
+    }
+
+    #[test]
+    fn untagged_templates() {
+        assert_eq!(tagged("html`<p>`; `css`; return `x`; a.css`y`; f()`z`"), vec![]);
+    }
+
+    fn contents<'a>(input: &'a str, tags: &[&str]) -> Vec<(String, Vec<&'a str>)> {
+        extract_tagged_template_contents(input, tags)
+            .into_iter()
+            .map(|(tag, chunks)| (tag, chunks.into_iter().map(|chunk| &input[chunk]).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn tagged_template_contents() {
+        assert_eq!(contents("q = gql`query { a ${frag} }`;", &["gql"]),
+                   vec![(String::from("gql"), vec!["query { a ", " }"])]);
+        assert_eq!(extract_tagged_template_contents("gql`{ a ${b} }`", &["gql"]),
+                   vec![(String::from("gql"), vec![4..8, 12..14])]);
+    }
+
+    #[test]
+    fn tagged_template_contents_nested() {
+        let input = "sql`SELECT ${f(`x${y}`)} FROM t WHERE a = ${a}${b}`; css`p {}`";
+        assert_eq!(contents(input, &["sql"]),
+                   vec![(String::from("sql"), vec!["SELECT ", " FROM t WHERE a = ", "", ""])]);
+        assert_eq!(contents("sql`SELECT 1`", &["sql"]),
+                   vec![(String::from("sql"), vec!["SELECT 1"])]);
+        assert_eq!(contents("other`a${b}c`", &["sql"]), vec![]);
+    }
+}
@@ -0,0 +1,104 @@
+use tokenizer::Token;
+
+fn program_name(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(pos) => &path[pos + 1..],
+        None => path,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShebangInfo<'a> {
+    /// The program name of the interpreter, without its directory.
+    pub interpreter: &'a str,
+    /// The words following the interpreter.
+    pub args: Vec<&'a str>,
+}
+
+/// Splits the line of a `Token::Shebang` into the interpreter it runs and
+/// the arguments passed to it, looking through `/usr/bin/env`, including its
+/// `-S` split-string form. Words are split on whitespace, without quoting.
+pub fn parse_shebang<'a>(token: &Token<'a>) -> Option<ShebangInfo<'a>> {
+    let line = match *token {
+        Token::Shebang(s) => &s[2..],
+        _ => return None,
+    };
+
+    let mut words = line.split_whitespace();
+    let mut program = match words.next() {
+        Some(word) => program_name(word),
+        None => return None,
+    };
+    if program == "env" {
+        // `env` accepts flags (`-S` among them) and `NAME=value` assignments
+        // before the command it runs.
+        program = match words.find(|word| !word.starts_with('-') && !word.contains('=')) {
+            Some(word) => program_name(word),
+            None => return None,
+        };
+    }
+
+    Some(ShebangInfo {
+        interpreter: program,
+        args: words.collect(),
+    })
+}
+
+/// Returns the program name of the interpreter a `Token::Shebang` runs,
+/// looking through `/usr/bin/env`, including its `-S` split-string form,
+/// so both `#!/usr/bin/env -S node --flag` and `#!/bin/node` give `node`.
+pub fn shebang_interpreter<'a>(token: &Token<'a>) -> Option<&'a str> {
+    parse_shebang(token).map(|info| info.interpreter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::Token;
+
+    #[test]
+    fn interpreter_env_split_string() {
+        let token = Token::Shebang("#!/usr/bin/env -S node --experimental-modules");
+        assert_eq!(shebang_interpreter(&token), Some("node"));
+    }
+
+    #[test]
+    fn interpreter_env() {
+        assert_eq!(shebang_interpreter(&Token::Shebang("#!/usr/bin/env node")), Some("node"));
+        assert_eq!(shebang_interpreter(&Token::Shebang("#! /usr/bin/env NODE_ENV=dev node")),
+                   Some("node"));
+    }
+
+    #[test]
+    fn interpreter_direct_path() {
+        assert_eq!(shebang_interpreter(&Token::Shebang("#!/bin/node")), Some("node"));
+    }
+
+    #[test]
+    fn parse_env_arguments() {
+        let info = parse_shebang(&Token::Shebang("#!/usr/bin/env node --flag=1 foo")).unwrap();
+        assert_eq!(info,
+                   ShebangInfo {
+                       interpreter: "node",
+                       args: vec!["--flag=1", "foo"],
+                   });
+        let info = parse_shebang(&Token::Shebang("#!/usr/bin/env -S deno run -A")).unwrap();
+        assert_eq!((info.interpreter, info.args), ("deno", vec!["run", "-A"]));
+    }
+
+    #[test]
+    fn parse_direct_arguments() {
+        let info = parse_shebang(&Token::Shebang("#! /usr/local/bin/node\t--harmony ")).unwrap();
+        assert_eq!((info.interpreter, info.args), ("node", vec!["--harmony"]));
+        assert_eq!(parse_shebang(&Token::Shebang("#!/bin/node")).unwrap().args,
+                   Vec::<&str>::new());
+        assert_eq!(parse_shebang(&Token::Shebang("#!/usr/bin/env -S")), None);
+    }
+
+    #[test]
+    fn interpreter_missing() {
+        assert_eq!(shebang_interpreter(&Token::Shebang("#!")), None);
+        assert_eq!(shebang_interpreter(&Token::Shebang("#!/usr/bin/env")), None);
+        assert_eq!(shebang_interpreter(&Token::LineComment("// node")), None);
+    }
+}
@@ -0,0 +1,137 @@
+//! Spec cases for `tokenize`, written in a compact token DSL so that adding
+//! a tricky input only takes one line.
+//!
+//! Each token is written as `kind(text)` for tokens carrying source text —
+//! `ws`, `shebang`, `lc` (line comment), `bc` (block comment), `num`, `str`,
+//! `re`, `tpl`, `id`, `kw` and `unknown` — where a bare `ws` is the empty
+//! whitespace placed between adjacent tokens. Any other item is a punctuator
+//! written as its source text, e.g. `=` or `>>>=`. Inside `(...)`, `\` escapes
+//! the next character, with `\n` and `\t` standing for newline and tab.
+
+use tokenizer::{tokenize, Token};
+
+fn describe(token: &Token) -> (&'static str, String) {
+    let kind = match *token {
+        Token::Whitespace(_) => "ws",
+        Token::Shebang(_) => "shebang",
+        Token::LineComment(_) => "lc",
+        Token::BlockComment(_) => "bc",
+        Token::NumericLiteral(_) => "num",
+        Token::StringLiteral(_) => "str",
+        Token::RegexLiteral(_) => "re",
+        Token::TemplateLiteral(_) => "tpl",
+        Token::Identifier(_) => "id",
+        Token::Keyword(_) => "kw",
+        Token::PrivateIdentifier(_) => "private",
+        Token::Unknown(_) => "unknown",
+        _ => "punct",
+    };
+    (kind, token.as_str().to_string())
+}
+
+fn parse_dsl(dsl: &str) -> Vec<(&'static str, String)> {
+    let kinds = ["ws", "shebang", "lc", "bc", "num", "str", "re", "tpl", "id", "kw", "private",
+                 "unknown"];
+    let mut expected = Vec::new();
+    let mut chars = dsl.chars().peekable();
+
+    loop {
+        while chars.peek().map_or(false, |c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let mut item = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '(' && !item.is_empty() && kinds.contains(&&item[..]) {
+                break;
+            }
+            item.push(c);
+            chars.next();
+        }
+        if item.is_empty() {
+            return expected;
+        }
+
+        let kind = match kinds.iter().find(|kind| **kind == item) {
+            Some(kind) => *kind,
+            None => {
+                expected.push(("punct", item));
+                continue;
+            }
+        };
+
+        let mut text = String::new();
+        if chars.peek() == Some(&'(') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some(')') => break,
+                    Some('\\') => {
+                        match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some(c) => text.push(c),
+                            None => panic!("dangling escape in {:?}", dsl),
+                        }
+                    }
+                    Some(c) => text.push(c),
+                    None => panic!("unclosed `(` in {:?}", dsl),
+                }
+            }
+        }
+        expected.push((kind, text));
+    }
+}
+
+fn check(input: &str, dsl: &str) {
+    let actual: Vec<_> = tokenize(input).iter().map(describe).collect();
+    assert_eq!(actual, parse_dsl(dsl), "tokenizing {:?}", input);
+}
+
+#[test]
+fn dsl_parser() {
+    assert_eq!(parse_dsl("ws id(a) ws( ) = re(/\\)\\\\/) ws(\\n)"),
+               vec![("ws", String::new()),
+                    ("id", String::from("a")),
+                    ("ws", String::from(" ")),
+                    ("punct", String::from("=")),
+                    ("re", String::from("/)\\/")),
+                    ("ws", String::from("\n"))]);
+}
+
+#[test]
+fn spec_cases() {
+    let cases = [
+        ("a = 1", "ws id(a) ws( ) = ws( ) num(1) ws"),
+        ("a1 = _0$", "ws id(a1) ws( ) = ws( ) id(_0$) ws"),
+        ("a / b / c", "ws id(a) ws( ) / ws( ) id(b) ws( ) / ws( ) id(c) ws"),
+        ("(a) / 2", "ws ( ws id(a) ws ) ws( ) / ws( ) num(2) ws"),
+        ("a++ / 2", "ws id(a) ws ++ ws( ) / ws( ) num(2) ws"),
+        ("a /= 2", "ws id(a) ws( ) /= ws( ) num(2) ws"),
+        ("x = /re/g", "ws id(x) ws( ) = ws( ) re(/re/g) ws"),
+        ("!/re/", "ws ! ws re(/re/) ws"),
+        ("x = /[/]/ / 2", "ws id(x) ws( ) = ws( ) re(/[/]/) ws( ) / ws( ) num(2) ws"),
+        ("1E5 * 2e3", "ws num(1E5) ws( ) * ws( ) num(2e3) ws"),
+        ("1e+5 - 2E-3", "ws num(1e+5) ws( ) - ws( ) num(2E-3) ws"),
+        ("3.14 + .5 + 1.", "ws num(3.14) ws( ) + ws( ) num(.5) ws( ) + ws( ) num(1.) ws"),
+        ("0XfF|0b10|0O17", "ws num(0XfF) ws | ws num(0b10) ws | ws num(0O17) ws"),
+        ("a.b.c", "ws id(a) ws . ws id(b) ws . ws id(c) ws"),
+        ("a?.b ?? c", "ws id(a) ws ?. ws id(b) ws( ) ?? ws( ) id(c) ws"),
+        ("a ??= b?.[0]", "ws id(a) ws( ) ??= ws( ) id(b) ws ?. ws [ ws num(0) ws ] ws"),
+        ("a?.5:b", "ws id(a) ws ? ws num(.5) ws : ws id(b) ws"),
+        ("a >>>= 1", "ws id(a) ws( ) >>>= ws( ) num(1) ws"),
+        ("x ||= y && z", "ws id(x) ws( ) ||= ws( ) id(y) ws( ) && ws( ) id(z) ws"),
+        ("`a${b}c`", "ws tpl(`a${b}c`) ws"),
+        ("`a${`b${c}`}`", "ws tpl(`a${`b${c}`}`) ws"),
+        ("`x` + `y`", "ws tpl(`x`) ws( ) + ws( ) tpl(`y`) ws"),
+        ("`a`/b/g", "ws tpl(`a`) ws / ws id(b) ws / ws id(g) ws"),
+        ("'a\\'b'", "ws str('a\\\\'b') ws"),
+        ("// c\n/* d */", "lc(// c) ws(\\n) bc(/* d */)"),
+        ("#!/bin/node\nx", "shebang(#!/bin/node) ws(\\n) id(x) ws"),
+        ("#x in o", "ws private(#x) ws( ) kw(in) ws( ) id(o) ws"),
+    ];
+
+    for &(input, dsl) in &cases {
+        check(input, dsl);
+    }
+}
@@ -0,0 +1,200 @@
+use std::usize;
+
+use memchr;
+
+use diff::hash_tokens;
+use tokenizer::{is_keyword, tokenize, tokenize_with_options, Token, TokenizeOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStats {
+    /// Lines of the input, ended by `\n` or `\r\n`.
+    pub lines: usize,
+    /// Tokens other than whitespace and comments.
+    pub tokens: usize,
+    /// Identifier tokens, not counting keywords.
+    pub identifiers: usize,
+    /// The `diff::token_hash` of the input.
+    pub hash: u64,
+}
+
+/// Gathers statistics about `input`, tokenizing it once.
+pub fn file_stats(input: &str) -> FileStats {
+    let tokens = tokenize(input);
+    let mut stats = FileStats {
+        lines: input.lines().count(),
+        tokens: 0,
+        identifiers: 0,
+        hash: hash_tokens(&tokens),
+    };
+
+    for token in tokens.iter().filter(|token| !token.is_greyspace()) {
+        stats.tokens += 1;
+        if let Token::Identifier(s) = *token {
+            if !is_keyword(s) {
+                stats.identifiers += 1;
+            }
+        }
+    }
+
+    stats
+}
+
+/// Which of the bytes that call for the tokenizer's slower paths occur in
+/// an input, as found by `scan_complexity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScanFlags {
+    /// A `/`, which may start a comment or regex or be division.
+    pub slash: bool,
+    /// A `` ` ``, starting a template literal.
+    pub backtick: bool,
+    /// A `/*`, starting a block comment.
+    pub block_comment: bool,
+    /// Any byte outside of ASCII.
+    pub non_ascii: bool,
+}
+
+impl ScanFlags {
+    /// Whether none of the flags are set, so the input has no comments,
+    /// regexes or templates and only ASCII identifiers.
+    pub fn is_simple(&self) -> bool {
+        *self == ScanFlags::default()
+    }
+}
+
+/// Cheaply checks `input` for slashes, backticks, block comments and
+/// non-ASCII bytes without tokenizing it, so callers can pick a faster way
+/// to handle inputs without them.
+pub fn scan_complexity(input: &str) -> ScanFlags {
+    let bytes = input.as_bytes();
+    let mut block_comment = false;
+    let mut index = 0;
+    while let Some(pos) = memchr::memchr(b'/', &bytes[index..]) {
+        index += pos + 1;
+        if bytes.get(index) == Some(&b'*') {
+            block_comment = true;
+            break;
+        }
+    }
+
+    ScanFlags {
+        slash: memchr::memchr(b'/', bytes).is_some(),
+        backtick: memchr::memchr(b'`', bytes).is_some(),
+        block_comment: block_comment,
+        non_ascii: bytes.iter().any(|&b| b >= 0x80),
+    }
+}
+
+/// Set in a `token_kind_bitmap` if the input has a regex literal.
+pub const HAS_REGEX: u32 = 1;
+/// Set in a `token_kind_bitmap` if the input has a template literal.
+pub const HAS_TEMPLATE: u32 = 1 << 1;
+/// Set in a `token_kind_bitmap` if the input has a JSX fragment.
+pub const HAS_JSX: u32 = 1 << 2;
+/// Set in a `token_kind_bitmap` if the input uses `import`.
+pub const HAS_IMPORT: u32 = 1 << 3;
+/// Set in a `token_kind_bitmap` if the input uses `class`.
+pub const HAS_CLASS: u32 = 1 << 4;
+/// Set in a `token_kind_bitmap` if the input uses `async` or `await`.
+pub const HAS_ASYNC: u32 = 1 << 5;
+
+/// Summarizes which language features `input` uses as a set of the `HAS_*`
+/// bits, such as for a search index to quickly filter files by. Words only
+/// count outside of strings and comments, and not as property names.
+/// JSX is only recognized by its fragments, as `TokenizeOptions::jsx` is.
+pub fn token_kind_bitmap(input: &str) -> u32 {
+    let options = TokenizeOptions {
+        max_template_depth: usize::MAX,
+        lenient: true,
+        jsx: true,
+        ..TokenizeOptions::default()
+    };
+    let tokens = match tokenize_with_options(input, &options) {
+        Ok(tokens) => tokens,
+        Err(err) => unreachable!("lenient tokenize cannot fail: {}", err),
+    };
+
+    let mut bitmap = 0;
+    let mut after_dot = false;
+    for token in tokens.iter().filter(|token| !token.is_greyspace()) {
+        bitmap |= match *token {
+            Token::RegexLiteral(_) => HAS_REGEX,
+            Token::TemplateLiteral(_) => HAS_TEMPLATE,
+            Token::JsxFragmentStart | Token::JsxFragmentEnd | Token::JsxText(_) => HAS_JSX,
+            _ if after_dot => 0,
+            _ if token.is_word("import") => HAS_IMPORT,
+            _ if token.is_word("class") => HAS_CLASS,
+            _ if token.is_word("async") || token.is_word("await") => HAS_ASYNC,
+            _ => 0,
+        };
+        after_dot = match *token {
+            Token::Dot | Token::OptionalChain => true,
+            _ => false,
+        };
+    }
+    bitmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diff::token_hash;
+
+    #[test]
+    fn stats_counts() {
+        let input = "// sum\nfunction f(a, b) {\r\n    return a + b;\n}\n";
+        assert_eq!(file_stats(input),
+                   FileStats {
+                       lines: 4,
+                       tokens: 14,
+                       identifiers: 5,
+                       hash: token_hash(input),
+                   });
+    }
+
+    #[test]
+    fn stats_empty() {
+        assert_eq!(file_stats(""),
+                   FileStats {
+                       lines: 0,
+                       tokens: 0,
+                       identifiers: 0,
+                       hash: token_hash(""),
+                   });
+    }
+
+    #[test]
+    fn scan_plain_arithmetic() {
+        let flags = scan_complexity("var a = b * (c + 1);\nf(a - 2, 'x');\n");
+        assert_eq!(flags, ScanFlags::default());
+        assert!(flags.is_simple());
+    }
+
+    #[test]
+    fn scan_regexes_and_templates() {
+        let flags = scan_complexity("/* é */ s = `${a / 2}`.replace(/x/g, '');");
+        assert_eq!(flags,
+                   ScanFlags {
+                       slash: true,
+                       backtick: true,
+                       block_comment: true,
+                       non_ascii: true,
+                   });
+        assert!(!flags.is_simple());
+        assert_eq!(scan_complexity("a / b // c*"),
+                   ScanFlags { slash: true, ..ScanFlags::default() });
+    }
+
+    #[test]
+    fn bitmap_regex_and_import() {
+        let input = "import {a} from 'a';\nconst re = /x+/g;\nexport async function f() {}";
+        assert_eq!(token_kind_bitmap(input), HAS_REGEX | HAS_IMPORT | HAS_ASYNC);
+        assert_eq!(token_kind_bitmap("class A {}; s = `x`; f(<>y</>)"),
+                   HAS_CLASS | HAS_TEMPLATE | HAS_JSX);
+    }
+
+    #[test]
+    fn bitmap_plain_arithmetic() {
+        assert_eq!(token_kind_bitmap("var a = (b + 1) / 2 * c;"), 0);
+        assert_eq!(token_kind_bitmap("o.class = o.import; s = 'async'; // await"), 0);
+    }
+}
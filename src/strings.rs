@@ -0,0 +1,382 @@
+use std::borrow::Cow;
+use std::char;
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+
+use structure::{next_meaningful, prev_meaningful};
+use tokenizer::{tokenize_spanned, Spanned, Token};
+use visit::{walk, TokenVisitor};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EscapeError {
+    /// A legacy octal escape such as `\101` or `\08`, which strict mode
+    /// forbids.
+    LegacyOctal { offset: usize },
+    /// `\8` or `\9`, which strict mode forbids.
+    NonOctalDecimal { offset: usize },
+    /// `\x` not followed by two hex digits.
+    InvalidHex { offset: usize },
+    /// `\u` not followed by four hex digits or a braced code point.
+    InvalidUnicode { offset: usize },
+}
+
+impl EscapeError {
+    pub fn offset(&self) -> usize {
+        match *self {
+            EscapeError::LegacyOctal { offset } |
+            EscapeError::NonOctalDecimal { offset } |
+            EscapeError::InvalidHex { offset } |
+            EscapeError::InvalidUnicode { offset } => offset,
+        }
+    }
+}
+
+impl fmt::Display for EscapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.description(), self.offset())
+    }
+}
+
+impl Error for EscapeError {
+    fn description(&self) -> &str {
+        match *self {
+            EscapeError::LegacyOctal { .. } => "octal escape sequences are not allowed in strict mode",
+            EscapeError::NonOctalDecimal { .. } => "\\8 and \\9 are not allowed in strict mode",
+            EscapeError::InvalidHex { .. } => "invalid hexadecimal escape sequence",
+            EscapeError::InvalidUnicode { .. } => "invalid Unicode escape sequence",
+        }
+    }
+}
+
+type Chars<'a> = Peekable<CharIndices<'a>>;
+
+fn hex_digits(chars: &mut Chars, count: usize) -> Option<u32> {
+    let mut value = 0;
+    for _ in 0..count {
+        match chars.next().and_then(|(_, c)| c.to_digit(16)) {
+            Some(digit) => value = value * 16 + digit,
+            None => return None,
+        }
+    }
+    Some(value)
+}
+
+// Parses what follows `\u`: four hex digits or a braced code point. Code
+// units are returned as is, so surrogates still need pairing.
+fn unicode_escape(chars: &mut Chars) -> Option<u32> {
+    if chars.peek().map(|&(_, c)| c) != Some('{') {
+        return hex_digits(chars, 4);
+    }
+
+    chars.next();
+    let mut value: u32 = 0;
+    let mut digits = 0;
+    loop {
+        match chars.next() {
+            Some((_, '}')) if digits > 0 => return Some(value),
+            Some((_, c)) => {
+                match c.to_digit(16) {
+                    Some(digit) if value <= 0x10FFFF => {
+                        value = value * 16 + digit;
+                        digits += 1;
+                    }
+                    _ => return None,
+                }
+            }
+            None => return None,
+        }
+        if value > 0x10FFFF {
+            return None;
+        }
+    }
+}
+
+fn is_high_surrogate(unit: u32) -> bool {
+    unit >= 0xD800 && unit < 0xDC00
+}
+
+fn is_low_surrogate(unit: u32) -> bool {
+    unit >= 0xDC00 && unit < 0xE000
+}
+
+// Reads the digits of a legacy octal escape whose first digit has been
+// consumed: up to three digits in total when starting with 0-3, two otherwise.
+fn legacy_octal(chars: &mut Chars, first: u32) -> u32 {
+    let max_digits = if first <= 3 { 3 } else { 2 };
+    let mut value = first;
+    for _ in 1..max_digits {
+        match chars.peek().and_then(|&(_, c)| c.to_digit(8)) {
+            Some(digit) => {
+                value = value * 8 + digit;
+                chars.next();
+            }
+            None => break,
+        }
+    }
+    value
+}
+
+/// Decodes the value of a string literal, given its source text including
+/// the quotes. Literals without escapes are borrowed.
+///
+/// `\0` not followed by a digit is the null character. Other escapes starting
+/// with an octal digit are legacy octal escapes (`\101` is `A`), and `\8` and
+/// `\9` stand for the digits themselves; in `strict` mode both are errors.
+/// Error offsets are relative to the start of `literal`. Unpaired surrogates
+/// can't be held by a `str` and decode to U+FFFD.
+pub fn string_value(literal: &str, strict: bool) -> Result<Cow<str>, EscapeError> {
+    let quote = match literal.chars().next() {
+        Some(quote) => quote,
+        None => return Ok(Cow::Borrowed(literal)),
+    };
+    let end = if literal.len() >= 2 && literal.ends_with(quote) {
+        literal.len() - quote.len_utf8()
+    } else {
+        literal.len()
+    };
+    let body = &literal[quote.len_utf8()..end];
+    let offset = quote.len_utf8();
+
+    if !body.contains('\\') {
+        return Ok(Cow::Borrowed(body));
+    }
+
+    let mut value = String::with_capacity(body.len());
+    let mut chars = body.char_indices().peekable();
+    let mut high_surrogate: Option<u32> = None;
+
+    while let Some((index, c)) = chars.next() {
+        if c != '\\' {
+            if high_surrogate.take().is_some() {
+                value.push(char::REPLACEMENT_CHARACTER);
+            }
+            value.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next() {
+            Some((_, escaped)) => escaped,
+            None => break,
+        };
+
+        let code = match escaped {
+            'n' => '\n' as u32,
+            't' => '\t' as u32,
+            'r' => '\r' as u32,
+            'b' => '\u{8}' as u32,
+            'f' => '\u{c}' as u32,
+            'v' => '\u{b}' as u32,
+            '0' if !chars.peek().map_or(false, |&(_, c)| c.is_digit(10)) => 0,
+            '0'...'7' => {
+                if strict {
+                    return Err(EscapeError::LegacyOctal { offset: offset + index });
+                }
+                legacy_octal(&mut chars, escaped.to_digit(8).unwrap())
+            }
+            '8' | '9' => {
+                if strict {
+                    return Err(EscapeError::NonOctalDecimal { offset: offset + index });
+                }
+                escaped as u32
+            }
+            'x' => {
+                match hex_digits(&mut chars, 2) {
+                    Some(code) => code,
+                    None => return Err(EscapeError::InvalidHex { offset: offset + index }),
+                }
+            }
+            'u' => {
+                match unicode_escape(&mut chars) {
+                    Some(code) => code,
+                    None => return Err(EscapeError::InvalidUnicode { offset: offset + index }),
+                }
+            }
+            // Line continuations contribute nothing to the value.
+            '\r' => {
+                if chars.peek().map(|&(_, c)| c) == Some('\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '\n' | '\u{2028}' | '\u{2029}' => continue,
+            _ => escaped as u32,
+        };
+
+        if let Some(high) = high_surrogate.take() {
+            if is_low_surrogate(code) {
+                let combined = 0x10000 + ((high - 0xD800) << 10) + (code - 0xDC00);
+                value.push(char::from_u32(combined).unwrap());
+                continue;
+            }
+            value.push(char::REPLACEMENT_CHARACTER);
+        }
+
+        if is_high_surrogate(code) {
+            high_surrogate = Some(code);
+        } else {
+            value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+    }
+
+    if high_surrogate.is_some() {
+        value.push(char::REPLACEMENT_CHARACTER);
+    }
+
+    Ok(Cow::Owned(value))
+}
+
+// Whether the string at `index` is an object key like `{'a': 1}` or names a
+// module, as in `import x from 'a'` and `import 'a'`.
+fn is_key_or_specifier(tokens: &[Spanned], index: usize) -> bool {
+    let prev = prev_meaningful(tokens, index).map(|prev| &tokens[prev].token);
+    let next = next_meaningful(tokens, index).map(|next| &tokens[next].token);
+    match (prev, next) {
+        (Some(&Token::LeftBrace), Some(&Token::Colon)) |
+        (Some(&Token::Comma), Some(&Token::Colon)) => true,
+        (Some(prev), _) => prev.is_word("from") || prev.is_word("import"),
+        _ => false,
+    }
+}
+
+struct StringHarvester<'a: 'b, 'b> {
+    tokens: &'b [Spanned<'a>],
+    skip_keys: bool,
+    found: Vec<(Cow<'a, str>, Range<usize>)>,
+}
+
+impl<'a, 'b> StringHarvester<'a, 'b> {
+    fn harvest(&mut self, index: usize, literal: &'a str) {
+        if self.skip_keys && is_key_or_specifier(self.tokens, index) {
+            return;
+        }
+        if let Ok(value) = string_value(literal, false) {
+            self.found.push((value, self.tokens[index].span.clone()));
+        }
+    }
+}
+
+impl<'a, 'b> TokenVisitor<'a> for StringHarvester<'a, 'b> {
+    fn visit_string(&mut self, index: usize, spanned: &Spanned<'a>) {
+        if let Token::StringLiteral(s) = spanned.token {
+            self.harvest(index, s);
+        }
+    }
+
+    fn visit_template(&mut self, index: usize, spanned: &Spanned<'a>) {
+        match spanned.token {
+            Token::TemplateLiteral(s) if spanned.token.template_substitution_count() == 0 => {
+                self.harvest(index, s)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Collects the decoded value and span of every string literal and every
+/// template literal without substitutions in `input`, such as for gathering
+/// text to translate. With `skip_keys`, strings used as object keys or
+/// module names are left out. Strings with invalid escapes are left out too.
+pub fn harvest_strings(input: &str, skip_keys: bool) -> Vec<(Cow<str>, Range<usize>)> {
+    let tokens = tokenize_spanned(input);
+    let mut harvester = StringHarvester {
+        tokens: &tokens,
+        skip_keys: skip_keys,
+        found: Vec::new(),
+    };
+    walk(&tokens, &mut harvester);
+    harvester.found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_value_plain() {
+        assert!(match string_value("'abc'", true) {
+            Ok(Cow::Borrowed("abc")) => true,
+            _ => false,
+        });
+        assert_eq!(string_value("\"\"", true).unwrap(), "");
+    }
+
+    #[test]
+    fn string_value_escapes() {
+        assert_eq!(string_value(r#""a\nb\t\"\\""#, true).unwrap(), "a\nb\t\"\\");
+        assert_eq!(string_value(r#"'\x41B\u{43}\q'"#, true).unwrap(), "ABCq");
+        assert_eq!(string_value("'a\\\nb'", true).unwrap(), "ab");
+    }
+
+    #[test]
+    fn string_value_surrogates() {
+        assert_eq!(string_value(r#"'\uD83D\uDE00'"#, true).unwrap(), "\u{1F600}");
+        assert_eq!(string_value(r#"'\uD83Dx'"#, true).unwrap(), "\u{FFFD}x");
+    }
+
+    #[test]
+    fn string_value_null() {
+        assert_eq!(string_value(r#""\0""#, true).unwrap(), "\0");
+        assert_eq!(string_value(r#""\0a""#, true).unwrap(), "\0a");
+    }
+
+    #[test]
+    fn string_value_legacy_octal() {
+        assert_eq!(string_value(r#""\101""#, false).unwrap(), "A");
+        assert_eq!(string_value(r#""\1011""#, false).unwrap(), "A1");
+        assert_eq!(string_value(r#""\08""#, false).unwrap(), "\08");
+        assert_eq!(string_value(r#""\477""#, false).unwrap(), "'7");
+        assert_eq!(string_value(r#""\101""#, true),
+                   Err(EscapeError::LegacyOctal { offset: 1 }));
+        assert_eq!(string_value(r#""a\08""#, true),
+                   Err(EscapeError::LegacyOctal { offset: 2 }));
+    }
+
+    #[test]
+    fn string_value_non_octal_decimal() {
+        assert_eq!(string_value(r#""\8""#, false).unwrap(), "8");
+        assert_eq!(string_value(r#""\9""#, false).unwrap(), "9");
+        assert_eq!(string_value(r#""\8""#, true),
+                   Err(EscapeError::NonOctalDecimal { offset: 1 }));
+    }
+
+    #[test]
+    fn string_value_invalid() {
+        assert_eq!(string_value(r#"'\x4'"#, false), Err(EscapeError::InvalidHex { offset: 1 }));
+        assert_eq!(string_value(r#"'\u{110000}'"#, false),
+                   Err(EscapeError::InvalidUnicode { offset: 1 }));
+        assert_eq!(string_value(r#"'\u{}'"#, false),
+                   Err(EscapeError::InvalidUnicode { offset: 1 }));
+    }
+
+    const SAMPLE: &'static str = "import a from 'a';\n\
+                                  var o = {'key': \"Hello,\\n world\", b: `Bye`};\n\
+                                  alert(`Hi ${name}`, '\\x41', '\\x4');";
+
+    fn harvested(skip_keys: bool) -> Vec<(String, Range<usize>)> {
+        harvest_strings(SAMPLE, skip_keys)
+            .into_iter()
+            .map(|(value, span)| (value.into_owned(), span))
+            .collect()
+    }
+
+    #[test]
+    fn harvest_all_strings() {
+        assert_eq!(harvested(false),
+                   vec![(String::from("a"), 14..17),
+                        (String::from("key"), 28..33),
+                        (String::from("Hello,\n world"), 35..51),
+                        (String::from("Bye"), 56..61),
+                        (String::from("A"), 84..90)]);
+    }
+
+    #[test]
+    fn harvest_skips_keys() {
+        assert_eq!(harvested(true),
+                   vec![(String::from("Hello,\n world"), 35..51),
+                        (String::from("Bye"), 56..61),
+                        (String::from("A"), 84..90)]);
+    }
+}
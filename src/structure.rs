@@ -0,0 +1,2014 @@
+use std::ops::Range;
+
+use strings::string_value;
+use tokenizer::{is_keyword, Operator, Relational, Spanned, Token};
+
+/// Index of the first non-greyspace token after `index`.
+pub fn next_meaningful(tokens: &[Spanned], index: usize) -> Option<usize> {
+    (index + 1..tokens.len()).find(|&i| !tokens[i].token.is_greyspace())
+}
+
+/// Index of the last non-greyspace token before `index`.
+pub fn prev_meaningful(tokens: &[Spanned], index: usize) -> Option<usize> {
+    (0..index).rev().find(|&i| !tokens[i].token.is_greyspace())
+}
+
+/// The tokens other than greyspace, in order.
+pub fn meaningful<'a, 'b>(tokens: &'b [Spanned<'a>]) -> Vec<&'b Spanned<'a>> {
+    tokens.iter().filter(|spanned| !spanned.token.is_greyspace()).collect()
+}
+
+/// Given the index of a bracket, returns the index of the bracket pairing
+/// with it, searching forwards from opening brackets and backwards from
+/// closing ones. Mismatched bracket kinds are not checked. The search keeps
+/// a count rather than recursing, so any nesting depth is fine.
+pub fn matching_bracket(tokens: &[Spanned], index: usize) -> Option<usize> {
+    let forwards = match tokens[index].token {
+        Token::LeftParen | Token::LeftBracket | Token::LeftBrace => true,
+        Token::RightParen | Token::RightBracket | Token::RightBrace => false,
+        _ => return None,
+    };
+
+    let mut depth = 0;
+    let mut i = index;
+    loop {
+        match tokens[i].token {
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                if forwards { depth += 1 } else { depth -= 1 }
+            }
+            Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                if forwards { depth -= 1 } else { depth += 1 }
+            }
+            _ => {}
+        }
+        if depth == 0 {
+            return Some(i);
+        }
+
+        if forwards {
+            i += 1;
+            if i == tokens.len() {
+                return None;
+            }
+        } else if i == 0 {
+            return None;
+        } else {
+            i -= 1;
+        }
+    }
+}
+
+fn is_chain_link(token: &Token) -> bool {
+    match *token {
+        Token::Dot | Token::OptionalChain => true,
+        _ => false,
+    }
+}
+
+/// Index of the first token of the member access and call chain (`a.b?.[c](d)`)
+/// whose last token is at `end`.
+pub fn chain_start(tokens: &[Spanned], end: usize) -> usize {
+    let mut index = end;
+    loop {
+        match tokens[index].token {
+            Token::RightParen | Token::RightBracket => {
+                match matching_bracket(tokens, index) {
+                    Some(open) => index = open,
+                    None => return index,
+                }
+            }
+            _ => {}
+        }
+
+        let prev = match prev_meaningful(tokens, index) {
+            Some(prev) => prev,
+            None => return index,
+        };
+        let prev_token = &tokens[prev].token;
+        let is_call_or_index = match tokens[index].token {
+            Token::LeftParen | Token::LeftBracket => true,
+            _ => false,
+        };
+
+        if is_chain_link(prev_token) {
+            match prev_meaningful(tokens, prev) {
+                Some(object) => index = object,
+                None => return index,
+            }
+        } else if is_call_or_index && (is_chain_link(prev_token) || ends_value(prev_token)) {
+            index = prev;
+        } else {
+            return index;
+        }
+    }
+}
+
+/// Index of the last token of the member access and call chain starting at
+/// `start`.
+pub fn chain_end(tokens: &[Spanned], start: usize) -> usize {
+    let mut index = match tokens[start].token {
+        Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+            matching_bracket(tokens, start).unwrap_or(tokens.len() - 1)
+        }
+        _ => start,
+    };
+
+    while let Some(next) = next_meaningful(tokens, index) {
+        match tokens[next].token {
+            Token::Dot | Token::OptionalChain => {
+                index = match next_meaningful(tokens, next) {
+                    Some(property) => property,
+                    None => return next,
+                };
+                if tokens[index].token == Token::LeftBracket ||
+                   tokens[index].token == Token::LeftParen {
+                    index = matching_bracket(tokens, index).unwrap_or(tokens.len() - 1);
+                }
+            }
+            Token::LeftParen | Token::LeftBracket => {
+                index = matching_bracket(tokens, next).unwrap_or(tokens.len() - 1);
+            }
+            _ => break,
+        }
+    }
+
+    index
+}
+
+fn is_chain_root(token: &Token) -> bool {
+    match *token {
+        Token::Identifier(s) | Token::Keyword(s) => {
+            !is_keyword(s) || s == "this" || s == "super"
+        }
+        _ => false,
+    }
+}
+
+/// Splits member access chains like `a.b?.[c].d` into the spans of their
+/// parts: the root identifier, each property name, and each bracketed access
+/// including its brackets. A call ends the chain, so `a.b().c` gives `a.b`.
+/// Chains nested inside brackets are reported separately, and lone
+/// identifiers aren't reported at all.
+pub fn member_access_chains(tokens: &[Spanned]) -> Vec<Vec<Range<usize>>> {
+    let mut chains = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        let is_property = prev_meaningful(tokens, index)
+                              .map_or(false, |prev| is_chain_link(&tokens[prev].token));
+        if is_property || !is_chain_root(&spanned.token) {
+            continue;
+        }
+
+        let mut parts = vec![spanned.span.clone()];
+        let mut current = index;
+        while let Some(next) = next_meaningful(tokens, current) {
+            let access = match tokens[next].token {
+                Token::Dot | Token::OptionalChain => next_meaningful(tokens, next),
+                Token::LeftBracket => Some(next),
+                _ => None,
+            };
+            let (start, end) = match access.map(|access| (access, &tokens[access].token)) {
+                Some((access, &Token::Identifier(_))) |
+                Some((access, &Token::Keyword(_))) |
+                Some((access, &Token::PrivateIdentifier(_))) => (access, access),
+                Some((access, &Token::LeftBracket)) => {
+                    match matching_bracket(tokens, access) {
+                        Some(close) => (access, close),
+                        None => break,
+                    }
+                }
+                _ => break,
+            };
+            parts.push(tokens[start].span.start..tokens[end].span.end);
+            current = end;
+        }
+
+        if parts.len() > 1 {
+            chains.push(parts);
+        }
+    }
+
+    chains
+}
+
+/// Finds computed member accesses like `obj[key]` and `obj?.[key]`, told
+/// apart from array literals by a value such as an identifier, a literal,
+/// `)` or `]` coming before the `[`. Each span runs from the start of the
+/// object's member access and call chain, as in `f()[c]`, to the `]`.
+pub fn find_computed_members(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        if spanned.token != Token::LeftBracket {
+            continue;
+        }
+        let is_member = prev_meaningful(tokens, index).map_or(false, |prev| {
+            let prev = &tokens[prev].token;
+            *prev == Token::OptionalChain || ends_value(prev)
+        });
+        if let (true, Some(close)) = (is_member, matching_bracket(tokens, index)) {
+            found.push(tokens[chain_start(tokens, close)].span.start..tokens[close].span.end);
+        }
+    }
+
+    found
+}
+
+// Index of the last token of the `new` expression whose `new` is at
+// `new_index`: the member access chain of the constructor, which may itself
+// be a `new` expression, followed by the argument list if there is one.
+fn new_expression_end(tokens: &[Spanned], new_index: usize) -> usize {
+    let callee = match next_meaningful(tokens, new_index) {
+        Some(callee) => callee,
+        None => return new_index,
+    };
+    let mut end = if tokens[callee].token.is_word("new") {
+        new_expression_end(tokens, callee)
+    } else if tokens[callee].token == Token::LeftParen {
+        matching_bracket(tokens, callee).unwrap_or(tokens.len() - 1)
+    } else {
+        callee
+    };
+
+    while let Some(next) = next_meaningful(tokens, end) {
+        match tokens[next].token {
+            Token::Dot => {
+                end = match next_meaningful(tokens, next) {
+                    Some(property) => property,
+                    None => return next,
+                };
+            }
+            Token::LeftBracket => end = matching_bracket(tokens, next).unwrap_or(tokens.len() - 1),
+            // The first argument list belongs to `new`, ending the expression.
+            Token::LeftParen => return matching_bracket(tokens, next).unwrap_or(tokens.len() - 1),
+            _ => break,
+        }
+    }
+
+    end
+}
+
+/// Finds `new` expressions like `new Foo(x)`, `new a.b.C()` and `new Foo`,
+/// each running from `new` through the constructor's member access chain to
+/// the end of the argument list, if any. A call or access after the
+/// argument list, as in `new Foo().bar`, isn't included. Nested `new`
+/// expressions are reported separately, and `new.target` isn't reported.
+pub fn find_new_expressions(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        if !spanned.token.is_word("new") {
+            continue;
+        }
+        let is_property = prev_meaningful(tokens, index).map_or(false, |prev| {
+            is_chain_link(&tokens[prev].token)
+        });
+        let is_meta_property = next_meaningful(tokens, index).map_or(true, |next| {
+            tokens[next].token == Token::Dot
+        });
+        if !is_property && !is_meta_property {
+            let end = new_expression_end(tokens, index);
+            found.push(spanned.span.start..tokens[end].span.end);
+        }
+    }
+
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrowBody {
+    /// `=> { ... }`
+    Block,
+    /// `=> x`, including a parenthesized object literal as in `=> ({})`.
+    Expression,
+}
+
+/// Whether the arrow function whose `=>` is at `arrow_index` has a block or
+/// an expression body.
+pub fn arrow_body_kind(tokens: &[Spanned], arrow_index: usize) -> ArrowBody {
+    match next_meaningful(tokens, arrow_index).map(|index| &tokens[index].token) {
+        Some(&Token::LeftBrace) => ArrowBody::Block,
+        _ => ArrowBody::Expression,
+    }
+}
+
+// Index of the innermost unclosed bracket before `index`.
+fn enclosing_bracket(tokens: &[Spanned], index: usize) -> Option<usize> {
+    let mut i = index;
+    while i > 0 {
+        i -= 1;
+        match tokens[i].token {
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => return Some(i),
+            Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                i = match matching_bracket(tokens, i) {
+                    Some(open) => open,
+                    None => return None,
+                };
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Whether the `(` at `open` holds function parameters: it follows
+// `function`, `function name` or `catch`, is followed by `=>` once closed, or
+// is followed by a body as in the method definition `name(a) {}`.
+fn is_parameter_list(tokens: &[Spanned], open: usize) -> bool {
+    let prev = prev_meaningful(tokens, open).map(|prev| (prev, &tokens[prev].token));
+    let after_function = match prev {
+        Some((_, token)) if token.is_word("function") || token.is_word("catch") => true,
+        Some((prev, &Token::Identifier(_))) => {
+            prev_meaningful(tokens, prev)
+                .map_or(false, |keyword| tokens[keyword].token.is_word("function"))
+        }
+        _ => false,
+    };
+    if after_function {
+        return true;
+    }
+
+    let after = matching_bracket(tokens, open)
+                    .and_then(|close| next_meaningful(tokens, close))
+                    .map(|after| &tokens[after].token);
+    match (prev.map(|(_, token)| token), after) {
+        (_, Some(&Token::Arrow)) => true,
+        (Some(&Token::Identifier(s)), Some(&Token::LeftBrace)) => !is_statement_keyword(s),
+        _ => false,
+    }
+}
+
+/// Finds array and object destructuring patterns: after `var`, `let` and
+/// `const`, among function parameters, and on the left of `=`. Nested
+/// patterns are covered by the span of the outermost one.
+///
+/// `{a} = x` at the start of a statement would be a block followed by a
+/// syntax error, so it's reported as the pattern it was presumably meant to
+/// be, like the valid `({a} = x)`.
+pub fn find_destructuring_patterns(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let is_object = match tokens[index].token {
+            Token::LeftBrace => true,
+            Token::LeftBracket => false,
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+        let close = match matching_bracket(tokens, index) {
+            Some(close) => close,
+            None => break,
+        };
+
+        let prev = prev_meaningful(tokens, index).map(|prev| &tokens[prev].token);
+        let is_declaration = prev.map_or(false, |prev| {
+            prev.is_word("var") || prev.is_word("let") || prev.is_word("const")
+        });
+        let is_assigned = next_meaningful(tokens, close).map_or(false, |next| {
+            tokens[next].token == Token::Equal
+        }) && (is_object || !prev.map_or(false, ends_value));
+        let is_parameter = match prev {
+            Some(&Token::LeftParen) | Some(&Token::Comma) => {
+                enclosing_bracket(tokens, index).map_or(false, |open| {
+                    tokens[open].token == Token::LeftParen && is_parameter_list(tokens, open)
+                })
+            }
+            _ => false,
+        };
+
+        if is_declaration || is_assigned || is_parameter {
+            found.push(tokens[index].span.start..tokens[close].span.end);
+            index = close;
+        }
+        index += 1;
+    }
+
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpreadRole {
+    /// Expands an iterable or object, as in `f(...a)`, `[...a]` or `{...o}`.
+    Spread,
+    /// Collects the remaining items, as in `function f(...a)` or
+    /// `const [a, ...b] = c`.
+    Rest,
+}
+
+// Whether the bracket at `open` is one of `patterns` or nested inside one as
+// an element or property value, rather than inside a default value.
+fn is_in_pattern(tokens: &[Spanned], mut open: usize, patterns: &[Range<usize>]) -> bool {
+    loop {
+        if patterns.iter().any(|pattern| pattern.start == tokens[open].span.start) {
+            return true;
+        }
+        match prev_meaningful(tokens, open).map(|prev| &tokens[prev].token) {
+            Some(&Token::LeftBracket) |
+            Some(&Token::LeftBrace) |
+            Some(&Token::Comma) |
+            Some(&Token::Colon) |
+            Some(&Token::Ellipsis) => {}
+            _ => return false,
+        }
+        open = match enclosing_bracket(tokens, open) {
+            Some(outer) if tokens[outer].token != Token::LeftParen => outer,
+            _ => return false,
+        };
+    }
+}
+
+/// Classifies each `Token::Ellipsis` as spreading or collecting the rest,
+/// returning one role per `...` in order.
+///
+/// Rest elements are those directly in a parameter list, or inside a
+/// destructuring pattern as found by `find_destructuring_patterns`, so the
+/// same heuristics apply.
+pub fn classify_spread(tokens: &[Spanned]) -> Vec<SpreadRole> {
+    let patterns = find_destructuring_patterns(tokens);
+
+    tokens.iter()
+          .enumerate()
+          .filter(|&(_, spanned)| spanned.token == Token::Ellipsis)
+          .map(|(index, _)| {
+              let is_rest = enclosing_bracket(tokens, index).map_or(false, |open| {
+                  if tokens[open].token == Token::LeftParen {
+                      is_parameter_list(tokens, open)
+                  } else {
+                      is_in_pattern(tokens, open, &patterns)
+                  }
+              });
+              if is_rest { SpreadRole::Rest } else { SpreadRole::Spread }
+          })
+          .collect()
+}
+
+// Whether a type is expected after `token` within a type annotation.
+fn expects_type(token: &Token) -> bool {
+    match *token {
+        Token::Colon |
+        Token::BitwiseOr |
+        Token::BitwiseAnd |
+        Token::Comma |
+        Token::Arrow |
+        Token::Relational(Relational::Less) => true,
+        _ => false,
+    }
+}
+
+// Index of the first token after the type annotation whose `:` is at
+// `colon`, or `tokens.len()`. Parameter types end at `,`, `=` or the closing
+// `)`, return types also at the function body or `=>`.
+fn type_annotation_end(tokens: &[Spanned], colon: usize, is_return: bool) -> usize {
+    // Open `<` of type arguments.
+    let mut angles = 0usize;
+    let mut prev = colon;
+    let mut index = colon + 1;
+    while index < tokens.len() {
+        let token = &tokens[index].token;
+        if token.is_greyspace() {
+            index += 1;
+            continue;
+        }
+
+        let ends = match *token {
+            Token::Comma |
+            Token::Equal |
+            Token::Semicolon |
+            Token::RightParen |
+            Token::RightBracket |
+            Token::RightBrace => true,
+            // An object type such as `: {a: T}` can't be told from a body
+            // by itself, but a body can't follow `:` or `|`.
+            Token::LeftBrace => is_return && !expects_type(&tokens[prev].token),
+            // `() => T` is a function type.
+            Token::Arrow => is_return && tokens[prev].token != Token::RightParen,
+            _ => false,
+        };
+        if ends && angles == 0 {
+            return index;
+        }
+
+        match *token {
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                index = match matching_bracket(tokens, index) {
+                    Some(close) => close,
+                    None => return tokens.len(),
+                };
+            }
+            Token::Relational(Relational::Less) => angles += 1,
+            Token::Relational(Relational::Greater) => angles = angles.saturating_sub(1),
+            Token::BitShift(Operator::RightShift) => angles = angles.saturating_sub(2),
+            Token::BitShift(Operator::UnsignedRightShift) => angles = angles.saturating_sub(3),
+            _ => {}
+        }
+        prev = index;
+        index += 1;
+    }
+    tokens.len()
+}
+
+// Span of the annotation from the `:` at `colon` up to the token at `end`,
+// including the `?` of an optional parameter.
+fn annotation_span(tokens: &[Spanned], colon: usize, end: usize) -> Range<usize> {
+    let start = match prev_meaningful(tokens, colon) {
+        Some(question) if tokens[question].token == Token::QuestionMark => question,
+        _ => colon,
+    };
+    let last = prev_meaningful(tokens, end).unwrap_or(colon);
+    tokens[start].span.start..tokens[last].span.end
+}
+
+/// Finds TypeScript-style type annotations of function parameters and return
+/// types, as in `function f(a: number, b?: string): void {}`, so that tools
+/// only understanding JavaScript can skip them. Each span runs from the `:`,
+/// or the `?` of an optional parameter, to the end of the type.
+///
+/// This is a heuristic meant for input tokenized with
+/// `TokenizeOptions::type_annotations`. Parameter lists are recognized like
+/// in `find_destructuring_patterns`, or by a return type followed by a body
+/// or `=>`; those of generic functions like `f<T>(a: T)` are missed.
+pub fn strip_type_annotations(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let mut found: Vec<Range<usize>> = Vec::new();
+
+    for open in 0..tokens.len() {
+        if tokens[open].token != Token::LeftParen ||
+           found.last().map_or(false, |last| tokens[open].span.start < last.end) {
+            continue;
+        }
+        let close = match matching_bracket(tokens, open) {
+            Some(close) => close,
+            None => break,
+        };
+
+        let return_type = match next_meaningful(tokens, close) {
+            Some(colon) if tokens[colon].token == Token::Colon => {
+                Some((colon, type_annotation_end(tokens, colon, true)))
+            }
+            _ => None,
+        };
+        let has_body = return_type.map_or(false, |(_, end)| {
+            match tokens.get(end).map(|spanned| &spanned.token) {
+                Some(&Token::LeftBrace) | Some(&Token::Arrow) => true,
+                _ => false,
+            }
+        });
+        if !has_body && !is_parameter_list(tokens, open) {
+            continue;
+        }
+
+        let mut index = open + 1;
+        while index < close {
+            match tokens[index].token {
+                // Skips default values and the renames of destructuring
+                // patterns like `{a: b}`.
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                    index = matching_bracket(tokens, index).unwrap_or(close);
+                }
+                Token::Colon => {
+                    let end = type_annotation_end(tokens, index, false);
+                    found.push(annotation_span(tokens, index, end));
+                    index = end;
+                    continue;
+                }
+                _ => {}
+            }
+            index += 1;
+        }
+
+        if let Some((colon, end)) = return_type {
+            found.push(annotation_span(tokens, colon, end));
+        }
+    }
+
+    found
+}
+
+// Whether the `{` at `open` holds the names of an `import` or `export`
+// declaration, where `as` renames a binding.
+fn is_module_names(tokens: &[Spanned], open: usize) -> bool {
+    let after_keyword = prev_meaningful(tokens, open).map_or(false, |prev| {
+        let prev = &tokens[prev].token;
+        prev.is_word("import") || prev.is_word("export") || *prev == Token::Comma
+    });
+    let before_from = matching_bracket(tokens, open)
+                          .and_then(|close| next_meaningful(tokens, close))
+                          .map_or(false, |next| tokens[next].token.is_word("from"));
+    after_keyword || before_from
+}
+
+/// Finds TypeScript type assertions with `as` and `satisfies`, as in
+/// `x as number` and `config satisfies Options`, so that tools only
+/// understanding JavaScript can skip them. Each span runs from the operator
+/// to the end of the type, which ends like a parameter's type annotation or
+/// where a semicolon would be inserted.
+///
+/// This is a heuristic meant for input tokenized with
+/// `TokenizeOptions::type_annotations`. `as` only counts after a value,
+/// such as an object literal, and not in `import` or `export` declarations,
+/// so it is left alone when used as an identifier.
+pub fn strip_as_assertions(tokens: &[Spanned]) -> Vec<Range<usize>> {
+    let asi = asi_points(tokens);
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        let is_operator = spanned.token.is_word("as") || spanned.token.is_word("satisfies");
+        if !is_operator ||
+           found.last().map_or(false, |last: &Range<usize>| spanned.span.start < last.end) {
+            continue;
+        }
+        let after_value = prev_meaningful(tokens, index).map_or(false, |prev| {
+            match tokens[prev].token {
+                Token::RightBrace => {
+                    matching_bracket(tokens, prev).map_or(false, |open| {
+                        is_object_literal(tokens, open)
+                    })
+                }
+                ref token => ends_value(token),
+            }
+        });
+        let before_type = next_meaningful(tokens, index).map_or(false, |next| {
+            match tokens[next].token {
+                Token::Identifier(_) |
+                Token::Keyword(_) |
+                Token::StringLiteral(_) |
+                Token::NumericLiteral(_) |
+                Token::TemplateLiteral(_) |
+                Token::LeftParen |
+                Token::LeftBracket |
+                Token::LeftBrace => true,
+                _ => false,
+            }
+        });
+        let renames = enclosing_bracket(tokens, index).map_or(false, |open| {
+            tokens[open].token == Token::LeftBrace && is_module_names(tokens, open)
+        });
+        if !after_value || !before_type || renames {
+            continue;
+        }
+
+        let mut end = type_annotation_end(tokens, index, false);
+        if let Some(&point) = asi.iter().find(|&&point| point > index) {
+            end = end.min(point + 1);
+        }
+        let last = prev_meaningful(tokens, end).unwrap_or(index);
+        found.push(spanned.span.start..tokens[last].span.end);
+    }
+
+    found
+}
+
+// Whether `token` can appear within type arguments, other than `<`, `>`,
+// `>>` and `>>>`.
+fn in_type_arguments(token: &Token) -> bool {
+    match *token {
+        Token::Identifier(_) |
+        Token::Keyword(_) |
+        Token::StringLiteral(_) |
+        Token::NumericLiteral(_) |
+        Token::Comma |
+        Token::Dot |
+        Token::Colon |
+        Token::Semicolon |
+        Token::QuestionMark |
+        Token::Arrow |
+        Token::Ellipsis |
+        Token::BitwiseOr |
+        Token::BitwiseAnd |
+        Token::LeftParen |
+        Token::RightParen |
+        Token::LeftBracket |
+        Token::RightBracket |
+        Token::LeftBrace |
+        Token::RightBrace => true,
+        _ => false,
+    }
+}
+
+// If the `<` at `open` starts type arguments, the index of the token
+// closing them and of each `>>` or `>>>` closing two or three levels of
+// them. They're taken to be type arguments if the `<` is closed before any
+// token that can't appear in a type, as in `a < b && c > d`, or a shift
+// closing more levels than are open.
+fn type_arguments(tokens: &[Spanned], open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0usize;
+    let mut shifts = Vec::new();
+    for index in open..tokens.len() {
+        match tokens[index].token {
+            Token::Relational(Relational::Less) => depth += 1,
+            Token::Relational(Relational::Greater) => depth -= 1,
+            Token::BitShift(Operator::RightShift) if depth >= 2 => {
+                depth -= 2;
+                shifts.push(index);
+            }
+            Token::BitShift(Operator::UnsignedRightShift) if depth >= 3 => {
+                depth -= 3;
+                shifts.push(index);
+            }
+            ref token if token.is_greyspace() || in_type_arguments(token) => {}
+            _ => return None,
+        }
+        if depth == 0 {
+            return Some((index, shifts));
+        }
+    }
+    None
+}
+
+/// Splits each `>>` or `>>>` closing nested type arguments, as in
+/// `Array<Array<T>>`, into one `Relational(Greater)` token per `>`, leaving
+/// shift operators intact. The tokenizer always emits shifts, since it
+/// can't tell them apart.
+///
+/// Type arguments are recognized by a `<` after an identifier that is
+/// closed before any token that can't appear in a type.
+pub fn split_type_argument_closes(tokens: Vec<Spanned>) -> Vec<Spanned> {
+    let mut shifts = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        let after_identifier = match prev_meaningful(&tokens, index) {
+            Some(prev) => {
+                match tokens[prev].token {
+                    Token::Identifier(s) => !is_keyword(s),
+                    _ => false,
+                }
+            }
+            None => false,
+        };
+        if tokens[index].token == Token::Relational(Relational::Less) && after_identifier {
+            if let Some((close, closing_shifts)) = type_arguments(&tokens, index) {
+                shifts.extend(closing_shifts);
+                index = close;
+            }
+        }
+        index += 1;
+    }
+
+    let mut split = Vec::with_capacity(tokens.len() + 2 * shifts.len());
+    for (index, spanned) in tokens.into_iter().enumerate() {
+        if shifts.binary_search(&index).is_err() {
+            split.push(spanned);
+            continue;
+        }
+        let start = spanned.span.start;
+        for offset in spanned.span {
+            if offset > start {
+                // Like the tokenizer, separates adjacent tokens with empty
+                // whitespace.
+                split.push(Spanned {
+                    token: Token::Whitespace(""),
+                    span: offset..offset,
+                });
+            }
+            split.push(Spanned {
+                token: Token::Relational(Relational::Greater),
+                span: offset..offset + 1,
+            });
+        }
+    }
+
+    split
+}
+
+/// Whether `token` is whitespace or a block comment containing a line
+/// terminator.
+pub fn has_line_break(token: &Token) -> bool {
+    match *token {
+        Token::Whitespace(s) | Token::BlockComment(s) | Token::Annotation(s) => {
+            s.contains('\n') || s.contains('\r') || s.contains('\u{2028}') || s.contains('\u{2029}')
+        }
+        _ => false,
+    }
+}
+
+// Words after which a line break ends the statement.
+fn is_restricted_word(token: &Token) -> bool {
+    token.is_word("return") || token.is_word("break") || token.is_word("continue") ||
+    token.is_word("throw") || token.is_word("yield")
+}
+
+// Whether a statement can end with `token`. `}` is handled by the caller
+// since it depends on whether it closed a block.
+fn can_end_statement(token: &Token) -> bool {
+    match *token {
+        Token::Identifier(s) | Token::Keyword(s) => {
+            !is_keyword(s) || s == "this" || s == "super" || s == "null" || s == "true" ||
+            s == "false" || s == "debugger" || s == "break" || s == "continue"
+        }
+        Token::DeIncrement(_) => true,
+        _ => ends_value(token),
+    }
+}
+
+// Whether a line starting with `token` can't continue the statement on the
+// line before, so that a semicolon is inserted between them.
+fn starts_new_statement(token: &Token) -> bool {
+    match *token {
+        Token::Identifier(s) | Token::Keyword(s) => s != "in" && s != "instanceof" && s != "of",
+        Token::NumericLiteral(_) |
+        Token::StringLiteral(_) |
+        Token::RegexLiteral(_) |
+        Token::LeftBrace |
+        Token::DeIncrement(_) |
+        Token::ExclamationMark |
+        Token::BitwiseNot => true,
+        _ => false,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bracket {
+    Block,
+    ObjectLiteral,
+    // A parenthesized `if`, `for`, `while` or similar head.
+    StatementHead,
+    Other,
+}
+
+/// Finds where automatic semicolon insertion adds semicolons, returning the
+/// index of each token a semicolon is inserted after.
+///
+/// This follows the three rules of the spec: a semicolon is inserted before
+/// a token on a new line that can't continue the statement (always after
+/// `return`, `break`, `continue`, `throw` and `yield`, and before `++` and
+/// `--`), before a `}` closing a block, and at the end of the input. Being
+/// token based, "can't continue" is judged from the tokens on either side of
+/// the line break, so a line starting with `(`, `[` or a template literal
+/// continues the previous one, as it does in JS. Either spanned or plain
+/// tokens can be given.
+pub fn asi_points<'a, T: AsRef<Token<'a>>>(tokens: &[T]) -> Vec<usize> {
+    let mut points = Vec::new();
+    let mut brackets: Vec<Bracket> = Vec::new();
+    let mut prev: Option<usize> = None;
+    let mut prev_ends_statement = false;
+    let mut line_break = false;
+
+    for (index, token) in tokens.iter().enumerate() {
+        let token = token.as_ref();
+        if token.is_greyspace() {
+            line_break = line_break || has_line_break(token);
+            continue;
+        }
+
+        let in_statement_list = match brackets.last() {
+            None | Some(&Bracket::Block) => true,
+            _ => false,
+        };
+        if let Some(prev) = prev {
+            let closes_block = *token == Token::RightBrace &&
+                               brackets.last() == Some(&Bracket::Block);
+            let inserted = if closes_block {
+                prev_ends_statement
+            } else if in_statement_list && line_break {
+                let prev_token = tokens[prev].as_ref();
+                if is_restricted_word(prev_token) {
+                    *token != Token::Semicolon
+                } else {
+                    prev_ends_statement && starts_new_statement(token)
+                }
+            } else {
+                false
+            };
+            if inserted {
+                points.push(prev);
+            }
+        }
+
+        let prev_token = prev.map(|prev| tokens[prev].as_ref());
+        prev_ends_statement = match *token {
+            Token::LeftParen => {
+                let is_head = prev_token.map_or(false, |prev| {
+                    prev.is_word("if") || prev.is_word("for") || prev.is_word("while") ||
+                    prev.is_word("with") || prev.is_word("switch") || prev.is_word("catch")
+                });
+                brackets.push(if is_head { Bracket::StatementHead } else { Bracket::Other });
+                false
+            }
+            Token::LeftBracket => {
+                brackets.push(Bracket::Other);
+                false
+            }
+            Token::LeftBrace => {
+                brackets.push(if starts_object_literal(prev_token) {
+                    Bracket::ObjectLiteral
+                } else {
+                    Bracket::Block
+                });
+                false
+            }
+            Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                match brackets.pop() {
+                    Some(Bracket::Block) | Some(Bracket::StatementHead) => false,
+                    _ => true,
+                }
+            }
+            Token::Semicolon => false,
+            _ => can_end_statement(token),
+        };
+        prev = Some(index);
+        line_break = false;
+    }
+
+    if let Some(prev) = prev {
+        if prev_ends_statement || is_restricted_word(tokens[prev].as_ref()) {
+            points.push(prev);
+        }
+    }
+
+    points
+}
+
+/// Finds the semicolons ending statements, returning the index of each `;`
+/// other than those separating the parts of a `for` header.
+pub fn statement_semicolons(tokens: &[Spanned]) -> Vec<usize> {
+    let mut for_headers = Vec::new();
+    for (index, spanned) in tokens.iter().enumerate() {
+        if !spanned.token.is_word("for") {
+            continue;
+        }
+        let open = match next_meaningful(tokens, index) {
+            Some(open) if tokens[open].token == Token::LeftParen => open,
+            _ => continue,
+        };
+        if let Some(close) = matching_bracket(tokens, open) {
+            for_headers.push(open..close);
+        }
+    }
+
+    (0..tokens.len())
+        .filter(|&index| {
+            tokens[index].token == Token::Semicolon &&
+            !for_headers.iter().any(|header| header.start < index && index < header.end)
+        })
+        .collect()
+}
+
+/// Inserts a zero-width `Token::StatementEnd` after each `;` found by
+/// `statement_semicolons` and each token `asi_points` finds a semicolon
+/// would be inserted after. `TokenizeOptions::emit_statement_boundaries`
+/// runs this while tokenizing.
+pub fn mark_statement_ends(tokens: Vec<Spanned>) -> Vec<Spanned> {
+    let asi = asi_points(&tokens);
+    let semicolons = statement_semicolons(&tokens);
+
+    let mut marked = Vec::with_capacity(tokens.len() + asi.len() + semicolons.len());
+    for (index, spanned) in tokens.into_iter().enumerate() {
+        let end = spanned.span.end;
+        marked.push(spanned);
+        if asi.binary_search(&index).is_ok() || semicolons.binary_search(&index).is_ok() {
+            marked.push(Spanned {
+                token: Token::StatementEnd,
+                span: end..end,
+            });
+        }
+    }
+    marked
+}
+
+/// The byte range of the expression starting at the token at `start`,
+/// found by scanning forward over whole bracketed groups until a `;`, a `,`
+/// or `:` that isn't part of the expression, an unmatched closing bracket,
+/// or the end of a line where a semicolon would be inserted.
+///
+/// Without a parser, precedence isn't taken into account, so the range is
+/// that of the largest expression starting there: from `a` in `a = b + c;`
+/// the whole assignment is covered. Keywords don't stop the scan either.
+///
+/// `asi` holds the points `asi_points` finds in `tokens`, so that they are
+/// found once however many expressions are looked at. A `start` past the
+/// last token gives the empty range `start..start`.
+pub fn expression_extent(tokens: &[Spanned], asi: &[usize], start: usize) -> Range<usize> {
+    if start >= tokens.len() {
+        return start..start;
+    }
+    let first = if tokens[start].token.is_greyspace() {
+        match next_meaningful(tokens, start) {
+            Some(first) => first,
+            None => return tokens[start].span.end..tokens[start].span.end,
+        }
+    } else {
+        start
+    };
+    let mut last = first;
+    let mut ternaries = 0;
+    let mut index = first;
+
+    while index < tokens.len() {
+        let end = match tokens[index].token {
+            _ if tokens[index].token.is_greyspace() => {
+                index += 1;
+                continue;
+            }
+            Token::Semicolon |
+            Token::Comma |
+            Token::RightParen |
+            Token::RightBracket |
+            Token::RightBrace => break,
+            Token::Colon if ternaries == 0 => break,
+            Token::Colon => {
+                ternaries -= 1;
+                index
+            }
+            Token::QuestionMark => {
+                ternaries += 1;
+                index
+            }
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => {
+                matching_bracket(tokens, index).unwrap_or(tokens.len() - 1)
+            }
+            _ => index,
+        };
+        last = end;
+        if asi.binary_search(&end).is_ok() {
+            break;
+        }
+        index = end + 1;
+    }
+
+    tokens[first].span.start..tokens[last].span.end
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommaRole {
+    /// The comma operator, as in `(a, b)` or `for (i = 0, j = 1;;)`.
+    Sequence,
+    /// Separates list items: arguments, parameters, array elements,
+    /// properties and declarators.
+    Separator,
+}
+
+// Whether an expression can end with `token`, making a following `(` a call
+// and a following `[` a member access.
+fn ends_value(token: &Token) -> bool {
+    match *token {
+        Token::Identifier(s) => !is_statement_keyword(s),
+        Token::PrivateIdentifier(_) |
+        Token::NumericLiteral(_) |
+        Token::StringLiteral(_) |
+        Token::TemplateLiteral(_) |
+        Token::RegexLiteral(_) |
+        Token::RightParen |
+        Token::RightBracket => true,
+        Token::Keyword(s) => s == "this" || s == "super" || s == "null" || s == "true" || s == "false",
+        _ => false,
+    }
+}
+
+// Keywords that are followed by a parenthesized expression rather than a
+// call. They can show up as identifiers when written without whitespace,
+// as in `if(a)`.
+fn is_statement_keyword(s: &str) -> bool {
+    s == "if" || s == "while" || s == "for" || s == "switch" || s == "with" ||
+    s == "catch" || s == "return" || s == "typeof" || s == "void" || s == "delete" ||
+    s == "throw" || s == "in" || s == "of" || s == "new"
+}
+
+fn starts_object_literal(prev: Option<&Token>) -> bool {
+    match prev {
+        None => false,
+        Some(&Token::RightParen) |
+        Some(&Token::Arrow) |
+        Some(&Token::Semicolon) |
+        Some(&Token::LeftBrace) |
+        Some(&Token::RightBrace) => false,
+        Some(token) if token.is_word("return") => true,
+        Some(&Token::Identifier(_)) | Some(&Token::Keyword(_)) => false,
+        Some(_) => true,
+    }
+}
+
+/// Whether the `{` at `open` starts an object literal rather than a block,
+/// judged from the token before it as `comma_roles` does.
+pub fn is_object_literal(tokens: &[Spanned], open: usize) -> bool {
+    starts_object_literal(prev_meaningful(tokens, open).map(|prev| &tokens[prev].token))
+}
+
+struct Group {
+    // Role of commas directly inside this group.
+    role: CommaRole,
+    // Inside a `var`/`let`/`const` declaration, whose commas separate declarators.
+    declaration: bool,
+    // Indexes into the result of the commas directly inside this group.
+    commas: Vec<usize>,
+    is_paren: bool,
+}
+
+impl Group {
+    fn new(role: CommaRole, is_paren: bool) -> Group {
+        Group {
+            role: role,
+            declaration: false,
+            commas: Vec::new(),
+            is_paren: is_paren,
+        }
+    }
+}
+
+/// Classifies each `Token::Comma` as either the comma operator or a list
+/// separator, returning one role per comma in order.
+///
+/// The context is judged from the tokens before each bracket: `f(` and
+/// `function (` hold lists while `(`, `if (` and `for (` hold expressions,
+/// `[` is an array literal unless it follows a value, and `{` is an object
+/// literal where an expression is expected and a block otherwise.
+/// Parenthesized commas followed by `=>` are arrow parameters.
+pub fn comma_roles(tokens: &[Spanned]) -> Vec<CommaRole> {
+    let tokens = meaningful(tokens);
+    let mut roles = Vec::new();
+    let mut groups = vec![Group::new(CommaRole::Sequence, false)];
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        let token = &spanned.token;
+        let prev = if index > 0 { Some(&tokens[index - 1].token) } else { None };
+        match *token {
+            Token::LeftParen => {
+                let role = match prev {
+                    Some(prev) if prev.is_word("function") => CommaRole::Separator,
+                    Some(prev) if ends_value(prev) => CommaRole::Separator,
+                    _ => CommaRole::Sequence,
+                };
+                groups.push(Group::new(role, true));
+            }
+            Token::LeftBracket => {
+                let role = match prev {
+                    Some(prev) if ends_value(prev) => CommaRole::Sequence,
+                    _ => CommaRole::Separator,
+                };
+                groups.push(Group::new(role, false));
+            }
+            Token::LeftBrace => {
+                let role = if starts_object_literal(prev) {
+                    CommaRole::Separator
+                } else {
+                    CommaRole::Sequence
+                };
+                groups.push(Group::new(role, false));
+            }
+            Token::RightParen |
+            Token::RightBracket |
+            Token::RightBrace => {
+                if groups.len() > 1 {
+                    let group = groups.pop().unwrap();
+                    let is_arrow_params = group.is_paren &&
+                                          tokens.get(index + 1)
+                                                .map_or(false, |next| next.token == Token::Arrow);
+                    if is_arrow_params {
+                        for &comma in &group.commas {
+                            roles[comma] = CommaRole::Separator;
+                        }
+                    }
+                }
+            }
+            Token::Semicolon => groups.last_mut().unwrap().declaration = false,
+            Token::Comma => {
+                let group = groups.last_mut().unwrap();
+                group.commas.push(roles.len());
+                roles.push(if group.declaration {
+                    CommaRole::Separator
+                } else {
+                    group.role
+                });
+            }
+            _ if token.is_word("var") || token.is_word("let") || token.is_word("const") => {
+                groups.last_mut().unwrap().declaration = true;
+            }
+            _ => {}
+        }
+    }
+
+    roles
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleKind {
+    /// An ES module, with top-level `import` or `export` declarations.
+    Module,
+    /// A classic script.
+    Script,
+}
+
+/// Guesses whether the tokens are those of an ES module or a classic script
+/// from whether `import` or `export` appear outside of any brackets. Calls
+/// to `import(...)`, which scripts can make, and properties named `import`
+/// or `export` don't count; `import.meta` does.
+pub fn guess_module_kind(tokens: &[Token]) -> ModuleKind {
+    let tokens: Vec<_> = tokens.iter().filter(|token| !token.is_greyspace()).collect();
+    let mut depth = 0usize;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match **token {
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                depth = depth.saturating_sub(1);
+            }
+            _ if depth > 0 => {}
+            _ if token.is_word("import") || token.is_word("export") => {
+                let is_property = match index.checked_sub(1).map(|prev| tokens[prev]) {
+                    Some(&Token::Dot) | Some(&Token::OptionalChain) => true,
+                    _ => false,
+                };
+                let is_call = token.is_word("import") &&
+                              tokens.get(index + 1) == Some(&&Token::LeftParen);
+                if !is_property && !is_call {
+                    return ModuleKind::Module;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ModuleKind::Script
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReExportKind {
+    /// `export * from "m"`.
+    All,
+    /// `export * as ns from "m"`.
+    Namespace,
+    /// `export { a, b as c } from "m"`.
+    Named,
+}
+
+/// A declaration exporting bindings of another module, found by
+/// `classify_reexports`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReExport {
+    pub kind: ReExportKind,
+    /// From `export` to the module specifier.
+    pub span: Range<usize>,
+    /// The module specifier, a string literal.
+    pub source: Range<usize>,
+}
+
+/// Finds the declarations re-exporting from other modules: `export *`,
+/// `export * as ns` and `export { ... }`, each followed by `from` and a
+/// module specifier. Exports of local bindings, like `export { a }`, are
+/// skipped.
+pub fn classify_reexports(tokens: &[Spanned]) -> Vec<ReExport> {
+    let mut found = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        if !spanned.token.is_word("export") {
+            continue;
+        }
+        let is_property = match prev_meaningful(tokens, index).map(|prev| &tokens[prev].token) {
+            Some(&Token::Dot) | Some(&Token::OptionalChain) => true,
+            _ => false,
+        };
+        let first = match next_meaningful(tokens, index) {
+            Some(first) if !is_property => first,
+            _ => continue,
+        };
+
+        let (kind, from) = match tokens[first].token {
+            Token::Star => {
+                let next = next_meaningful(tokens, first);
+                match next {
+                    Some(next) if tokens[next].token.is_word("as") => {
+                        let name = next_meaningful(tokens, next);
+                        let from = name.and_then(|name| next_meaningful(tokens, name));
+                        (ReExportKind::Namespace, from)
+                    }
+                    _ => (ReExportKind::All, next),
+                }
+            }
+            Token::LeftBrace => {
+                let close = matching_bracket(tokens, first);
+                (ReExportKind::Named, close.and_then(|close| next_meaningful(tokens, close)))
+            }
+            _ => continue,
+        };
+        let source = match from {
+            Some(from) if tokens[from].token.is_word("from") => next_meaningful(tokens, from),
+            _ => continue,
+        };
+        if let Some(source) = source {
+            if let Token::StringLiteral(_) = tokens[source].token {
+                found.push(ReExport {
+                    kind: kind,
+                    span: spanned.span.start..tokens[source].span.end,
+                    source: tokens[source].span.clone(),
+                });
+            }
+        }
+    }
+
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineKind {
+    Function,
+    Class,
+    Method,
+}
+
+/// A declaration shown in an outline view, found by `outline`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub kind: OutlineKind,
+    /// The declared name, or `None` for anonymous function and class
+    /// expressions and computed method names.
+    pub name: Option<String>,
+    /// From the `function` or `class` keyword, or the method name, to the
+    /// closing brace of the body.
+    pub span: Range<usize>,
+}
+
+// The name declared by the token at `index`, a binding or method name.
+fn declared_name(tokens: &[Spanned], index: usize) -> Option<String> {
+    match tokens[index].token {
+        Token::StringLiteral(s) => string_value(s, false).ok().map(|value| value.into_owned()),
+        Token::Identifier(s) | Token::Keyword(s) | Token::PrivateIdentifier(s) |
+        Token::NumericLiteral(s) => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+// The name and body of the function whose `function` keyword is at `index`.
+fn function_parts(tokens: &[Spanned], index: usize) -> Option<(Option<String>, usize)> {
+    let mut name = None;
+    let mut next = next_meaningful(tokens, index);
+    if let Some(star) = next {
+        if tokens[star].token == Token::Star {
+            next = next_meaningful(tokens, star);
+        }
+    }
+    if let Some(word) = next {
+        if let Token::Identifier(_) = tokens[word].token {
+            name = declared_name(tokens, word);
+            next = next_meaningful(tokens, word);
+        }
+    }
+    let body = match next {
+        Some(open) if tokens[open].token == Token::LeftParen => {
+            matching_bracket(tokens, open).and_then(|close| next_meaningful(tokens, close))
+        }
+        _ => None,
+    };
+    match body {
+        Some(body) if tokens[body].token == Token::LeftBrace => Some((name, body)),
+        _ => None,
+    }
+}
+
+// The name and body of the class whose `class` keyword is at `index`.
+fn class_parts(tokens: &[Spanned], index: usize) -> Option<(Option<String>, usize)> {
+    let mut name = None;
+    let mut next = next_meaningful(tokens, index);
+    if let Some(word) = next {
+        match tokens[word].token {
+            Token::Identifier(s) if !is_keyword(s) => {
+                name = declared_name(tokens, word);
+                next = next_meaningful(tokens, word);
+            }
+            _ => {}
+        }
+    }
+    // The body is the first `{` outside the brackets of an `extends` clause.
+    while let Some(current) = next {
+        let skip_to = match tokens[current].token {
+            Token::LeftBrace => return Some((name, current)),
+            Token::LeftParen | Token::LeftBracket => matching_bracket(tokens, current),
+            _ => Some(current),
+        };
+        next = skip_to.and_then(|skip_to| next_meaningful(tokens, skip_to));
+    }
+    None
+}
+
+/// Finds the functions and classes at the top level, and the methods and
+/// class expressions directly in class bodies, for an outline view. Function
+/// and class expressions count, as in `const f = function() {}`, while
+/// arrow functions, object literal methods and anything inside a function
+/// body are left out. Items are in order of their start, so a class comes
+/// before its methods.
+pub fn outline(tokens: &[Spanned]) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    // For each open brace, whether it is a class body.
+    let mut braces: Vec<bool> = Vec::new();
+    // How many of the open braces aren't class bodies.
+    let mut other_braces = 0;
+    let mut class_bodies = Vec::new();
+
+    for (index, spanned) in tokens.iter().enumerate() {
+        match spanned.token {
+            Token::LeftBrace => {
+                let is_class = class_bodies.contains(&index);
+                if !is_class {
+                    other_braces += 1;
+                }
+                braces.push(is_class);
+            }
+            Token::RightBrace => {
+                if braces.pop() == Some(false) {
+                    other_braces -= 1;
+                }
+            }
+            _ => {}
+        }
+        if other_braces > 0 {
+            continue;
+        }
+
+        let prev = prev_meaningful(tokens, index);
+        let is_property = match prev.map(|prev| &tokens[prev].token) {
+            Some(&Token::Dot) | Some(&Token::OptionalChain) => true,
+            _ => false,
+        };
+        let (kind, name, start, body) = if spanned.token.is_word("function") && !is_property {
+            match function_parts(tokens, index) {
+                Some((name, body)) => (OutlineKind::Function, name, index, body),
+                None => continue,
+            }
+        } else if spanned.token.is_word("class") && !is_property {
+            match class_parts(tokens, index) {
+                Some((name, body)) => {
+                    class_bodies.push(body);
+                    (OutlineKind::Class, name, index, body)
+                }
+                None => continue,
+            }
+        } else if spanned.token == Token::LeftParen && braces.last() == Some(&true) {
+            // A method is a name followed by its parameters and body.
+            let close = matching_bracket(tokens, index);
+            let body = close.and_then(|close| next_meaningful(tokens, close));
+            let body = match body {
+                Some(body) if tokens[body].token == Token::LeftBrace => body,
+                _ => continue,
+            };
+            match prev {
+                Some(prev) if tokens[prev].token == Token::RightBracket => {
+                    match matching_bracket(tokens, prev) {
+                        Some(open) => (OutlineKind::Method, None, open, body),
+                        None => continue,
+                    }
+                }
+                Some(prev) if !tokens[prev].token.is_word("function") => {
+                    match declared_name(tokens, prev) {
+                        Some(name) => (OutlineKind::Method, Some(name), prev, body),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            }
+        } else {
+            continue;
+        };
+
+        let end = matching_bracket(tokens, body).unwrap_or(tokens.len() - 1);
+        items.push(OutlineItem {
+            kind: kind,
+            name: name,
+            span: tokens[start].span.start..tokens[end].span.end,
+        });
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::{tokenize, tokenize_spanned, tokenize_spanned_offset_with_options,
+                    TokenizeOptions};
+
+    #[test]
+    fn matching_brackets() {
+        let tokens = tokenize_spanned("f(a[b], {c})");
+        assert_eq!(tokens[3].token, Token::LeftParen);
+        assert_eq!(matching_bracket(&tokens, 3), Some(21));
+        assert_eq!(matching_bracket(&tokens, 21), Some(3));
+        assert_eq!(matching_bracket(&tokens, 1), None);
+        assert_eq!(matching_bracket(&tokenize_spanned("(("), 1), None);
+    }
+
+    #[test]
+    fn deeply_nested_parens() {
+        let depth = 10000;
+        let input = format!("{}x{}", "(".repeat(depth), ")".repeat(depth));
+        let tokens = tokenize_spanned(&input);
+        let opens: Vec<_> = (0..tokens.len()).filter(|&i| tokens[i].token == Token::LeftParen)
+                                             .collect();
+        let closes: Vec<_> = (0..tokens.len()).filter(|&i| tokens[i].token == Token::RightParen)
+                                              .collect();
+        assert_eq!((opens.len(), closes.len()), (depth, depth));
+        for &i in &[0, depth / 2, depth - 1] {
+            assert_eq!(matching_bracket(&tokens, opens[i]), Some(closes[depth - 1 - i]));
+            assert_eq!(matching_bracket(&tokens, closes[depth - 1 - i]), Some(opens[i]));
+        }
+        assert_eq!(asi_points(&tokens), vec![closes[depth - 1]]);
+        assert_eq!(comma_roles(&tokens), vec![]);
+    }
+
+    #[test]
+    fn chain_bounds() {
+        let tokens = tokenize_spanned("x = a.b?.[c](d).e + 1");
+        let start = tokens.iter().position(|t| t.token == Token::Identifier("a")).unwrap();
+        let end = tokens.iter().position(|t| t.token == Token::Identifier("e")).unwrap();
+        assert_eq!(chain_end(&tokens, start), end);
+        assert_eq!(chain_start(&tokens, end), start);
+    }
+
+    fn chains(input: &str) -> Vec<Vec<Range<usize>>> {
+        member_access_chains(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn member_access_chain() {
+        assert_eq!(chains("a.b.c"), vec![vec![0..1, 2..3, 4..5]]);
+        assert_eq!(chains("x = this.a;"), vec![vec![4..8, 9..10]]);
+    }
+
+    #[test]
+    fn member_access_chain_optional_and_computed() {
+        assert_eq!(chains("a?.b[c].d"), vec![vec![0..1, 3..4, 4..7, 8..9]]);
+        assert_eq!(chains("a?.[b.c]"), vec![vec![0..1, 3..8], vec![4..5, 6..7]]);
+    }
+
+    #[test]
+    fn member_access_chain_ends_at_call() {
+        assert_eq!(chains("a.b().c"), vec![vec![0..1, 2..3]]);
+        assert_eq!(chains("f(a); return x"), Vec::<Vec<Range<usize>>>::new());
+    }
+
+    fn arrow_body(input: &str) -> ArrowBody {
+        let tokens = tokenize_spanned(input);
+        let arrow = tokens.iter().position(|spanned| spanned.token == Token::Arrow).unwrap();
+        arrow_body_kind(&tokens, arrow)
+    }
+
+    #[test]
+    fn arrow_body_block() {
+        assert_eq!(arrow_body("() => {}"), ArrowBody::Block);
+        assert_eq!(arrow_body("a=>/* c */{ return a }"), ArrowBody::Block);
+    }
+
+    #[test]
+    fn arrow_body_expression() {
+        assert_eq!(arrow_body("() => x"), ArrowBody::Expression);
+        assert_eq!(arrow_body("() => ({a:1})"), ArrowBody::Expression);
+        assert_eq!(arrow_body("() =>"), ArrowBody::Expression);
+    }
+
+    fn patterns(input: &str) -> Vec<Range<usize>> {
+        find_destructuring_patterns(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn destructuring_declarations() {
+        assert_eq!(patterns("const {a} = x"), vec![6..9]);
+        assert_eq!(patterns("const [a] = x"), vec![6..9]);
+        assert_eq!(patterns("let {a: [b, {c}], d} = x"), vec![4..20]);
+        assert_eq!(patterns("for (const [k, v] of m) {}"), vec![11..17]);
+    }
+
+    #[test]
+    fn destructuring_assignments() {
+        assert_eq!(patterns("[a, b] = [b, a]"), vec![0..6]);
+        assert_eq!(patterns("({a} = x)"), vec![1..4]);
+        assert_eq!(patterns("{a} = x"), vec![0..3]);
+        assert_eq!(patterns("x[i] = 1; y = [a]; if (a) {b} x == {}"), vec![]);
+    }
+
+    #[test]
+    fn destructuring_parameters() {
+        assert_eq!(patterns("([a, b]) => a"), vec![1..7]);
+        assert_eq!(patterns("function f(x, {y} = {}) {}"), vec![14..17]);
+        assert_eq!(patterns("class A { m({a}) {} }"), vec![12..15]);
+        assert_eq!(patterns("try {} catch ({message}) {}"), vec![14..23]);
+        assert_eq!(patterns("f([a], {b}); if ({a}) {}"), vec![]);
+    }
+
+    // The source text of each token a semicolon is inserted after.
+    fn asi(input: &str) -> Vec<&str> {
+        let tokens = tokenize_spanned(input);
+        asi_points(&tokens).into_iter().map(|index| &input[tokens[index].span.clone()]).collect()
+    }
+
+    fn extent<'a>(input: &'a str, start: &str) -> &'a str {
+        let tokens = tokenize_spanned(input);
+        let start = tokens.iter().position(|spanned| spanned.token.as_str() == start).unwrap();
+        &input[expression_extent(&tokens, &asi_points(&tokens), start)]
+    }
+
+    #[test]
+    fn expression_extent_binary() {
+        assert_eq!(extent("a + b * c; d", "a"), "a + b * c");
+        assert_eq!(extent("x = a + b\nc()", "a"), "a + b");
+        assert_eq!(extent("x = c ? a : b, y", "c"), "c ? a : b");
+    }
+
+    #[test]
+    fn expression_extent_call() {
+        assert_eq!(extent("f(x, y)", "f"), "f(x, y)");
+        assert_eq!(extent("f(x, y)", "x"), "x");
+        assert_eq!(extent("{a: g(b)[0].c, d}", "g"), "g(b)[0].c");
+        assert_eq!(extent("if (a) { b }", "b"), "b");
+    }
+
+    #[test]
+    fn expression_extent_out_of_range() {
+        assert_eq!(expression_extent(&[], &[], 0), 0..0);
+        let tokens = tokenize_spanned("a + b");
+        let asi = asi_points(&tokens);
+        assert_eq!(expression_extent(&tokens, &asi, tokens.len()), tokens.len()..tokens.len());
+        assert_eq!(expression_extent(&tokens, &asi, 100), 100..100);
+    }
+
+    #[test]
+    fn asi_between_lines() {
+        assert_eq!(asi("a = 1\nb = 2"), vec!["1", "2"]);
+        assert_eq!(asi("a = 1; b = 2;"), Vec::<&str>::new());
+        assert_eq!(asi("if (a)\n  b\nelse c"), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn asi_continued_lines() {
+        assert_eq!(asi("a = b\n(c)"), vec![")"]);
+        assert_eq!(asi("a = b\n  + c;\nx.\ny;"), Vec::<&str>::new());
+        assert_eq!(asi("for (a\nb;;) {}"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn asi_restricted_productions() {
+        assert_eq!(asi("return\nx"), vec!["return", "x"]);
+        assert_eq!(asi("a\n++b"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn asi_before_closing_brace() {
+        assert_eq!(asi("function f() { return 1 }"), vec!["1"]);
+        assert_eq!(asi("x = {a: 1}\ny = {}"), vec!["}", "}"]);
+        assert_eq!(asi("if (a) { b() }\nc()"), vec![")", ")"]);
+    }
+
+    fn statement_ends(input: &str) -> Vec<Token> {
+        mark_statement_ends(tokenize_spanned(input))
+            .into_iter()
+            .map(|spanned| spanned.token)
+            .filter(|token| match *token {
+                Token::Whitespace(_) => false,
+                _ => true,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn statement_end_at_asi_point() {
+        assert_eq!(statement_ends("a = 1\nb = 2"),
+                   vec![Token::Identifier("a"),
+                        Token::Equal,
+                        Token::NumericLiteral("1"),
+                        Token::StatementEnd,
+                        Token::Identifier("b"),
+                        Token::Equal,
+                        Token::NumericLiteral("2"),
+                        Token::StatementEnd]);
+    }
+
+    #[test]
+    fn statement_end_at_semicolon() {
+        assert_eq!(statement_ends("a;b"),
+                   vec![Token::Identifier("a"),
+                        Token::Semicolon,
+                        Token::StatementEnd,
+                        Token::Identifier("b"),
+                        Token::StatementEnd]);
+        assert_eq!(statement_ends("for (;;) x();"),
+                   vec![Token::Keyword("for"),
+                        Token::LeftParen,
+                        Token::Semicolon,
+                        Token::Semicolon,
+                        Token::RightParen,
+                        Token::Identifier("x"),
+                        Token::LeftParen,
+                        Token::RightParen,
+                        Token::Semicolon,
+                        Token::StatementEnd]);
+        assert!(!tokenize("a;b").contains(&Token::StatementEnd));
+        assert_eq!(mark_statement_ends(tokenize_spanned("a;b"))[4].span, 2..2);
+    }
+
+    fn roles(input: &str) -> Vec<CommaRole> {
+        comma_roles(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn comma_in_parens() {
+        assert_eq!(roles("a = (b, c)"), vec![CommaRole::Sequence]);
+    }
+
+    #[test]
+    fn comma_in_call() {
+        assert_eq!(roles("f(a, b)"), vec![CommaRole::Separator]);
+        assert_eq!(roles("f(a)(b, c)"), vec![CommaRole::Separator]);
+    }
+
+    #[test]
+    fn comma_in_array() {
+        assert_eq!(roles("[a, b]"), vec![CommaRole::Separator]);
+        assert_eq!(roles("a[b, c]"), vec![CommaRole::Sequence]);
+    }
+
+    #[test]
+    fn comma_in_for_init() {
+        assert_eq!(roles("for(a, b;;)"), vec![CommaRole::Sequence]);
+        assert_eq!(roles("for (var i = 0, j = 1;;) {}"), vec![CommaRole::Separator]);
+    }
+
+    #[test]
+    fn comma_in_declarations() {
+        assert_eq!(roles("var a = 1, b; a, b"),
+                   vec![CommaRole::Separator, CommaRole::Sequence]);
+    }
+
+    #[test]
+    fn comma_in_parameters() {
+        assert_eq!(roles("function f(a, b) { a, b }"),
+                   vec![CommaRole::Separator, CommaRole::Sequence]);
+        assert_eq!(roles("(a, b) => 1"), vec![CommaRole::Separator]);
+    }
+
+    fn computed_members(input: &str) -> Vec<&str> {
+        find_computed_members(&tokenize_spanned(input))
+            .into_iter()
+            .map(|span| &input[span])
+            .collect()
+    }
+
+    #[test]
+    fn computed_member_access() {
+        assert_eq!(computed_members("a[b]"), vec!["a[b]"]);
+        assert_eq!(computed_members("f()[c]"), vec!["f()[c]"]);
+        assert_eq!(computed_members("x = o.p?.[Symbol.iterator]"),
+                   vec!["o.p?.[Symbol.iterator]"]);
+        assert_eq!(computed_members("a[b][c]"), vec!["a[b]", "a[b][c]"]);
+    }
+
+    #[test]
+    fn array_literals_not_computed() {
+        assert!(computed_members("[a, b]").is_empty());
+        assert!(computed_members("x = [a]; return [b]; f([c])").is_empty());
+    }
+
+    fn new_expressions(input: &str) -> Vec<&str> {
+        find_new_expressions(&tokenize_spanned(input))
+            .into_iter()
+            .map(|span| &input[span])
+            .collect()
+    }
+
+    #[test]
+    fn new_with_arguments() {
+        assert_eq!(new_expressions("new Foo()"), vec!["new Foo()"]);
+        assert_eq!(new_expressions("x = new a.b.C(x).d;"), vec!["new a.b.C(x)"]);
+        assert_eq!(new_expressions("new (f())(1, 2)"), vec!["new (f())(1, 2)"]);
+        assert_eq!(new_expressions("new m[k](g(new B))"), vec!["new m[k](g(new B))", "new B"]);
+    }
+
+    #[test]
+    fn new_without_arguments() {
+        assert_eq!(new_expressions("new Foo;"), vec!["new Foo"]);
+        assert_eq!(new_expressions("x = new a.B + 1"), vec!["new a.B"]);
+        assert_eq!(new_expressions("new new A()()"), vec!["new new A()()", "new A()"]);
+    }
+
+    #[test]
+    fn new_lookalikes() {
+        assert!(new_expressions("if (new.target) o.new(x); s = 'new A'").is_empty());
+    }
+
+    fn spread_roles(input: &str) -> Vec<SpreadRole> {
+        classify_spread(&tokenize_spanned(input))
+    }
+
+    #[test]
+    fn spread_arguments_and_literals() {
+        assert_eq!(spread_roles("f(...a)"), vec![SpreadRole::Spread]);
+        assert_eq!(spread_roles("x = [...a, {...o}]"),
+                   vec![SpreadRole::Spread, SpreadRole::Spread]);
+    }
+
+    #[test]
+    fn rest_parameters_and_patterns() {
+        assert_eq!(spread_roles("function f(...a){}"), vec![SpreadRole::Rest]);
+        assert_eq!(spread_roles("const [x, ...y] = z"), vec![SpreadRole::Rest]);
+        assert_eq!(spread_roles("({a, ...b} = c); (x, ...y) => y"),
+                   vec![SpreadRole::Rest, SpreadRole::Rest]);
+        assert_eq!(spread_roles("let {a: [b, ...c], d = [...e]} = f"),
+                   vec![SpreadRole::Rest, SpreadRole::Spread]);
+    }
+
+    fn annotations(input: &str) -> Vec<&str> {
+        let tokens = tokenize_spanned(input);
+        strip_type_annotations(&tokens).into_iter().map(|span| &input[span]).collect()
+    }
+
+    #[test]
+    fn type_annotations_of_function() {
+        assert_eq!(annotations("function f(a: number): void {}"), vec![": number", ": void"]);
+        assert_eq!(annotations("function f(a?: Map<K, V[]>, b = 1, {c: d}: T) {}"),
+                   vec!["?: Map<K, V[]>", ": T"]);
+        assert_eq!(annotations("function f(): {a: A} | null { return null }"),
+                   vec![": {a: A} | null"]);
+    }
+
+    #[test]
+    fn type_annotations_of_methods_and_arrows() {
+        assert_eq!(annotations("class A { m(a: A<B<C>>): () => void {} }"),
+                   vec![": A<B<C>>", ": () => void"]);
+        assert_eq!(annotations("f((a: string, cb: (e: E) => void): T => a)"),
+                   vec![": string", ": (e: E) => void", ": T"]);
+    }
+
+    #[test]
+    fn type_annotations_not_in_javascript() {
+        assert!(annotations("c ? f(a) : b; x = {a: (1)}; switch (a) { case (1): {} }").is_empty());
+    }
+
+    fn split_closes(input: &str) -> Vec<Token> {
+        split_type_argument_closes(tokenize_spanned(input))
+            .into_iter()
+            .map(|spanned| {
+                assert_eq!(&input[spanned.span], spanned.token.as_str());
+                spanned.token
+            })
+            .filter(|token| !token.is_greyspace())
+            .collect()
+    }
+
+    #[test]
+    fn split_nested_type_argument_closes() {
+        let greater = || Token::Relational(Relational::Greater);
+        assert_eq!(&split_closes("let a: Array<Array<T>> = []")[8..10], &[greater(), greater()]);
+        assert_eq!(&split_closes("m: Map<K, Set<Array<V>>>;")[11..],
+                   &[greater(), greater(), greater(), Token::Semicolon]);
+        assert_eq!(&split_closes("m: Map<K, Set<Array<V>> >;")[11..],
+                   &[greater(), greater(), greater(), Token::Semicolon]);
+        assert_eq!(&split_closes("a: A<B<C<D>>>")[9..], &[greater(), greater(), greater()]);
+    }
+
+    #[test]
+    fn keep_shift_operators() {
+        let shift = Token::BitShift(Operator::RightShift);
+        assert_eq!(split_closes("x = a >> 2")[3], shift);
+        assert_eq!(split_closes("x = a < b && c >> 1")[7], shift);
+        assert_eq!(split_closes("if (a<b) c = d >> e")[9], shift);
+        assert_eq!(split_closes("x = a<b<c>>>d")[7],
+                   Token::BitShift(Operator::UnsignedRightShift));
+    }
+
+    #[test]
+    fn keep_shift_operators_with_type_annotations() {
+        let input = "let a: Array<Array<T>> = []; x = a >> 2;";
+        let options = TokenizeOptions::typescript();
+        let tokens = tokenize_spanned_offset_with_options(input, 0, None, &options).unwrap();
+        let tokens: Vec<Token> = split_type_argument_closes(tokens)
+                                     .into_iter()
+                                     .map(|spanned| spanned.token)
+                                     .filter(|token| !token.is_greyspace())
+                                     .collect();
+        assert_eq!(&tokens[8..10],
+                   &[Token::Relational(Relational::Greater),
+                     Token::Relational(Relational::Greater)]);
+        assert_eq!(tokens[tokens.len() - 3], Token::BitShift(Operator::RightShift));
+    }
+
+    #[test]
+    fn comma_in_object() {
+        assert_eq!(roles("x = {a: 1, b: (2, 3)}"),
+                   vec![CommaRole::Separator, CommaRole::Sequence]);
+    }
+
+    #[test]
+    fn module_with_import() {
+        assert_eq!(guess_module_kind(&tokenize("import {a} from 'a';\na();")),
+                   ModuleKind::Module);
+        assert_eq!(guess_module_kind(&tokenize("const url = import.meta.url;")),
+                   ModuleKind::Module);
+    }
+
+    #[test]
+    fn module_with_export() {
+        assert_eq!(guess_module_kind(&tokenize("export default function () {}")),
+                   ModuleKind::Module);
+        assert_eq!(guess_module_kind(&tokenize("function f() {}\nexport { f };")),
+                   ModuleKind::Module);
+    }
+
+    #[test]
+    fn plain_script() {
+        assert_eq!(guess_module_kind(&tokenize("var a = 1;\nfunction f() { return a; }")),
+                   ModuleKind::Script);
+        assert_eq!(guess_module_kind(&tokenize("import('a').then(f); o.export = 1;")),
+                   ModuleKind::Script);
+        assert_eq!(guess_module_kind(&tokenize("x = {export: 1}; // import a")),
+                   ModuleKind::Script);
+        assert_eq!(guess_module_kind(&tokenize("")), ModuleKind::Script);
+    }
+
+    fn outline_of(input: &str) -> Vec<(OutlineKind, Option<String>, &str)> {
+        outline(&tokenize_spanned(input))
+            .into_iter()
+            .map(|item| (item.kind, item.name, &input[item.span]))
+            .collect()
+    }
+
+    fn name(s: &str) -> Option<String> {
+        Some(String::from(s))
+    }
+
+    #[test]
+    fn outline_functions() {
+        let input = concat!("function f(a) { function inner() {} return a; }\n",
+                            "const g = function() { return 1; };\n",
+                            "export default async function* h() {}\n",
+                            "let o = { m: function() {}, n() {} }; a.function(x) {}");
+        assert_eq!(outline_of(input),
+                   vec![(OutlineKind::Function,
+                         name("f"),
+                         "function f(a) { function inner() {} return a; }"),
+                        (OutlineKind::Function, None, "function() { return 1; }"),
+                        (OutlineKind::Function, name("h"), "function* h() {}")]);
+    }
+
+    #[test]
+    fn outline_classes() {
+        let input = concat!("class A extends mixin({ b: 1 }) {\n",
+                            "  constructor(x) { this.x = f(x); }\n",
+                            "  static async *gen() {}\n",
+                            "  get #size() { return 0; }\n",
+                            "  ['computed']() {}\n",
+                            "  field = () => {};\n",
+                            "  handler = function() {};\n",
+                            "}\n",
+                            "const B = class {};");
+        let items = outline_of(input);
+        assert_eq!(items.iter().map(|item| (item.0, item.1.clone())).collect::<Vec<_>>(),
+                   vec![(OutlineKind::Class, name("A")),
+                        (OutlineKind::Method, name("constructor")),
+                        (OutlineKind::Method, name("gen")),
+                        (OutlineKind::Method, name("#size")),
+                        (OutlineKind::Method, None),
+                        (OutlineKind::Function, None),
+                        (OutlineKind::Class, None)]);
+        assert!(items[0].2.starts_with("class A") && items[0].2.ends_with("};\n}"));
+        assert_eq!(items[1].2, "constructor(x) { this.x = f(x); }");
+        assert_eq!(items[4].2, "['computed']() {}");
+        assert_eq!(items[6].2, "class {}");
+    }
+
+    fn reexports(input: &str) -> Vec<(ReExportKind, &str)> {
+        classify_reexports(&tokenize_spanned(input))
+            .into_iter()
+            .map(|reexport| {
+                assert!(input[reexport.span.clone()].starts_with("export"));
+                assert!(input[reexport.span.clone()].ends_with(&input[reexport.source.clone()]));
+                (reexport.kind, &input[reexport.source])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reexport_forms() {
+        assert_eq!(reexports("export * from \"m\";"), vec![(ReExportKind::All, "\"m\"")]);
+        assert_eq!(reexports("export * as ns from 'm'"), vec![(ReExportKind::Namespace, "'m'")]);
+        assert_eq!(reexports("export { a, b as c } from './m.js'"),
+                   vec![(ReExportKind::Named, "'./m.js'")]);
+        assert_eq!(reexports("export {} from 'm'\nexport*from'n'"),
+                   vec![(ReExportKind::Named, "'m'"), (ReExportKind::All, "'n'")]);
+    }
+
+    #[test]
+    fn local_exports() {
+        assert_eq!(reexports("export { a }; export { b as from }"), vec![]);
+        assert_eq!(reexports("export const from = 'm'; export default from;"), vec![]);
+        assert_eq!(reexports("import * as ns from 'm'; o.export * from('m')"), vec![]);
+    }
+
+    fn assertions(input: &str) -> Vec<&str> {
+        let tokens = tokenize_spanned(input);
+        strip_as_assertions(&tokens).into_iter().map(|span| &input[span]).collect()
+    }
+
+    #[test]
+    fn as_and_satisfies_assertions() {
+        assert_eq!(assertions("let y = x as number;"), vec!["as number"]);
+        assert_eq!(assertions("const c = {a: 1} satisfies T\nf(c)"), vec!["satisfies T"]);
+        assert_eq!(assertions("f(a as Array<string>, b as unknown as B)"),
+                   vec!["as Array<string>", "as unknown as B"]);
+        assert_eq!(assertions("(x as any).y = 1"), vec!["as any"]);
+    }
+
+    #[test]
+    fn as_identifiers() {
+        assert_eq!(assertions("var as = 1; as(x); a = as"), Vec::<&str>::new());
+        assert_eq!(assertions("import {a as b} from 'm'; import * as ns from 'n'"),
+                   Vec::<&str>::new());
+        assert_eq!(assertions("export {a as default, b as c}"), Vec::<&str>::new());
+    }
+}
@@ -0,0 +1,281 @@
+//! Helpers for writing tokenizer tests and reducing failing inputs.
+
+// Not every helper is used by every test run.
+#![allow(dead_code)]
+
+use tokenizer::{tokenize_spanned, Operator, Relational, Token};
+
+/// Builds an expected token stream, e.g.
+/// `TokenStreamBuilder::new().ws("").ident("a").eq().num("1").build()`.
+#[derive(Debug, Default)]
+pub struct TokenStreamBuilder<'a> {
+    tokens: Vec<Token<'a>>,
+}
+
+impl<'a> TokenStreamBuilder<'a> {
+    pub fn new() -> TokenStreamBuilder<'a> {
+        TokenStreamBuilder { tokens: Vec::new() }
+    }
+
+    /// Appends any token, for variants without a method of their own.
+    pub fn token(mut self, token: Token<'a>) -> TokenStreamBuilder<'a> {
+        self.tokens.push(token);
+        self
+    }
+
+    pub fn ws(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::Whitespace(s))
+    }
+
+    pub fn shebang(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::Shebang(s))
+    }
+
+    pub fn line_comment(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::LineComment(s))
+    }
+
+    pub fn block_comment(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::BlockComment(s))
+    }
+
+    pub fn num(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::NumericLiteral(s))
+    }
+
+    pub fn string(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::StringLiteral(s))
+    }
+
+    pub fn regex(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::RegexLiteral(s))
+    }
+
+    pub fn template(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::TemplateLiteral(s))
+    }
+
+    pub fn ident(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::Identifier(s))
+    }
+
+    pub fn keyword(self, s: &'a str) -> TokenStreamBuilder<'a> {
+        self.token(Token::Keyword(s))
+    }
+
+    pub fn eq(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::Equal)
+    }
+
+    pub fn semicolon(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::Semicolon)
+    }
+
+    pub fn comma(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::Comma)
+    }
+
+    pub fn dot(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::Dot)
+    }
+
+    pub fn colon(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::Colon)
+    }
+
+    pub fn lparen(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::LeftParen)
+    }
+
+    pub fn rparen(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::RightParen)
+    }
+
+    pub fn lbrace(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::LeftBrace)
+    }
+
+    pub fn rbrace(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::RightBrace)
+    }
+
+    pub fn lbracket(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::LeftBracket)
+    }
+
+    pub fn rbracket(self) -> TokenStreamBuilder<'a> {
+        self.token(Token::RightBracket)
+    }
+
+    pub fn build(self) -> Vec<Token<'a>> {
+        self.tokens
+    }
+}
+
+/// A small xorshift generator, so randomized tests are reproducible.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn random_token(rng: &mut Rng) -> Token<'static> {
+    match rng.below(40) {
+        0 => Token::Identifier("a"),
+        1 => Token::Identifier("$b_1"),
+        2 => Token::Identifier("caf\u{e9}"),
+        3 => Token::Keyword("if"),
+        4 => Token::Keyword("in"),
+        5 => Token::Keyword("this"),
+        6 => Token::PrivateIdentifier("#x"),
+        7 => Token::NumericLiteral("1"),
+        8 => Token::NumericLiteral("0x1F"),
+        9 => Token::NumericLiteral(".5e-3"),
+        10 => Token::StringLiteral("'a\\'b'"),
+        11 => Token::StringLiteral("\"\""),
+        12 => Token::TemplateLiteral("`a${b}c`"),
+        13 => Token::RegexLiteral("/[/]a/gi"),
+        14 => Token::Plus,
+        15 => Token::Minus,
+        16 => Token::DeIncrement(Operator::Add),
+        17 => Token::Star,
+        18 => Token::Exponeniation,
+        19 => Token::Slash,
+        20 => Token::UpdateAssignment(Operator::Divide),
+        21 => Token::UpdateAssignment(Operator::UnsignedRightShift),
+        22 => Token::BitShift(Operator::RightShift),
+        23 => Token::Relational(Relational::Less),
+        24 => Token::Relational(Relational::EqualStrict),
+        25 => Token::Equal,
+        26 => Token::Arrow,
+        27 => Token::LogicalAnd,
+        28 => Token::NullishCoalescing,
+        29 => Token::OptionalChain,
+        30 => Token::QuestionMark,
+        31 => Token::ExclamationMark,
+        32 => Token::Dot,
+        33 => Token::Ellipsis,
+        34 => Token::Comma,
+        35 => Token::Colon,
+        36 => Token::Semicolon,
+        37 => Token::LeftParen,
+        38 => Token::RightBracket,
+        _ => Token::LeftBrace,
+    }
+}
+
+/// Builds `len` random tokens other than whitespace and comments. Regexes
+/// only come where an expression may start and `/` only where one can't,
+/// so rendering the tokens with spaces between them tokenizes back to the
+/// same tokens.
+pub fn random_tokens(rng: &mut Rng, len: usize) -> Vec<Token<'static>> {
+    let mut builder = TokenStreamBuilder::new();
+    let mut expression_allowed = true;
+
+    for _ in 0..len {
+        let mut token = random_token(rng);
+        loop {
+            let fits = match token {
+                Token::RegexLiteral(_) => expression_allowed,
+                Token::Slash | Token::UpdateAssignment(Operator::Divide) => !expression_allowed,
+                _ => true,
+            };
+            if fits {
+                break;
+            }
+            token = random_token(rng);
+        }
+        expression_allowed = token.before_expression();
+        builder = builder.token(token);
+    }
+
+    builder.build()
+}
+
+// Removes ever smaller runs of `pieces` for as long as what's left still
+// satisfies `predicate`.
+fn reduce<'a, F>(mut pieces: Vec<&'a str>, predicate: &F) -> Vec<&'a str>
+    where F: Fn(&str) -> bool
+{
+    let mut chunk = (pieces.len() / 2).max(1);
+    while chunk > 0 && !pieces.is_empty() {
+        let mut removed = false;
+        let mut start = 0;
+        while start < pieces.len() {
+            let end = (start + chunk).min(pieces.len());
+            let candidate = pieces[..start].concat() + &pieces[end..].concat();
+            if predicate(&candidate) {
+                pieces.drain(start..end);
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if !removed {
+            chunk /= 2;
+        }
+    }
+    pieces
+}
+
+/// Shrinks `input` to a small input that still satisfies `predicate`, such
+/// as still making the tokenizer panic, by delta debugging: whole lines are
+/// removed first, then single tokens. `predicate` should hold for `input`.
+pub fn minimize<F>(input: &str, predicate: F) -> String
+    where F: Fn(&str) -> bool
+{
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (index, _) in input.match_indices('\n') {
+        lines.push(&input[start..index + 1]);
+        start = index + 1;
+    }
+    lines.push(&input[start..]);
+    let reduced = reduce(lines, &predicate).concat();
+
+    let tokens = tokenize_spanned(&reduced);
+    let pieces = tokens.iter()
+                       .map(|spanned| spanned.token.as_str())
+                       .filter(|text| !text.is_empty())
+                       .collect();
+    reduce(pieces, &predicate).concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize;
+
+    fn has_unknown(input: &str) -> bool {
+        tokenize(input).iter().any(|token| match *token {
+            Token::Unknown(_) => true,
+            _ => false,
+        })
+    }
+
+    #[test]
+    fn minimize_to_offending_token() {
+        let input = "var a = 1;\nfunction f(b) {\n    return b @ a;\n}\nf(2);\n";
+        assert_eq!(minimize(input, has_unknown), "@");
+    }
+
+    #[test]
+    fn minimize_keeps_needed_tokens() {
+        let input = "let x = [1, 2];\nif (x) { g(x, 'y'); }";
+        assert_eq!(minimize(input, |s| s.contains("if") && s.contains("'y'")), "if'y'");
+    }
+}
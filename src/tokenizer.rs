@@ -1,6 +1,13 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::{DerefMut, Range};
 use std::str;
 use std::mem;
 use memchr;
+use structure::mark_statement_ends;
+use format::needs_separating_space;
+#[cfg(feature = "smallvec")]
+use smallvec::{Array, SmallVec};
 
 #[derive(Debug, PartialEq, Eq)]
 enum TokenizerType {
@@ -11,6 +18,18 @@ enum TokenizerType {
     Blackspace,
     LineComment,
     BlockComment,
+    JsxText,
+}
+
+// What is being tokenized within JSX, innermost last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsxContext {
+    // The children of a fragment or element, text up to a `{` or `<`.
+    Children,
+    // An opening tag, or a closing one, up to its `>`.
+    Tag { closing: bool },
+    // A `{}` expression, with the number of braces open within it.
+    Expression(usize),
 }
 
 impl TokenizerType {
@@ -36,6 +55,33 @@ pub enum Operator {
     Divide,
     Multiply,
     BitwiseOr,
+    BitwiseAnd,
+    BitwiseXOR,
+    LogicalOr,
+    LogicalAnd,
+    NullishCoalescing,
+}
+
+impl Operator {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Operator::UnsignedRightShift => ">>>",
+            Operator::RightShift => ">>",
+            Operator::LeftShift => "<<",
+            Operator::Exponeniation => "**",
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Modulo => "%",
+            Operator::Divide => "/",
+            Operator::Multiply => "*",
+            Operator::BitwiseOr => "|",
+            Operator::BitwiseAnd => "&",
+            Operator::BitwiseXOR => "^",
+            Operator::LogicalOr => "||",
+            Operator::LogicalAnd => "&&",
+            Operator::NullishCoalescing => "??",
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -50,6 +96,86 @@ pub enum Relational {
     NotEqualStrict,
 }
 
+impl Relational {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Relational::Less => "<",
+            Relational::Greater => ">",
+            Relational::LessOrEqual => "<=",
+            Relational::GreaterOrEqual => ">=",
+            Relational::Equal => "==",
+            Relational::NotEqual => "!=",
+            Relational::EqualStrict => "===",
+            Relational::NotEqualStrict => "!==",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericKind {
+    Decimal,
+    Hex,
+    Octal,
+    Binary,
+}
+
+/// A dense code for each `Token` variant, for table-driven processing. The
+/// values are stable: they won't change, and new variants get new values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum TokenOp {
+    Whitespace = 0,
+    Shebang = 1,
+    LineComment = 2,
+    BlockComment = 3,
+    NumericLiteral = 4,
+    StringLiteral = 5,
+    RegexLiteral = 6,
+    TemplateLiteral = 7,
+    Identifier = 8,
+    Keyword = 9,
+    PrivateIdentifier = 10,
+    Unknown = 11,
+    DeIncrement = 12,
+    BitShift = 13,
+    Relational = 14,
+    UpdateAssignment = 15,
+    Exponeniation = 16,
+    Arrow = 17,
+    Equal = 18,
+    LogicalOr = 19,
+    LogicalAnd = 20,
+    NullishCoalescing = 21,
+    OptionalChain = 22,
+    BitwiseOr = 23,
+    BitwiseXOR = 24,
+    BitwiseAnd = 25,
+    BitwiseNot = 26,
+    Plus = 27,
+    Minus = 28,
+    Modulo = 29,
+    Star = 30,
+    Slash = 31,
+    Semicolon = 32,
+    LeftParen = 33,
+    RightParen = 34,
+    LeftBrace = 35,
+    RightBrace = 36,
+    LeftBracket = 37,
+    RightBracket = 38,
+    Dot = 39,
+    Ellipsis = 40,
+    Comma = 41,
+    QuestionMark = 42,
+    Colon = 43,
+    ExclamationMark = 44,
+    StatementEnd = 45,
+    JsxFragmentStart = 46,
+    JsxFragmentEnd = 47,
+    Annotation = 48,
+    JsxText = 49,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Token<'a> {
     Whitespace(&'a str),
@@ -62,6 +188,10 @@ pub enum Token<'a> {
     TemplateLiteral(&'a str),
     Identifier(&'a str),
     Keyword(&'a str),
+    /// The name of a private class member, such as `#x`.
+    PrivateIdentifier(&'a str),
+    /// A character that doesn't start any token, only produced in lenient mode.
+    Unknown(&'a str),
 
     DeIncrement(Operator),
     BitShift(Operator),
@@ -72,6 +202,8 @@ pub enum Token<'a> {
     Equal,
     LogicalOr,
     LogicalAnd,
+    NullishCoalescing,
+    OptionalChain,
     BitwiseOr,
     BitwiseXOR,
     BitwiseAnd,
@@ -89,15 +221,44 @@ pub enum Token<'a> {
     LeftBracket,
     RightBracket,
     Dot,
+    /// `...`, spreading or collecting the rest of a list.
+    Ellipsis,
     Comma,
     QuestionMark,
     Colon,
     ExclamationMark,
+    /// A zero-width marker of where a statement ends, after its `;` or where
+    /// automatic semicolon insertion adds one. Only produced with
+    /// `TokenizeOptions::emit_statement_boundaries`.
+    StatementEnd,
+    /// `<>`, opening a JSX fragment. Only produced with `TokenizeOptions::jsx`.
+    JsxFragmentStart,
+    /// `</>`, closing a JSX fragment. Only produced with `TokenizeOptions::jsx`.
+    JsxFragmentEnd,
+    /// Text among the children of a JSX fragment or element, up to the next
+    /// `{` or `<`. Only produced with `TokenizeOptions::jsx`.
+    JsxText(&'a str),
+    /// A block comment holding only an annotation registered in
+    /// `TokenizeOptions::annotation_comments`, such as
+    /// `/* @__NO_SIDE_EFFECTS__ */`.
+    Annotation(&'a str),
 }
 
 impl<'a> Token<'a> {
-    fn before_expression(&self) -> bool {
+    /// Whether an expression can start after this token, making a following
+    /// `/` a regex. Values, template literals among them, are followed by
+    /// division instead. Keywords such as `return`, `typeof` or `case` take
+    /// an operand, and `await` and `yield` do too, whether or not they were
+    /// tokenized as identifiers. An arrow is followed by the function's body,
+    /// which may be an expression.
+    pub fn before_expression(&self) -> bool {
         match *self {
+            Token::Keyword(s) => {
+                s == "in" || s == "instanceof" || s == "typeof" || s == "void" ||
+                s == "delete" || s == "return" || s == "throw" || s == "case" || s == "do" ||
+                s == "else" || s == "new" || s == "yield" || s == "await"
+            }
+            Token::Identifier(s) => s == "await" || s == "yield",
             Token::LeftBracket |
             Token::LeftBrace |
             Token::LeftParen |
@@ -107,41 +268,684 @@ impl<'a> Token<'a> {
             Token::QuestionMark |
             Token::Equal |
             Token::ExclamationMark |
+            Token::Ellipsis |
+            Token::Arrow |
             Token::UpdateAssignment(_) => true,
             _ => false,
         }
     }
 
-    fn is_greyspace(&self) -> bool {
+    /// Whether this is `=` or a compound assignment such as `+=` or `??=`.
+    pub fn is_assignment_operator(&self) -> bool {
+        match *self {
+            Token::Equal |
+            Token::UpdateAssignment(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this is a block comment starting with `/*!`, which by
+    /// convention holds a license or other legal notice to keep when
+    /// minifying.
+    pub fn is_license_comment(&self) -> bool {
+        match *self {
+            Token::BlockComment(s) => s.starts_with("/*!"),
+            _ => false,
+        }
+    }
+
+    /// Whether this is whitespace, a comment or another token without meaning
+    /// to the program, such as a `StatementEnd` marker.
+    pub fn is_greyspace(&self) -> bool {
         match *self {
             Token::Whitespace(_) |
             Token::BlockComment(_) |
             Token::LineComment(_) |
-            Token::Shebang(_) => true,
+            Token::Shebang(_) |
+            Token::Annotation(_) |
+            Token::StatementEnd => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this is an identifier or keyword spelled `word`. Keywords are
+    /// only recognized when they stand alone between whitespace, so analyses
+    /// looking for a particular word should accept either variant.
+    pub fn is_word(&self, word: &str) -> bool {
+        match *self {
+            Token::Identifier(s) | Token::Keyword(s) => s == word,
             _ => false,
         }
     }
+
+    /// Whether both tokens are of the same kind, ignoring their text: an
+    /// `Identifier("a")` is the same kind as `Identifier("b")`, but not as
+    /// `Keyword("b")`. Operators sharing a variant, such as the `Relational`
+    /// ones, only match the same operator.
+    pub fn same_kind(&self, other: &Token) -> bool {
+        match (self, other) {
+            (&Token::DeIncrement(ref a), &Token::DeIncrement(ref b)) |
+            (&Token::BitShift(ref a), &Token::BitShift(ref b)) |
+            (&Token::UpdateAssignment(ref a), &Token::UpdateAssignment(ref b)) => a == b,
+            (&Token::Relational(ref a), &Token::Relational(ref b)) => a == b,
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+
+    /// A copy of the token that doesn't borrow from the input, for keeping
+    /// tokens around after the input is gone. This allocates for every
+    /// token with text, so prefer the borrowed tokens where possible.
+    pub fn to_owned(&self) -> OwnedToken {
+        OwnedToken {
+            op: self.op(),
+            text: self.as_str().to_owned(),
+        }
+    }
+
+    /// The opcode of the token's variant.
+    pub fn op(&self) -> TokenOp {
+        match *self {
+            Token::Whitespace(_) => TokenOp::Whitespace,
+            Token::Shebang(_) => TokenOp::Shebang,
+            Token::LineComment(_) => TokenOp::LineComment,
+            Token::BlockComment(_) => TokenOp::BlockComment,
+            Token::NumericLiteral(_) => TokenOp::NumericLiteral,
+            Token::StringLiteral(_) => TokenOp::StringLiteral,
+            Token::RegexLiteral(_) => TokenOp::RegexLiteral,
+            Token::TemplateLiteral(_) => TokenOp::TemplateLiteral,
+            Token::Identifier(_) => TokenOp::Identifier,
+            Token::Keyword(_) => TokenOp::Keyword,
+            Token::PrivateIdentifier(_) => TokenOp::PrivateIdentifier,
+            Token::Unknown(_) => TokenOp::Unknown,
+            Token::DeIncrement(_) => TokenOp::DeIncrement,
+            Token::BitShift(_) => TokenOp::BitShift,
+            Token::Relational(_) => TokenOp::Relational,
+            Token::UpdateAssignment(_) => TokenOp::UpdateAssignment,
+            Token::Exponeniation => TokenOp::Exponeniation,
+            Token::Arrow => TokenOp::Arrow,
+            Token::Equal => TokenOp::Equal,
+            Token::LogicalOr => TokenOp::LogicalOr,
+            Token::LogicalAnd => TokenOp::LogicalAnd,
+            Token::NullishCoalescing => TokenOp::NullishCoalescing,
+            Token::OptionalChain => TokenOp::OptionalChain,
+            Token::BitwiseOr => TokenOp::BitwiseOr,
+            Token::BitwiseXOR => TokenOp::BitwiseXOR,
+            Token::BitwiseAnd => TokenOp::BitwiseAnd,
+            Token::BitwiseNot => TokenOp::BitwiseNot,
+            Token::Plus => TokenOp::Plus,
+            Token::Minus => TokenOp::Minus,
+            Token::Modulo => TokenOp::Modulo,
+            Token::Star => TokenOp::Star,
+            Token::Slash => TokenOp::Slash,
+            Token::Semicolon => TokenOp::Semicolon,
+            Token::LeftParen => TokenOp::LeftParen,
+            Token::RightParen => TokenOp::RightParen,
+            Token::LeftBrace => TokenOp::LeftBrace,
+            Token::RightBrace => TokenOp::RightBrace,
+            Token::LeftBracket => TokenOp::LeftBracket,
+            Token::RightBracket => TokenOp::RightBracket,
+            Token::Dot => TokenOp::Dot,
+            Token::Ellipsis => TokenOp::Ellipsis,
+            Token::Comma => TokenOp::Comma,
+            Token::QuestionMark => TokenOp::QuestionMark,
+            Token::Colon => TokenOp::Colon,
+            Token::ExclamationMark => TokenOp::ExclamationMark,
+            Token::StatementEnd => TokenOp::StatementEnd,
+            Token::JsxFragmentStart => TokenOp::JsxFragmentStart,
+            Token::JsxFragmentEnd => TokenOp::JsxFragmentEnd,
+            Token::Annotation(_) => TokenOp::Annotation,
+            Token::JsxText(_) => TokenOp::JsxText,
+        }
+    }
+
+    pub fn numeric_kind(&self) -> Option<NumericKind> {
+        let literal = match *self {
+            Token::NumericLiteral(s) => s.as_bytes(),
+            _ => return None,
+        };
+        if literal.len() < 2 || literal[0] != b'0' {
+            return Some(NumericKind::Decimal);
+        }
+        Some(match literal[1] {
+            b'x' | b'X' => NumericKind::Hex,
+            b'o' | b'O' => NumericKind::Octal,
+            b'b' | b'B' => NumericKind::Binary,
+            _ => NumericKind::Decimal,
+        })
+    }
+
+    /// Counts the blank lines in a whitespace token: the line breaks after
+    /// the first, so that `"\n\n"` separates two lines with one blank line
+    /// between them. Other tokens have none.
+    pub fn blank_line_count(&self) -> usize {
+        match *self {
+            Token::Whitespace(s) => {
+                // `\r\n` counts once, through its `\n`.
+                let breaks = s.bytes()
+                              .enumerate()
+                              .filter(|&(i, b)| {
+                                  b == b'\n' || b == b'\r' && s.as_bytes().get(i + 1) != Some(&b'\n')
+                              })
+                              .count();
+                if breaks > 0 { breaks - 1 } else { 0 }
+            }
+            _ => 0,
+        }
+    }
+
+    /// The text of a line or block comment with its `//`, `/*` and `*/`
+    /// delimiters removed. Other tokens have none.
+    pub fn comment_text(&self) -> Option<&'a str> {
+        match *self {
+            Token::LineComment(s) => Some(&s[2..]),
+            Token::BlockComment(s) if s.len() >= 4 && s.ends_with("*/") => Some(&s[2..s.len() - 2]),
+            Token::BlockComment(s) => Some(&s[2..]),
+            Token::Annotation(s) => Token::BlockComment(s).comment_text(),
+            _ => None,
+        }
+    }
+
+    /// The name of an annotation comment, without the whitespace around it.
+    /// Other tokens have none.
+    pub fn annotation_name(&self) -> Option<&'a str> {
+        match *self {
+            Token::Annotation(_) => self.comment_text().map(str::trim),
+            _ => None,
+        }
+    }
+
+    /// Counts the `${}` substitutions of a template literal, not including
+    /// ones inside nested templates. Other tokens have none.
+    pub fn template_substitution_count(&self) -> usize {
+        self.template_substitution_spans().len()
+    }
+
+    /// The byte ranges of the `${}` substitutions of a template literal
+    /// within its text, not including ones inside nested templates. Other
+    /// tokens have none.
+    pub fn template_substitution_spans(&self) -> Vec<Range<usize>> {
+        match *self {
+            Token::TemplateLiteral(s) => template_substitutions(s),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The token's source text, so joining every token of a `tokenize`
+    /// result in order reproduces the input.
+    pub fn as_str(&self) -> &'a str {
+        match *self {
+            Token::Whitespace(s) |
+            Token::Shebang(s) |
+            Token::LineComment(s) |
+            Token::BlockComment(s) |
+            Token::Annotation(s) |
+            Token::JsxText(s) |
+            Token::NumericLiteral(s) |
+            Token::StringLiteral(s) |
+            Token::RegexLiteral(s) |
+            Token::TemplateLiteral(s) |
+            Token::Identifier(s) |
+            Token::Keyword(s) |
+            Token::PrivateIdentifier(s) |
+            Token::Unknown(s) => s,
+            Token::DeIncrement(Operator::Add) => "++",
+            Token::DeIncrement(Operator::Subtract) => "--",
+            Token::DeIncrement(ref op) => op.as_str(),
+            Token::BitShift(ref op) => op.as_str(),
+            Token::Relational(ref rel) => rel.as_str(),
+            Token::UpdateAssignment(ref op) => {
+                match *op {
+                    Operator::UnsignedRightShift => ">>>=",
+                    Operator::RightShift => ">>=",
+                    Operator::LeftShift => "<<=",
+                    Operator::Exponeniation => "**=",
+                    Operator::Add => "+=",
+                    Operator::Subtract => "-=",
+                    Operator::Modulo => "%=",
+                    Operator::Divide => "/=",
+                    Operator::Multiply => "*=",
+                    Operator::BitwiseOr => "|=",
+                    Operator::BitwiseAnd => "&=",
+                    Operator::BitwiseXOR => "^=",
+                    Operator::LogicalOr => "||=",
+                    Operator::LogicalAnd => "&&=",
+                    Operator::NullishCoalescing => "??=",
+                }
+            }
+            Token::Exponeniation => "**",
+            Token::Arrow => "=>",
+            Token::Equal => "=",
+            Token::LogicalOr => "||",
+            Token::LogicalAnd => "&&",
+            Token::NullishCoalescing => "??",
+            Token::OptionalChain => "?.",
+            Token::BitwiseOr => "|",
+            Token::BitwiseXOR => "^",
+            Token::BitwiseAnd => "&",
+            Token::BitwiseNot => "~",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Modulo => "%",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Semicolon => ";",
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+            Token::LeftBrace => "{",
+            Token::RightBrace => "}",
+            Token::LeftBracket => "[",
+            Token::RightBracket => "]",
+            Token::Dot => ".",
+            Token::Ellipsis => "...",
+            Token::Comma => ",",
+            Token::QuestionMark => "?",
+            Token::Colon => ":",
+            Token::ExclamationMark => "!",
+            Token::StatementEnd => "",
+            Token::JsxFragmentStart => "<>",
+            Token::JsxFragmentEnd => "</>",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenizeOptions {
+    /// How many template literals may be nested inside each other's
+    /// substitutions before tokenizing gives up.
+    pub max_template_depth: usize,
+    /// Emit `Token::Unknown` for unrecognized characters instead of failing.
+    pub lenient: bool,
+    /// Always emit `let` as an identifier, as sloppy mode code like
+    /// `let = 1` may use it. Otherwise it is a keyword when standing alone
+    /// or directly followed by `[` or `{`, as in the declaration `let[a] = x`.
+    pub treat_let_as_identifier: bool,
+    /// Recognize regex literals where an expression may start. When off,
+    /// `/` is always division.
+    pub regex: bool,
+    /// Expect TypeScript-style type annotations. They tokenize like
+    /// JavaScript, so the `>>` closing nested type arguments, as in
+    /// `Array<Array<T>>`, is a shift; `structure::split_type_argument_closes`
+    /// splits it, along with `>>>`. See `structure::strip_type_annotations`.
+    pub type_annotations: bool,
+    /// Emit `using` as a keyword when it starts a declaration, as in
+    /// `using x = f()` or `await using x = f()`, whose `await` is then a
+    /// keyword too. `using` is an identifier otherwise.
+    pub resource_management: bool,
+    /// Insert a `Token::StatementEnd` after each `;` outside of `for` headers
+    /// and each token automatic semicolon insertion adds a semicolon after,
+    /// as `structure::mark_statement_ends` does.
+    pub emit_statement_boundaries: bool,
+    /// Recognize JSX fragments, `<>` and `</>`, and elements starting where
+    /// an expression may. The text among their children is emitted as
+    /// `Token::JsxText`, while `{}` expressions and the attributes of tags
+    /// are tokenized as JavaScript.
+    pub jsx: bool,
+    /// Tokenize a `#!` line anywhere but at the very start of the file as a
+    /// line comment, as when scripts are concatenated into a bundle. Only
+    /// such lines starting a line, or starting the window given to
+    /// `tokenize_spanned_offset_with_options`, are affected; a `#` is
+    /// otherwise an unexpected character.
+    pub demote_late_shebang: bool,
+    /// The most bytes a single token may have, to reject untrusted input
+    /// with absurdly long identifiers, strings or comments early. Runs of
+    /// whitespace aren't limited.
+    pub max_token_len: Option<usize>,
+    /// Annotations, such as `@__PURE__` or `#__NO_SIDE_EFFECTS__`, that a
+    /// block comment holding nothing else is emitted as a
+    /// `Token::Annotation` for. Other block comments stay
+    /// `Token::BlockComment`.
+    pub annotation_comments: Vec<String>,
+}
+
+impl Default for TokenizeOptions {
+    fn default() -> TokenizeOptions {
+        TokenizeOptions {
+            max_template_depth: 64,
+            lenient: false,
+            treat_let_as_identifier: false,
+            regex: true,
+            type_annotations: false,
+            resource_management: false,
+            emit_statement_boundaries: false,
+            jsx: false,
+            demote_late_shebang: false,
+            max_token_len: None,
+            annotation_comments: Vec::new(),
+        }
+    }
+}
+
+impl TokenizeOptions {
+    /// Options for strict mode code, where `let` is always reserved and
+    /// unrecognized characters are errors. This is the same as the default.
+    pub fn strict() -> TokenizeOptions {
+        TokenizeOptions::default()
+    }
+
+    /// Options for JSON, which has no regex literals.
+    pub fn json() -> TokenizeOptions {
+        TokenizeOptions { regex: false, ..TokenizeOptions::default() }
+    }
+
+    /// Options for machine-generated bundles, which are trusted not to nest
+    /// templates maliciously deep.
+    pub fn minified() -> TokenizeOptions {
+        TokenizeOptions { max_template_depth: usize::MAX, ..TokenizeOptions::default() }
+    }
+
+    /// Options for JSX.
+    pub fn jsx() -> TokenizeOptions {
+        TokenizeOptions { jsx: true, ..TokenizeOptions::default() }
+    }
+
+    /// Options for TypeScript, which has type annotations.
+    pub fn typescript() -> TokenizeOptions {
+        TokenizeOptions { type_annotations: true, ..TokenizeOptions::default() }
+    }
+
+    /// Options for a REPL, which tokenizes what has been typed so far after
+    /// every keystroke. Unrecognized characters become `Token::Unknown` and
+    /// literals left open run to the end of the input, so tokenizing never
+    /// fails. See `tokenize_repl`.
+    pub fn repl() -> TokenizeOptions {
+        TokenizeOptions {
+            lenient: true,
+            max_template_depth: usize::MAX,
+            ..TokenizeOptions::default()
+        }
+    }
+}
+
+/// Where tokenizing starts when the input is a window into a larger file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenizerState {
+    /// Between tokens.
+    Initial,
+    /// Inside a line comment, before the end of the line.
+    LineComment,
+    /// Inside a block comment, before its closing `*/`.
+    BlockComment,
+    /// Inside a string literal opened with the given quote.
+    StringLiteral(u8),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenizeError {
+    TemplateNestingTooDeep { offset: usize },
+    UnexpectedChar { offset: usize },
+    /// A numeric literal directly followed by an identifier, as in `3abc`.
+    /// The offset is that of the literal.
+    InvalidNumericLiteral { offset: usize },
+    /// A regex literal with a line break before its closing `/`. The offset
+    /// is that of the literal.
+    UnterminatedRegex { offset: usize },
+    /// A template literal with a `${` substitution that is never closed, as
+    /// in `` `a${b` ``. The offset is that of the literal.
+    UnterminatedTemplateSubstitution { offset: usize },
+    /// A token longer than `TokenizeOptions::max_token_len`. The offset is
+    /// that of the token.
+    TokenTooLong { offset: usize },
+}
+
+impl TokenizeError {
+    pub fn offset(&self) -> usize {
+        match *self {
+            TokenizeError::TemplateNestingTooDeep { offset } |
+            TokenizeError::UnexpectedChar { offset } |
+            TokenizeError::InvalidNumericLiteral { offset } |
+            TokenizeError::UnterminatedRegex { offset } |
+            TokenizeError::UnterminatedTemplateSubstitution { offset } |
+            TokenizeError::TokenTooLong { offset } => offset,
+        }
+    }
+
+    // The same error `by` bytes further into the input.
+    fn shifted(self, by: usize) -> TokenizeError {
+        match self {
+            TokenizeError::TemplateNestingTooDeep { offset } => {
+                TokenizeError::TemplateNestingTooDeep { offset: offset + by }
+            }
+            TokenizeError::UnexpectedChar { offset } => {
+                TokenizeError::UnexpectedChar { offset: offset + by }
+            }
+            TokenizeError::InvalidNumericLiteral { offset } => {
+                TokenizeError::InvalidNumericLiteral { offset: offset + by }
+            }
+            TokenizeError::UnterminatedRegex { offset } => {
+                TokenizeError::UnterminatedRegex { offset: offset + by }
+            }
+            TokenizeError::UnterminatedTemplateSubstitution { offset } => {
+                TokenizeError::UnterminatedTemplateSubstitution { offset: offset + by }
+            }
+            TokenizeError::TokenTooLong { offset } => {
+                TokenizeError::TokenTooLong { offset: offset + by }
+            }
+        }
+    }
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.description(), self.offset())
+    }
+}
+
+impl Error for TokenizeError {
+    fn description(&self) -> &str {
+        match *self {
+            TokenizeError::TemplateNestingTooDeep { .. } => "template literals nested too deeply",
+            TokenizeError::UnexpectedChar { .. } => "unexpected character",
+            TokenizeError::InvalidNumericLiteral { .. } => "identifier directly after number",
+            TokenizeError::UnterminatedRegex { .. } => "line break in regex literal",
+            TokenizeError::UnterminatedTemplateSubstitution { .. } => {
+                "unclosed template substitution"
+            }
+            TokenizeError::TokenTooLong { .. } => "token too long",
+        }
+    }
+}
+
+// The 1-based line and column, in characters, of `offset` in `input`.
+fn line_and_column(input: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    let mut chars = input[..offset.min(input.len())].chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' || (c == '\r' && chars.peek() != Some(&'\n')) {
+            line += 1;
+            column = 1;
+        } else if c != '\r' {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+/// A `TokenizeError` located by line and column, and by file when known.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileTokenizeError<'a> {
+    pub file: Option<&'a str>,
+    /// 1-based line of the error's offset.
+    pub line: usize,
+    /// 1-based column of the error's offset, counted in characters.
+    pub column: usize,
+    pub error: TokenizeError,
+}
+
+impl<'a> FileTokenizeError<'a> {
+    /// Locates `error`, which happened tokenizing `input`. Lines end at
+    /// `\n`, `\r\n` or `\r`.
+    pub fn new(file: Option<&'a str>, input: &str, error: TokenizeError) -> FileTokenizeError<'a> {
+        let (line, column) = line_and_column(input, error.offset());
+        FileTokenizeError {
+            file: file,
+            line: line,
+            column: column,
+            error: error,
+        }
+    }
+}
+
+impl<'a> fmt::Display for FileTokenizeError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = self.file {
+            try!(write!(f, "{}:", file));
+        }
+        write!(f, "{}:{}: {}", self.line, self.column, self.error.description())
+    }
+}
+
+impl<'a> Error for FileTokenizeError<'a> {
+    fn description(&self) -> &str {
+        self.error.description()
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        Some(&self.error)
+    }
+}
+
+/// Shows where `error`, which happened tokenizing `input`, is: the line it
+/// is on, after its number, and below it a `^` under the offending
+/// character, as in
+///
+/// ```text
+/// 2 | x = /a
+///   |     ^
+/// ```
+pub fn format_error_snippet(input: &str, error: &TokenizeError) -> String {
+    let offset = error.offset().min(input.len());
+    let is_line_break = |c: char| c == '\n' || c == '\r';
+    let start = input[..offset].rfind(&is_line_break).map_or(0, |index| index + 1);
+    let end = input[offset..].find(&is_line_break).map_or(input.len(), |index| offset + index);
+    let number = line_and_column(input, offset).0.to_string();
+    // Tabs are kept so that the caret lines up however they are displayed.
+    let indent: String = input[start..offset]
+                             .chars()
+                             .map(|c| if c == '\t' { '\t' } else { ' ' })
+                             .collect();
+    format!("{} | {}\n{:width$} | {}^",
+            number,
+            &input[start..end],
+            "",
+            indent,
+            width = number.len())
+}
+
+/// What `tokenize_with_handler` does with a character that starts no token.
+#[derive(Debug, PartialEq, Eq)]
+pub enum UnknownAction<'a> {
+    /// Drops this many bytes without producing a token.
+    Skip(usize),
+    /// Produces this token, which should be the text of the input at the
+    /// character's offset, and continues after it.
+    Emit(Token<'a>),
+    /// Fails with `TokenizeError::UnexpectedChar`.
+    Error,
+}
+
+impl<'a> fmt::Display for Token<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(PartialEq, Eq)]
+pub struct Spanned<'a> {
+    pub token: Token<'a>,
+    /// Byte range of the token within the tokenized input.
+    pub span: Range<usize>,
+}
+
+/// Formats as the token followed by its span, as in `Identifier("a")@3..4`.
+impl<'a> fmt::Debug for Spanned<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}@{}..{}", self.token, self.span.start, self.span.end)
+    }
+}
+
+impl<'a> AsRef<Token<'a>> for Spanned<'a> {
+    fn as_ref(&self) -> &Token<'a> {
+        &self.token
+    }
+}
+
+impl<'a> AsRef<Token<'a>> for Token<'a> {
+    fn as_ref(&self) -> &Token<'a> {
+        self
+    }
+}
+
+/// A token owning its text, made by `Token::to_owned`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedToken {
+    pub op: TokenOp,
+    pub text: String,
+}
+
+impl OwnedToken {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
 }
 
 fn is_id(c: u8) -> bool {
     (c as char).is_alphabetic() || c == b'$' || c == b'_'
 }
 
+// Byte length of the identifier character at `index`, or 0 if there isn't one.
+// Non-ASCII characters are decoded so they are never split.
+fn id_char_len(input: &str, index: usize) -> usize {
+    let bytes = input.as_bytes();
+    if index >= bytes.len() {
+        0
+    } else if bytes[index] < 0x80 {
+        if is_id(bytes[index]) { 1 } else { 0 }
+    } else {
+        match input[index..].chars().next() {
+            Some(c) if c.is_alphabetic() => c.len_utf8(),
+            _ => 0,
+        }
+    }
+}
+
+/// Reserved words, which can never be used as identifiers. `let` and `yield`
+/// are only reserved in strict code but are always treated as keywords here.
 #[allow(cyclomatic_complexity)]
-fn is_keyword(s: &str) -> bool {
+pub fn is_keyword(s: &str) -> bool {
     s == "var" || s == "let" || s == "function" || s == "return" || s == "for" ||
-    s == "undefined" || s == "in" || s == "break" || s == "case" ||
+    s == "in" || s == "break" || s == "case" ||
     s == "continue" || s == "debugger" || s == "default" || s == "do" ||
-    s == "if" || s == "finally" ||
+    s == "if" || s == "else" || s == "catch" || s == "finally" ||
     s == "switch" || s == "throw" || s == "try" ||
     s == "const" || s == "while" || s == "with" || s == "new" || s == "this" || s == "super" ||
-    s == "class" || s == "extends" || s == "export" || s == "import" ||
+    s == "class" || s == "extends" || s == "export" || s == "import" || s == "enum" ||
     s == "yield" || s == "null" || s == "true" ||
     s == "false" ||
     s == "instanceof" || s == "typeof" ||
     s == "void" || s == "delete"
 }
 
+/// Words that only act as keywords in certain positions (`async`, `of`,
+/// `get`, ...) or in strict code (`static`, `private`, ...). Elsewhere they
+/// are ordinary identifiers, so the tokenizer emits them as
+/// `Token::Identifier`.
+pub fn is_contextual_keyword(s: &str) -> bool {
+    s == "async" || s == "await" || s == "of" || s == "get" || s == "set" ||
+    s == "static" || s == "as" || s == "satisfies" || s == "from" || s == "target" ||
+    s == "meta" ||
+    s == "implements" || s == "interface" || s == "package" ||
+    s == "private" || s == "protected" || s == "public"
+}
+
+// Like `id_char_len`, but also accepting the digits allowed after the first character.
+fn id_continue_len(input: &str, index: usize) -> usize {
+    match input.as_bytes().get(index) {
+        Some(&b) if b >= b'0' && b <= b'9' => 1,
+        _ => id_char_len(input, index),
+    }
+}
+
 fn next_occurence_of(bytes: &[u8], index: usize, byte: u8) -> usize {
     let mut ignore_next = true;
     let mut end_index = index;
@@ -161,56 +965,392 @@ fn find_string_literal(bytes: &[u8], start_index: usize, quote_type: u8) -> usiz
     next_occurence_of(bytes, start_index, quote_type)
 }
 
-fn find_template_string_literal(bytes: &[u8], start_index: usize) -> usize {
-    next_occurence_of(bytes, start_index, b'`')
+// Scans a template literal, including any `${}` substitutions and templates
+// nested inside them. Nesting is tracked with an explicit stack, so the depth
+// limit is a policy decision rather than protection against stack overflow.
+// Fails on the backtick that exceeds `max_depth`, and unless `lenient`, when
+// the input ends inside a `${}` substitution. When `substitutions` is given,
+// the ranges of the outermost template's completed `${}` substitutions are
+// pushed to it.
+fn find_template_string_literal(bytes: &[u8],
+                                start_index: usize,
+                                max_depth: usize,
+                                lenient: bool,
+                                mut substitutions: Option<&mut Vec<Range<usize>>>)
+                                -> Result<usize, TokenizeError> {
+    // Each entry is `None` for template text, or `Some(open braces)` for a substitution.
+    let mut stack: Vec<Option<usize>> = vec![None];
+    let mut template_depth = 1;
+    let mut substitution_start = 0;
+    let mut end_index = start_index + 1;
+
+    while end_index < bytes.len() {
+        let b = bytes[end_index];
+        match *last_item(&stack) {
+            None => {
+                match b {
+                    b'\\' => end_index += 1,
+                    b'`' => {
+                        stack.pop();
+                        template_depth -= 1;
+                        if stack.is_empty() {
+                            return Ok(end_index + 1);
+                        }
+                    }
+                    b'$' if is_next(bytes, end_index, b'{') => {
+                        if stack.len() == 1 {
+                            substitution_start = end_index;
+                        }
+                        stack.push(Some(0));
+                        end_index += 1;
+                    }
+                    _ => {}
+                }
+            }
+            Some(braces) => {
+                match b {
+                    b'{' => *stack.last_mut().unwrap() = Some(braces + 1),
+                    b'}' if braces == 0 => {
+                        stack.pop();
+                        if stack.len() == 1 {
+                            if let Some(ref mut substitutions) = substitutions {
+                                substitutions.push(substitution_start..end_index + 1);
+                            }
+                        }
+                    }
+                    b'}' => *stack.last_mut().unwrap() = Some(braces - 1),
+                    b'"' | b'\'' => {
+                        end_index = next_occurence_of(bytes, end_index, b);
+                        continue;
+                    }
+                    b'`' => {
+                        template_depth += 1;
+                        if template_depth > max_depth {
+                            return Err(TokenizeError::TemplateNestingTooDeep { offset: end_index });
+                        }
+                        stack.push(None);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        end_index += 1;
+    }
+
+    if !lenient && stack.iter().any(Option::is_some) {
+        return Err(TokenizeError::UnterminatedTemplateSubstitution { offset: start_index });
+    }
+    Ok(bytes.len())
+}
+
+// Byte ranges of the complete `${}` substitutions in a template literal's text.
+fn template_substitutions(template: &str) -> Vec<Range<usize>> {
+    let mut substitutions = Vec::new();
+    let _ = find_template_string_literal(template.as_bytes(),
+                                         0,
+                                         usize::MAX,
+                                         true,
+                                         Some(&mut substitutions));
+    substitutions
 }
 
-fn find_regex_literal(bytes: &[u8], start_index: usize) -> usize {
-    let mut end_index = next_occurence_of(bytes, start_index, b'/');
+/// Whether every `${` in the template literal `template`, given with its
+/// backticks, is closed by a matching `}`. Braces inside the substitutions,
+/// such as those of an object literal, must balance as well.
+pub fn template_braces_balanced(template: &str) -> bool {
+    find_template_string_literal(template.as_bytes(), 0, usize::MAX, false, None).is_ok()
+}
 
-    while end_index < bytes.len() && !(bytes[end_index] as char).is_whitespace() {
-        end_index += 1;
+fn is_line_terminator(bytes: &[u8], index: usize) -> bool {
+    match bytes[index] {
+        b'\n' | b'\r' => true,
+        // U+2028 and U+2029
+        0xE2 => bytes.get(index + 1) == Some(&0x80) &&
+                (bytes.get(index + 2) == Some(&0xA8) || bytes.get(index + 2) == Some(&0xA9)),
+        _ => false,
     }
+}
 
-    end_index
+// Finds the end of the regex body starting at the `/` at `start_index`, along
+// with the index of its first line terminator, which regexes can't contain
+// even when escaped. A `/` inside a character class like `[/]` doesn't end the
+// body, and escapes such as `\/` or `\]` are skipped whole.
+fn find_regex_body(bytes: &[u8], start_index: usize) -> (usize, Option<usize>) {
+    let mut in_class = false;
+    let mut line_break = None;
+    let mut index = start_index + 1;
+    let mut escaped = false;
+    while index < bytes.len() {
+        if line_break.is_none() && is_line_terminator(bytes, index) {
+            line_break = Some(index);
+        }
+        match bytes[index] {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'[' => in_class = true,
+            b']' => in_class = false,
+            b'/' if !in_class => return (index + 1, line_break),
+            _ => {}
+        }
+        index += 1;
+    }
+    (bytes.len(), line_break)
 }
 
-fn as_str(bytes: &[u8]) -> &str {
-    unsafe { str::from_utf8_unchecked(bytes) }
+// Finds the end of the regex literal starting at `start_index`, including its
+// flags, which are identifier characters. Unless `lenient`, a line terminator
+// in the body is an error giving its index.
+fn find_regex_literal(bytes: &[u8], start_index: usize, lenient: bool) -> Result<usize, usize> {
+    let (mut end_index, line_break) = find_regex_body(bytes, start_index);
+    if let (Some(line_break), false) = (line_break, lenient) {
+        return Err(line_break);
+    }
+
+    while end_index < bytes.len() && bytes[end_index] < 0x80 &&
+          (is_id(bytes[end_index]) || (bytes[end_index] as char).is_digit(10)) {
+        end_index += 1;
+    }
+
+    Ok(end_index)
 }
 
-fn tokenize_blackspace<'a>(tokens: &mut Vec<Token<'a>>, input: &'a str, position: usize) {
-    let bytes = input.as_bytes();
+fn is_digit(bytes: &[u8], index: usize, radix: u32) -> bool {
+    index < bytes.len() && (bytes[index] as char).is_digit(radix)
+}
 
-    let mut start_index = 0;
-    while start_index < bytes.len() {
-        if !tokens.is_empty() && !last_item(&tokens).is_greyspace() {
-            tokens.push(Token::Whitespace(""));
+// Consumes digits, along with `_` separators between two of them when
+// `separators` is set.
+fn consume_digits(bytes: &[u8], mut index: usize, radix: u32, separators: bool) -> usize {
+    let start = index;
+    loop {
+        if is_digit(bytes, index, radix) {
+            index += 1;
+        } else if separators && index > start && bytes.get(index) == Some(&b'_') &&
+                  is_digit(bytes, index + 1, radix) {
+            index += 2;
+        } else {
+            return index;
         }
+    }
+}
 
-        let mut end_index = start_index + 1;
-        if is_id(bytes[start_index]) {
-            while end_index < bytes.len() && is_id(bytes[end_index]) {
-                end_index += 1;
+fn find_numeric_literal(bytes: &[u8], start_index: usize) -> usize {
+    if bytes[start_index] == b'0' && start_index + 1 < bytes.len() {
+        let radix = match bytes[start_index + 1] {
+            b'x' | b'X' => 16,
+            b'o' | b'O' => 8,
+            b'b' | b'B' => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            let end_index = consume_digits(bytes, start_index + 2, radix, true);
+            if end_index > start_index + 2 && bytes.get(end_index) == Some(&b'n') {
+                return end_index + 1;
             }
+            return end_index;
+        }
+    }
 
-            tokens.push(Token::Identifier(as_str(&bytes[start_index..end_index])));
-        } else if (bytes[start_index] as char).is_numeric() {
-            // consume digits, then, if we find an e, consume digits after it as well.
+    // consume digits and an optional fraction, then, if we find an e
+    // followed by digits, consume the (optionally signed) exponent as well.
+    // Integers starting with 0, like legacy octal `017`, can't have separators.
+    let mut end_index = consume_digits(bytes, start_index, 10, bytes[start_index] != b'0');
+    // A BigInt, which is an integer without leading zeros followed by `n`.
+    if bytes.get(end_index) == Some(&b'n') &&
+       (bytes[start_index] != b'0' || end_index == start_index + 1) {
+        return end_index + 1;
+    }
+    if end_index < bytes.len() && bytes[end_index] == b'.' {
+        end_index = consume_digits(bytes, end_index + 1, 10, true);
+    }
 
-            while end_index < bytes.len() && (bytes[end_index] as char).is_numeric() {
-                end_index += 1;
-            }
+    if end_index < bytes.len() && (bytes[end_index] == b'e' || bytes[end_index] == b'E') {
+        let mut exponent_index = end_index + 1;
+        if exponent_index < bytes.len() &&
+           (bytes[exponent_index] == b'+' || bytes[exponent_index] == b'-') {
+            exponent_index += 1;
+        }
+        if is_digit(bytes, exponent_index, 10) {
+            end_index = consume_digits(bytes, exponent_index, 10, true);
+        }
+    }
 
-            if end_index < bytes.len() && (bytes[end_index] == b'e' || bytes[end_index] == b'E') {
-                end_index += 1;
+    end_index
+}
 
-                while end_index < bytes.len() && (bytes[end_index] as char).is_numeric() {
-                    end_index += 1;
-                }
+/// A misplaced `_` numeric separator, found by `validate_numeric_separators`.
+/// Offsets are of the separator within the literal.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SeparatorError {
+    /// A separator starting a part of the literal, as in `0x_1` or `1._5`.
+    Leading { offset: usize },
+    /// A separator ending a part of the literal, as in `1_`, `1_.5` or
+    /// `1_e5`.
+    Trailing { offset: usize },
+    /// A separator directly after another, as in `1__0`.
+    Doubled { offset: usize },
+    /// A separator in an integer starting with 0, as in `0_1`, which would
+    /// be a legacy octal literal.
+    AfterLeadingZero { offset: usize },
+}
+
+impl SeparatorError {
+    pub fn offset(&self) -> usize {
+        match *self {
+            SeparatorError::Leading { offset } |
+            SeparatorError::Trailing { offset } |
+            SeparatorError::Doubled { offset } |
+            SeparatorError::AfterLeadingZero { offset } => offset,
+        }
+    }
+}
+
+impl fmt::Display for SeparatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.description(), self.offset())
+    }
+}
+
+impl Error for SeparatorError {
+    fn description(&self) -> &str {
+        match *self {
+            SeparatorError::Leading { .. } => "numeric separator before any digits",
+            SeparatorError::Trailing { .. } => "numeric separator after the last digit",
+            SeparatorError::Doubled { .. } => "consecutive numeric separators",
+            SeparatorError::AfterLeadingZero { .. } => {
+                "numeric separator in number starting with 0"
             }
+        }
+    }
+}
 
-            tokens.push(Token::NumericLiteral(as_str(&bytes[start_index..end_index])));
+// Checks the separators of one run of digits, which starts at `offset`
+// within its literal.
+fn validate_digit_separators(digits: &[u8], offset: usize) -> Result<(), SeparatorError> {
+    for (index, &b) in digits.iter().enumerate() {
+        if b != b'_' {
+            continue;
+        }
+        let offset = offset + index;
+        if index == 0 {
+            return Err(SeparatorError::Leading { offset: offset });
+        } else if index == digits.len() - 1 {
+            return Err(SeparatorError::Trailing { offset: offset });
+        } else if digits[index + 1] == b'_' {
+            return Err(SeparatorError::Doubled { offset: offset });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every `_` separator in the numeric literal `literal` stands
+/// between two digits: not next to a `0x`-style prefix, a decimal point or
+/// an exponent, nor next to another separator. Integers starting with 0
+/// can't have separators at all. The tokenizer never makes such separators
+/// part of a literal, and fails on what follows instead unless lenient;
+/// this checks literals from elsewhere and explains what is wrong.
+pub fn validate_numeric_separators(literal: &str) -> Result<(), SeparatorError> {
+    let bytes = literal.as_bytes();
+    let is_prefixed = match Token::NumericLiteral(literal).numeric_kind() {
+        Some(NumericKind::Decimal) | None => false,
+        _ => true,
+    };
+    if is_prefixed {
+        return validate_digit_separators(&bytes[2..], 2);
+    }
+
+    let mantissa_end = bytes.iter().position(|&b| b == b'e' || b == b'E').unwrap_or(bytes.len());
+    let integer_end = bytes[..mantissa_end]
+                          .iter()
+                          .position(|&b| b == b'.')
+                          .unwrap_or(mantissa_end);
+    if bytes.first() == Some(&b'0') {
+        if let Some(index) = bytes[..integer_end].iter().position(|&b| b == b'_') {
+            return Err(SeparatorError::AfterLeadingZero { offset: index });
+        }
+    }
+    try!(validate_digit_separators(&bytes[..integer_end], 0));
+    if integer_end < mantissa_end {
+        try!(validate_digit_separators(&bytes[integer_end + 1..mantissa_end], integer_end + 1));
+    }
+    if mantissa_end < bytes.len() {
+        let mut exponent = mantissa_end + 1;
+        if exponent < bytes.len() && (bytes[exponent] == b'+' || bytes[exponent] == b'-') {
+            exponent += 1;
+        }
+        try!(validate_digit_separators(&bytes[exponent..], exponent));
+    }
+    Ok(())
+}
+
+fn as_str(bytes: &[u8]) -> &str {
+    unsafe { str::from_utf8_unchecked(bytes) }
+}
+
+// What `tokenize_with_options` does with a character that starts no token:
+// keep it as `Token::Unknown` when lenient, or fail.
+fn default_unknown_action(input: &str, offset: usize, lenient: bool) -> UnknownAction {
+    if !lenient {
+        return UnknownAction::Error;
+    }
+    let len = input[offset..].chars().next().map_or(1, |c| c.len_utf8());
+    UnknownAction::Emit(Token::Unknown(&input[offset..offset + len]))
+}
+
+fn tokenize_blackspace<'a, S>(tokens: &mut S,
+                              input: &'a str,
+                              position: usize,
+                              options: &TokenizeOptions,
+                              on_unknown: &mut FnMut(u8, usize) -> UnknownAction<'a>)
+                              -> Result<(), TokenizeError>
+    where S: TokenSink<'a>
+{
+    let bytes = input.as_bytes();
+
+    let mut start_index = 0;
+    while start_index < bytes.len() {
+        if !tokens.is_empty() && !last_item(&tokens).is_greyspace() {
+            tokens.push(Token::Whitespace(""));
+        }
+
+        let mut end_index = start_index + 1;
+        let id_len = id_char_len(input, start_index);
+        if id_len > 0 {
+            end_index = start_index + id_len;
+            loop {
+                let id_len = id_continue_len(input, end_index);
+                if id_len == 0 {
+                    break;
+                }
+                end_index += id_len;
+            }
+
+            let word = as_str(&bytes[start_index..end_index]);
+            let is_let_declaration = word == "let" && !options.treat_let_as_identifier &&
+                                     (is_next(bytes, end_index - 1, b'[') ||
+                                      is_next(bytes, end_index - 1, b'{'));
+            tokens.push(if is_let_declaration {
+                Token::Keyword(word)
+            } else {
+                Token::Identifier(word)
+            });
+        } else if bytes[start_index] == b'#' && id_char_len(input, start_index + 1) > 0 {
+            end_index = start_index + 1;
+            loop {
+                let id_len = id_continue_len(input, end_index);
+                if id_len == 0 {
+                    break;
+                }
+                end_index += id_len;
+            }
+            tokens.push(Token::PrivateIdentifier(as_str(&bytes[start_index..end_index])));
+        } else if is_digit(bytes, start_index, 10) ||
+                  bytes[start_index] == b'.' && is_digit(bytes, start_index + 1, 10) {
+            end_index = find_numeric_literal(bytes, start_index);
+            if !options.lenient && id_char_len(input, end_index) > 0 {
+                return Err(TokenizeError::InvalidNumericLiteral { offset: position + start_index });
+            }
+            tokens.push(Token::NumericLiteral(as_str(&bytes[start_index..end_index])));
         } else {
             let curr = bytes[start_index];
             let next = if end_index < bytes.len() {
@@ -225,13 +1365,22 @@ fn tokenize_blackspace<'a>(tokens: &mut Vec<Token<'a>>, input: &'a str, position
             };
 
             let token = match (curr, next, next_next) {
+                (b'<', Some(b'/'), Some(b'>')) if options.jsx => Token::JsxFragmentEnd,
+                (b'<', Some(b'>'), _) if options.jsx => Token::JsxFragmentStart,
                 (b'>', Some(b'>'), Some(b'>')) if end_index + 2 < bytes.len() &&
                                                   bytes[end_index + 2] == b'=' => {
                     Token::UpdateAssignment(Operator::UnsignedRightShift)
                 }
+                (b'>', Some(b'>'), Some(b'>')) => Token::BitShift(Operator::UnsignedRightShift),
                 (b'*', Some(b'*'), Some(b'=')) => Token::UpdateAssignment(Operator::Exponeniation),
+                (b'|', Some(b'|'), Some(b'=')) => Token::UpdateAssignment(Operator::LogicalOr),
+                (b'&', Some(b'&'), Some(b'=')) => Token::UpdateAssignment(Operator::LogicalAnd),
+                (b'?', Some(b'?'), Some(b'=')) => {
+                    Token::UpdateAssignment(Operator::NullishCoalescing)
+                }
                 (b'<', Some(b'<'), Some(b'=')) => Token::UpdateAssignment(Operator::LeftShift),
                 (b'>', Some(b'>'), Some(b'=')) => Token::UpdateAssignment(Operator::RightShift),
+                (b'.', Some(b'.'), Some(b'.')) => Token::Ellipsis,
                 (b'=', Some(b'='), Some(b'=')) => Token::Relational(Relational::EqualStrict),
                 (b'!', Some(b'='), Some(b'=')) => Token::Relational(Relational::NotEqualStrict),
                 (b'=', Some(b'='), _) => Token::Relational(Relational::Equal),
@@ -247,11 +1396,17 @@ fn tokenize_blackspace<'a>(tokens: &mut Vec<Token<'a>>, input: &'a str, position
                 (b'*', Some(b'*'), _) => Token::Exponeniation,
                 (b'|', Some(b'|'), _) => Token::LogicalOr,
                 (b'&', Some(b'&'), _) => Token::LogicalAnd,
+                (b'?', Some(b'?'), _) => Token::NullishCoalescing,
+                // `a?.5:b` is a conditional with a fractional number.
+                (b'?', Some(b'.'), Some(b)) if (b as char).is_digit(10) => Token::QuestionMark,
+                (b'?', Some(b'.'), _) => Token::OptionalChain,
                 (b'=', Some(b'>'), _) => Token::Arrow,
                 (b'%', Some(b'='), _) => Token::UpdateAssignment(Operator::Modulo),
                 (b'/', Some(b'='), _) => Token::UpdateAssignment(Operator::Divide),
                 (b'*', Some(b'='), _) => Token::UpdateAssignment(Operator::Multiply),
                 (b'|', Some(b'='), _) => Token::UpdateAssignment(Operator::BitwiseOr),
+                (b'&', Some(b'='), _) => Token::UpdateAssignment(Operator::BitwiseAnd),
+                (b'^', Some(b'='), _) => Token::UpdateAssignment(Operator::BitwiseXOR),
                 (b'.', _, _) => Token::Dot,
                 (b'(', _, _) => Token::LeftParen,
                 (b')', _, _) => Token::RightParen,
@@ -277,38 +1432,39 @@ fn tokenize_blackspace<'a>(tokens: &mut Vec<Token<'a>>, input: &'a str, position
                 (b'|', _, _) => Token::BitwiseOr,
                 (b'^', _, _) => Token::BitwiseXOR,
                 _ => {
-                    panic!("Unknown Blackspace Token: matched: {:?}, {:?}, {:?} at {}",
-                           curr as char,
-                           next,
-                           next_next,
-                           position + start_index)
+                    let len = match on_unknown(curr, position + start_index) {
+                        UnknownAction::Skip(len) => len,
+                        UnknownAction::Emit(token) => {
+                            let len = token.as_str().len();
+                            tokens.push(token);
+                            len
+                        }
+                        UnknownAction::Error => {
+                            return Err(TokenizeError::UnexpectedChar {
+                                offset: position + start_index,
+                            })
+                        }
+                    };
+                    // Consume at least the character, and never past the
+                    // end of this run of non-whitespace.
+                    end_index = (start_index + len).max(end_index).min(bytes.len());
+                    while !input.is_char_boundary(end_index) {
+                        end_index += 1;
+                    }
+                    start_index = end_index;
+                    continue;
                 }
             };
 
-            end_index += match token {
-                Token::UpdateAssignment(Operator::Exponeniation) |
-                Token::UpdateAssignment(Operator::RightShift) |
-                Token::UpdateAssignment(Operator::LeftShift) |
-                Token::Relational(Relational::EqualStrict) |
-                Token::Relational(Relational::NotEqualStrict) => 2,
-                Token::Relational(Relational::Equal) |
-                Token::Relational(Relational::NotEqual) |
-                Token::Relational(Relational::LessOrEqual) |
-                Token::Relational(Relational::GreaterOrEqual) |
-                Token::UpdateAssignment(_) |
-                Token::DeIncrement(_) |
-                Token::BitShift(_) |
-                Token::Exponeniation |
-                Token::LogicalOr |
-                Token::LogicalAnd |
-                Token::Arrow => 1,
-                _ => 0,
-            };
+            // The first byte was already consumed above.
+            end_index += token.as_str().len() - 1;
             tokens.push(token);
         }
 
         start_index = end_index;
     }
+
+    Ok(())
 }
 
 fn is_next(bytes: &[u8], current_index: usize, next: u8) -> bool {
@@ -325,9 +1481,475 @@ fn last_item<T>(slice: &[T]) -> &T {
     unsafe { slice.get_unchecked(slice.len() - 1) }
 }
 
-#[allow(cyclomatic_complexity)]
+/// Whether both token sequences have the same kinds of tokens in the same
+/// order, as compared by `Token::same_kind`.
+pub fn tokens_same_shape(a: &[Token], b: &[Token]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.same_kind(b))
+}
+
+fn unlimited_options() -> TokenizeOptions {
+    TokenizeOptions {
+        max_template_depth: usize::MAX,
+        lenient: true,
+        treat_let_as_identifier: false,
+        regex: true,
+        type_annotations: false,
+        resource_management: false,
+        emit_statement_boundaries: false,
+        jsx: false,
+        demote_late_shebang: false,
+        max_token_len: None,
+        annotation_comments: Vec::new(),
+    }
+}
+
+/// Tokenizes leniently and without limits, so this never fails.
 pub fn tokenize(input: &str) -> Vec<Token> {
+    match tokenize_with_options(input, &unlimited_options()) {
+        Ok(tokens) => tokens,
+        Err(err) => unreachable!("lenient tokenize cannot fail: {}", err),
+    }
+}
+
+// How many tokens `tokenize_with_budget` produces between calls to its
+// budget callback.
+const BUDGET_INTERVAL: usize = 256;
+
+/// Tokenizes like `tokenize`, but calls `budget` every few hundred tokens
+/// and gives up, returning `None`, as soon as it returns false. This lets an
+/// editor abandon tokenizing a large input that has changed in the meantime.
+pub fn tokenize_with_budget<'a, F>(input: &'a str, budget: &mut F) -> Option<Vec<Token<'a>>>
+    where F: FnMut() -> bool
+{
+    let on_unknown = &mut |_, offset| default_unknown_action(input, offset, true);
+    match tokenize_checked(input, &unlimited_options(), budget, on_unknown) {
+        Ok(tokens) => tokens,
+        Err(err) => unreachable!("lenient tokenize cannot fail: {}", err),
+    }
+}
+
+pub fn tokenize_spanned(input: &str) -> Vec<Spanned> {
+    with_spans(tokenize(input))
+}
+
+// Pairs up tokens of a whole input with their spans.
+fn with_spans(tokens: Vec<Token>) -> Vec<Spanned> {
+    let mut offset = 0;
+    tokens.into_iter()
+          .map(|token| {
+              let start = offset;
+              offset += token.as_str().len();
+              Spanned {
+                  token: token,
+                  span: start..offset,
+              }
+          })
+          .collect()
+}
+
+/// Tokenizes and spans `input` like `tokenize_spanned`, but with the default
+/// options, under which lexical errors aren't ignored. On an error, the
+/// tokens before it are returned along with the error, so that an editor
+/// can still highlight the start of an invalid file.
+pub fn tokenize_spanned_partial(input: &str) -> (Vec<Spanned>, Option<TokenizeError>) {
+    match tokenize_with_options(input, &TokenizeOptions::default()) {
+        Ok(tokens) => (with_spans(tokens), None),
+        Err(error) => {
+            let valid = &input[..error.offset().min(input.len())];
+            (tokenize_spanned(valid), Some(error))
+        }
+    }
+}
+
+// Length of the rest of a token that `input` starts in the middle of.
+fn continued_token_len(input: &[u8], state: TokenizerState) -> usize {
+    match state {
+        TokenizerState::Initial => 0,
+        TokenizerState::LineComment => memchr::memchr(b'\n', input).unwrap_or(input.len()),
+        TokenizerState::BlockComment => {
+            let mut index = 0;
+            while let Some(pos) = memchr::memchr(b'/', &input[index..]) {
+                index += pos + 1;
+                if index >= 2 && input[index - 2] == b'*' {
+                    return index;
+                }
+            }
+            input.len()
+        }
+        TokenizerState::StringLiteral(quote) => {
+            let mut index = 0;
+            while index < input.len() {
+                let b = input[index];
+                index += 1;
+                if b == quote {
+                    return index;
+                } else if b == b'\\' {
+                    index += 1;
+                }
+            }
+            input.len()
+        }
+    }
+}
+
+/// Like `tokenize_spanned` for a window `slice` of a larger file, with spans
+/// in whole-file coordinates given the window starts at `base_offset`.
+///
+/// When the window starts partway through a comment or string, `state` says
+/// which, and the first token holds just the remainder of it.
+pub fn tokenize_spanned_offset(slice: &str,
+                               base_offset: usize,
+                               state: Option<TokenizerState>)
+                               -> Vec<Spanned> {
+    match tokenize_spanned_offset_with_options(slice, base_offset, state, &unlimited_options()) {
+        Ok(tokens) => tokens,
+        Err(err) => unreachable!("lenient tokenize cannot fail: {}", err),
+    }
+}
+
+/// Like `tokenize_spanned_offset`, tokenizing the rest of the window after
+/// any continued token with `options`. Errors are in whole-file coordinates
+/// too.
+pub fn tokenize_spanned_offset_with_options<'a>(slice: &'a str,
+                                                base_offset: usize,
+                                                state: Option<TokenizerState>,
+                                                options: &TokenizeOptions)
+                                                -> Result<Vec<Spanned<'a>>, TokenizeError> {
+    let continued = continued_token_len(slice.as_bytes(),
+                                        state.unwrap_or(TokenizerState::Initial));
+    let mut spanned = Vec::new();
+    if continued > 0 {
+        let text = &slice[..continued];
+        let token = match state {
+            Some(TokenizerState::LineComment) => Token::LineComment(text),
+            Some(TokenizerState::BlockComment) => Token::BlockComment(text),
+            _ => Token::StringLiteral(text),
+        };
+        spanned.push(Spanned {
+            token: token,
+            span: base_offset..base_offset + continued,
+        });
+    }
+
+    let mut offset = base_offset + continued;
+    let rest = try!(tokenize_with_options(&slice[continued..], options)
+                        .map_err(|error| error.shifted(offset)));
+    for token in rest {
+        let token = match token {
+            Token::Shebang(s) if options.demote_late_shebang && offset > 0 => {
+                Token::LineComment(s)
+            }
+            token => token,
+        };
+        let start = offset;
+        offset += token.as_str().len();
+        spanned.push(Spanned {
+            token: token,
+            span: start..offset,
+        });
+    }
+    Ok(spanned)
+}
+
+fn contains_line_break(tokens: &[Token]) -> bool {
+    tokens.iter().any(|token| {
+        match *token {
+            Token::Whitespace(s) | Token::BlockComment(s) | Token::Annotation(s) => {
+                s.contains('\n') || s.contains('\r')
+            }
+            _ => false,
+        }
+    })
+}
+
+// Whether a statement can start after `tokens`: at the start of the input,
+// after `;`, `{` or `}`, or on a new line.
+fn at_statement_start(tokens: &[Token]) -> bool {
+    let last = tokens.iter().rposition(|token| !token.is_greyspace());
+    match last.map(|last| &tokens[last]) {
+        None | Some(&Token::Semicolon) | Some(&Token::LeftBrace) | Some(&Token::RightBrace) => true,
+        _ => contains_line_break(&tokens[last.unwrap() + 1..]),
+    }
+}
+
+// Whether the `using` ending at `end` starts a declaration: it's followed by
+// a binding name on the same line and either starts a statement or follows
+// an `await` that does. That `await` is made a keyword.
+fn is_using_declaration(tokens: &mut [Token], input: &str, end: usize) -> bool {
+    let bytes = input.as_bytes();
+    let mut name_start = end;
+    while name_start < bytes.len() && (bytes[name_start] == b' ' || bytes[name_start] == b'\t') {
+        name_start += 1;
+    }
+    let mut name_end = name_start + id_char_len(input, name_start);
+    if name_end == name_start {
+        return false;
+    }
+    loop {
+        let id_len = id_continue_len(input, name_end);
+        if id_len == 0 {
+            break;
+        }
+        name_end += id_len;
+    }
+    if is_keyword(&input[name_start..name_end]) {
+        return false;
+    }
+
+    let last = tokens.iter().rposition(|token| !token.is_greyspace());
+    match last.map(|last| &tokens[last]) {
+        Some(&Token::Identifier("await")) => {
+            let last = last.unwrap();
+            if contains_line_break(&tokens[last + 1..]) || !at_statement_start(&tokens[..last]) {
+                return false;
+            }
+            tokens[last] = Token::Keyword("await");
+            true
+        }
+        _ => at_statement_start(tokens),
+    }
+}
+
+pub fn tokenize_with_options<'a>(input: &'a str,
+                                 options: &TokenizeOptions)
+                                 -> Result<Vec<Token<'a>>, TokenizeError> {
+    let on_unknown = &mut |_, offset| default_unknown_action(input, offset, options.lenient);
+    tokenize_checked(input, options, &mut || true, on_unknown)
+        .map(|tokens| tokens.expect("tokenizing without a budget is never cancelled"))
+}
+
+/// Tokenizes like `tokenize`, keeping up to 32 tokens inline. Callers holding
+/// on to the tokens of many short inputs, such as attribute values, then
+/// avoid a heap allocation for each.
+#[cfg(feature = "smallvec")]
+pub fn tokenize_small(input: &str) -> SmallVec<[Token; 32]> {
+    tokenize_small_with_options(input, &unlimited_options())
+}
+
+#[cfg(feature = "smallvec")]
+fn tokenize_small_with_options<'a>(input: &'a str,
+                                   options: &TokenizeOptions)
+                                   -> SmallVec<[Token<'a>; 32]> {
+    let mut tokens = SmallVec::new();
+    let on_unknown = &mut |_, offset| default_unknown_action(input, offset, true);
+    match tokenize_into(input, &mut tokens, options, &mut || true, on_unknown) {
+        Ok(_) => tokens,
+        Err(err) => unreachable!("lenient tokenize cannot fail: {}", err),
+    }
+}
+
+// Whether `token`, the last of its input, is a literal or block comment
+// missing its closing delimiter. Appending the delimiter extends such a
+// literal, while a closed one ends before it.
+fn is_unterminated(token: &Token) -> bool {
+    match *token {
+        Token::BlockComment(s) | Token::Annotation(s) => s.len() < 4 || !s.ends_with("*/"),
+        Token::StringLiteral(s) => {
+            let bytes = [s.as_bytes(), &s.as_bytes()[..1]].concat();
+            find_string_literal(&bytes, 0, bytes[0]) > s.len()
+        }
+        Token::TemplateLiteral(s) => {
+            let bytes = [s.as_bytes(), b"`"].concat();
+            find_template_string_literal(&bytes, 0, usize::MAX, true, None)
+                .map_or(true, |end| end > s.len())
+        }
+        Token::RegexLiteral(s) => {
+            let bytes = [s.as_bytes(), b"/"].concat();
+            find_regex_body(&bytes, 0).0 > s.len()
+        }
+        _ => false,
+    }
+}
+
+// Whether `tokens` end inside a literal or block comment left open.
+fn ends_unterminated(tokens: &[Token]) -> bool {
+    tokens.iter().rev().find(|token| !token.as_str().is_empty()).map_or(false, is_unterminated)
+}
+
+/// Tokenizes `input` with `TokenizeOptions::repl`, also returning whether it
+/// ends inside a string, template, regex or block comment that is still
+/// open, as when the user is in the middle of typing one. That token runs to
+/// the end of the input.
+pub fn tokenize_repl(input: &str) -> (Vec<Token>, bool) {
+    let tokens = tokenize_with_options(input, &TokenizeOptions::repl())
+                     .expect("lenient tokenize cannot fail");
+    let unterminated = ends_unterminated(&tokens);
+    (tokens, unterminated)
+}
+
+/// Tokenizes like `tokenize_repl`, keeping up to 32 tokens inline as
+/// `tokenize_small` does.
+#[cfg(feature = "smallvec")]
+pub fn tokenize_repl_small(input: &str) -> (SmallVec<[Token; 32]>, bool) {
+    let tokens = tokenize_small_with_options(input, &TokenizeOptions::repl());
+    let unterminated = ends_unterminated(&tokens);
+    (tokens, unterminated)
+}
+
+// The first line terminator in `s`, with `\r\n` counting as one.
+fn first_line_terminator(s: &str) -> Option<&str> {
+    s.char_indices()
+     .find(|&(_, c)| c == '\n' || c == '\r' || c == '\u{2028}' || c == '\u{2029}')
+     .map(|(index, c)| {
+         if s[index..].starts_with("\r\n") {
+             &s[index..index + 2]
+         } else {
+             &s[index..index + c.len_utf8()]
+         }
+     })
+}
+
+/// Tokenizes like `tokenize`, dropping what a minifier would: comments other
+/// than license comments and the shebang, and whitespace. Whitespace or
+/// comments containing a line break are replaced by a single line
+/// terminator, since automatic semicolon insertion depends on it, and a
+/// single space is kept between tokens that would otherwise run together.
+pub fn tokenize_minify(input: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    for token in tokenize(input) {
+        let line_terminator = match token {
+            Token::Whitespace(s) | Token::LineComment(s) | Token::BlockComment(s)
+                if !token.is_license_comment() => first_line_terminator(s),
+            _ => {
+                let needs_space = match tokens.last() {
+                    Some(&Token::Whitespace(_)) | None => false,
+                    Some(prev) => needs_separating_space(prev, &token),
+                };
+                if needs_space {
+                    tokens.push(Token::Whitespace(" "));
+                }
+                tokens.push(token);
+                continue;
+            }
+        };
+        let after_line_break = match tokens.last() {
+            Some(&Token::Whitespace(_)) => true,
+            _ => false,
+        };
+        if let Some(line_terminator) = line_terminator {
+            if !after_line_break {
+                tokens.push(Token::Whitespace(line_terminator));
+            }
+        }
+    }
+    tokens
+}
+
+/// Tokenizes each `(name, source)` pair of `files`, stopping at the first
+/// error, which is reported along with the name of its file.
+pub fn tokenize_files<'a>(files: &[(&'a str, &'a str)],
+                          options: &TokenizeOptions)
+                          -> Result<Vec<Vec<Token<'a>>>, FileTokenizeError<'a>> {
+    files.iter()
+         .map(|&(file, input)| {
+             tokenize_with_options(input, options)
+                 .map_err(|error| FileTokenizeError::new(Some(file), input, error))
+         })
+         .collect()
+}
+
+/// Tokenizes like `tokenize`, but hands each character that starts no
+/// token to `on_unknown`, along with its offset, instead of producing
+/// `Token::Unknown`. This lets embedded languages add their own tokens, such
+/// as `@` directives. Skipped or emitted text ends at the next whitespace at
+/// the latest.
+pub fn tokenize_with_handler<'a, F>(input: &'a str,
+                                    on_unknown: &mut F)
+                                    -> Result<Vec<Token<'a>>, TokenizeError>
+    where F: FnMut(u8, usize) -> UnknownAction<'a>
+{
+    tokenize_checked(input, &unlimited_options(), &mut || true, on_unknown)
+        .map(|tokens| tokens.expect("tokenizing without a budget is never cancelled"))
+}
+
+// Whether the byte at `index` can start the name of a JSX tag after a `<`,
+// including the `/` of a closing tag and the `>` of a fragment.
+fn is_jsx_tag_start(bytes: &[u8], index: usize) -> bool {
+    match bytes.get(index) {
+        Some(&b) => {
+            (b as char).is_alphabetic() || b == b'_' || b == b'$' || b == b'/' || b == b'>'
+        }
+        None => false,
+    }
+}
+
+// Whether the block comment `comment` holds nothing but one of the
+// annotations registered in `options`.
+fn is_annotation(comment: &str, options: &TokenizeOptions) -> bool {
+    let text = Token::BlockComment(comment).comment_text().unwrap_or("").trim();
+    options.annotation_comments.iter().any(|name| name == text)
+}
+
+// Fails if one of `tokens`, which are slices of `input`, has more than `max`
+// bytes. Whitespace may be of any length.
+fn check_token_lengths(input: &str, tokens: &[Token], max: usize) -> Result<(), TokenizeError> {
+    let too_long = |token: &&Token| {
+        match **token {
+            Token::Whitespace(_) => false,
+            _ => token.as_str().len() > max,
+        }
+    };
+    match tokens.iter().find(too_long) {
+        Some(token) => {
+            let offset = token.as_str().as_ptr() as usize - input.as_ptr() as usize;
+            Err(TokenizeError::TokenTooLong { offset: offset })
+        }
+        None => Ok(()),
+    }
+}
+
+// A growable sequence of tokens the tokenizer fills and looks back over, so
+// that callers wanting a `SmallVec` needn't collect a `Vec` into one.
+trait TokenSink<'a>: DerefMut<Target = [Token<'a>]> {
+    fn push(&mut self, token: Token<'a>);
+}
+
+impl<'a> TokenSink<'a> for Vec<Token<'a>> {
+    fn push(&mut self, token: Token<'a>) {
+        Vec::push(self, token);
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<'a, A: Array<Item = Token<'a>>> TokenSink<'a> for SmallVec<A> {
+    fn push(&mut self, token: Token<'a>) {
+        SmallVec::push(self, token);
+    }
+}
+
+// Tokenizes `input`, returning `None` once `budget` returns false. It's
+// called every `BUDGET_INTERVAL` tokens. Characters that start no token are
+// handed to `on_unknown`.
+fn tokenize_checked<'a>(input: &'a str,
+                        options: &TokenizeOptions,
+                        budget: &mut FnMut() -> bool,
+                        on_unknown: &mut FnMut(u8, usize) -> UnknownAction<'a>)
+                        -> Result<Option<Vec<Token<'a>>>, TokenizeError> {
     let mut tokens: Vec<Token> = Vec::with_capacity(4096 / mem::size_of::<Token>() + 1);
+    if !try!(tokenize_into(input, &mut tokens, options, budget, on_unknown)) {
+        return Ok(None);
+    }
+
+    if options.emit_statement_boundaries {
+        tokens = mark_statement_ends(with_spans(tokens))
+                     .into_iter()
+                     .map(|spanned| spanned.token)
+                     .collect();
+    }
+    Ok(Some(tokens))
+}
+
+// Tokenizes `input` onto `tokens` like `tokenize_checked`, returning false
+// if cancelled. Statement boundaries are left to the caller.
+#[allow(cyclomatic_complexity)]
+fn tokenize_into<'a, S>(input: &'a str,
+                        tokens: &mut S,
+                        options: &TokenizeOptions,
+                        budget: &mut FnMut() -> bool,
+                        on_unknown: &mut FnMut(u8, usize) -> UnknownAction<'a>)
+                        -> Result<bool, TokenizeError>
+    where S: TokenSink<'a>
+{
     let bytes = input.as_bytes();
 
     let mut start_index = 0;
@@ -337,16 +1959,45 @@ pub fn tokenize(input: &str) -> Vec<Token> {
         let content = as_str(&bytes[start_index..nearest_newline]);
         tokens.push(Token::Shebang(content));
         start_index += content.len();
+        if options.max_token_len.map_or(false, |max| content.len() > max) {
+            return Err(TokenizeError::TokenTooLong { offset: 0 });
+        }
     }
 
     let mut state = TokenizerType::Whitespace;
     let mut last_broke_at_index = start_index;
     let mut is_possible_expression = true;
+    let mut jsx: Vec<JsxContext> = Vec::new();
+    let mut next_budget_check = BUDGET_INTERVAL;
     while start_index < bytes.len() {
+        if tokens.len() >= next_budget_check {
+            if !budget() {
+                return Ok(false);
+            }
+            next_budget_check = tokens.len() + BUDGET_INTERVAL;
+        }
+
+        let chunk_start = tokens.len();
         let mut end_index = start_index;
+        let jsx_context = jsx.last().cloned();
+        let in_tag = match jsx_context {
+            Some(JsxContext::Tag { .. }) => true,
+            _ => false,
+        };
 
         match bytes[start_index] {
-            b'/' if is_next(&bytes, start_index, b'/') => {
+            b if jsx_context == Some(JsxContext::Children) && b != b'{' && b != b'<' => {
+                if state == TokenizerType::Whitespace {
+                    tokens.push(Token::Whitespace(""));
+                }
+
+                state = TokenizerType::JsxText;
+                end_index = bytes[start_index..]
+                                .iter()
+                                .position(|&b| b == b'{' || b == b'<')
+                                .map_or(bytes.len(), |pos| start_index + pos);
+            }
+            b'/' if is_next(&bytes, start_index, b'/') && !in_tag => {
                 state = TokenizerType::LineComment;
 
                 match memchr::memchr(b'\n', &bytes[end_index..]) {
@@ -354,7 +2005,7 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                     None => end_index = bytes.len(),
                 };
             }
-            b'/' if is_next(&bytes, start_index, b'*') => {
+            b'/' if is_next(&bytes, start_index, b'*') && !in_tag => {
                 state = TokenizerType::BlockComment;
 
                 end_index += 1; // Since we're looking for a slash, we need to skip the one we just found
@@ -375,14 +2026,96 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                     end_index = bytes.len();
                 }
             }
-            b'/' if is_possible_expression => {
+            b'/' if options.regex && is_possible_expression && !in_tag => {
                 if state == TokenizerType::Whitespace {
                     tokens.push(Token::Whitespace(""));
                 }
 
                 state = TokenizerType::RegexLiteral;
 
-                end_index = find_regex_literal(&bytes, end_index);
+                end_index = match find_regex_literal(&bytes, end_index, options.lenient) {
+                    Ok(end_index) => end_index,
+                    Err(_) => return Err(TokenizeError::UnterminatedRegex { offset: start_index }),
+                };
+            }
+            b'#' if options.demote_late_shebang && is_next(&bytes, start_index, b'!') &&
+                    start_index > 0 &&
+                    (bytes[start_index - 1] == b'\n' || bytes[start_index - 1] == b'\r') => {
+                state = TokenizerType::LineComment;
+                end_index = memchr::memchr(b'\n', &bytes[end_index..])
+                                .map_or(bytes.len(), |pos| end_index + pos);
+            }
+            // The `/` of `</>` would otherwise be taken for division or a
+            // regex, so the whole of it is tokenized on its own.
+            b'<' if options.jsx && bytes[start_index..].starts_with(b"</>") => {
+                if state == TokenizerType::Whitespace {
+                    tokens.push(Token::Whitespace(""));
+                }
+
+                state = TokenizerType::Blackspace;
+                end_index += 3;
+                if jsx_context == Some(JsxContext::Children) {
+                    jsx.pop();
+                }
+            }
+            b'<' if options.jsx && is_next(&bytes, start_index, b'>') => {
+                if state == TokenizerType::Whitespace {
+                    tokens.push(Token::Whitespace(""));
+                }
+
+                state = TokenizerType::Blackspace;
+                end_index += 2;
+                jsx.push(JsxContext::Children);
+            }
+            // A tag, always within children, and otherwise where an
+            // expression may start, as in `return <div/>`.
+            b'<' if options.jsx &&
+                    (jsx_context == Some(JsxContext::Children) ||
+                     is_possible_expression && is_jsx_tag_start(bytes, start_index + 1)) => {
+                if state == TokenizerType::Whitespace {
+                    tokens.push(Token::Whitespace(""));
+                }
+
+                state = TokenizerType::Blackspace;
+                end_index += 1;
+                let closing = is_next(&bytes, start_index, b'/');
+                if closing && jsx_context == Some(JsxContext::Children) {
+                    jsx.pop();
+                }
+                jsx.push(JsxContext::Tag { closing: closing });
+            }
+            b'>' if in_tag => {
+                if state == TokenizerType::Whitespace {
+                    tokens.push(Token::Whitespace(""));
+                }
+
+                state = TokenizerType::Blackspace;
+                end_index += 1;
+                let self_closing = is_prev(&bytes, start_index, b'/');
+                if jsx.pop() == Some(JsxContext::Tag { closing: false }) && !self_closing {
+                    jsx.push(JsxContext::Children);
+                }
+            }
+            b'{' | b'}' if jsx_context.is_some() => {
+                if state == TokenizerType::Whitespace {
+                    tokens.push(Token::Whitespace(""));
+                }
+
+                state = TokenizerType::Blackspace;
+                end_index += 1;
+                match (bytes[start_index], jsx_context) {
+                    (b'{', Some(JsxContext::Expression(braces))) => {
+                        *jsx.last_mut().unwrap() = JsxContext::Expression(braces + 1);
+                    }
+                    (b'{', _) => jsx.push(JsxContext::Expression(0)),
+                    (_, Some(JsxContext::Expression(0))) => {
+                        jsx.pop();
+                    }
+                    (_, Some(JsxContext::Expression(braces))) => {
+                        *jsx.last_mut().unwrap() = JsxContext::Expression(braces - 1);
+                    }
+                    _ => {}
+                }
             }
             b'"' | b'\'' => {
                 if state == TokenizerType::Whitespace {
@@ -399,18 +2132,30 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                 }
 
                 state = TokenizerType::TemplateLiteral;
-                end_index = find_template_string_literal(&bytes, end_index);
+                end_index = try!(find_template_string_literal(&bytes,
+                                                              end_index,
+                                                              options.max_template_depth,
+                                                              options.lenient,
+                                                              None));
             }
             _ => {
                 while end_index < bytes.len() {
                     let b = bytes[end_index];
+                    // JSX delimiters are tokenized on their own, to keep
+                    // track of where text is.
+                    let is_jsx_delimiter = options.jsx &&
+                                           (b == b'<' && is_jsx_tag_start(bytes, end_index + 1) ||
+                                            jsx_context.is_some() && (b == b'{' || b == b'}') ||
+                                            in_tag && b == b'>');
                     if last_broke_at_index != end_index &&
-                       (b == b'/' || b == b'"' || b == b'\'' || b == b'`') {
+                       (b == b'/' || b == b'"' || b == b'\'' || b == b'`' || is_jsx_delimiter) {
                         last_broke_at_index = end_index;
                         break;
                     }
 
-                    let is_whitespace = (b as char).is_whitespace();
+                    // Bytes of multi-byte characters can look like Latin-1
+                    // whitespace, so only ASCII whitespace counts.
+                    let is_whitespace = b < 0x80 && (b as char).is_whitespace();
 
                     if state.is_greyspace() != is_whitespace {
                         break;
@@ -422,22 +2167,38 @@ pub fn tokenize(input: &str) -> Vec<Token> {
         }
 
         let content = as_str(&bytes[start_index..end_index]);
-        if state == TokenizerType::Blackspace && !is_keyword(content) {
-            tokenize_blackspace(&mut tokens, content, start_index);
+        let is_keyword_chunk = (is_keyword(content) &&
+                                !(options.treat_let_as_identifier && content == "let")) ||
+                               (options.resource_management && content == "using" &&
+                                state == TokenizerType::Blackspace &&
+                                is_using_declaration(tokens, input, end_index));
+        if state == TokenizerType::Blackspace && !is_keyword_chunk {
+            try!(tokenize_blackspace(tokens, content, start_index, options, on_unknown));
         } else {
             let token = match state {
                 TokenizerType::Blackspace => Token::Keyword(content),
                 TokenizerType::Whitespace => Token::Whitespace(content),
                 TokenizerType::LineComment => Token::LineComment(content),
+                TokenizerType::BlockComment if is_annotation(content, options) => {
+                    Token::Annotation(content)
+                }
                 TokenizerType::BlockComment => Token::BlockComment(content),
                 TokenizerType::StringLiteral => Token::StringLiteral(content),
                 TokenizerType::RegexLiteral => Token::RegexLiteral(content),
                 TokenizerType::TemplateLiteral => Token::TemplateLiteral(content),
+                TokenizerType::JsxText => Token::JsxText(content),
             };
 
             tokens.push(token);
         }
 
+        // Tokens are no longer than the chunk they come from.
+        if let Some(max) = options.max_token_len {
+            if end_index - start_index > max {
+                try!(check_token_lengths(input, &tokens[chunk_start..], max));
+            }
+        }
+
         state = if state.is_greyspace() {
             TokenizerType::Blackspace
         } else {
@@ -451,11 +2212,10 @@ pub fn tokenize(input: &str) -> Vec<Token> {
         start_index = end_index;
     }
 
-    if !tokens.is_empty() && !last_item(&tokens).is_greyspace() {
+    if !tokens.is_empty() && !last_item(tokens).is_greyspace() {
         tokens.push(Token::Whitespace(""));
     }
-
-    tokens
+    Ok(true)
 }
 
 #[cfg(test)]
@@ -474,7 +2234,9 @@ mod bench {
 
     macro_rules! benchmark_tokenize_blackspace {
         ($name: ident, $toRun: expr) => (
-            _benchmark!($name, super::tokenize_blackspace(&mut Vec::new(), $toRun, 0));
+            _benchmark!($name, super::tokenize_blackspace(&mut Vec::new(), $toRun, 0,
+                                                          &TokenizeOptions::default(),
+                                                          &mut |_, _| UnknownAction::Error));
         )
     }
 
@@ -486,7 +2248,22 @@ mod bench {
 
     mod tokenize {
         use test::Bencher;
-        use super::super::tokenize;
+        use super::super::{tokenize, Token};
+
+        // A single token of about 1MB, which should tokenize in linear time.
+        macro_rules! benchmark_tokenize_long {
+            ($name: ident, $prefix: expr, $repeated: expr, $suffix: expr) => (
+                #[bench]
+                fn $name(b: &mut Bencher) {
+                    let mut input = String::from($prefix);
+                    for _ in 0..(1 << 20) {
+                        input.push_str($repeated);
+                    }
+                    input.push_str($suffix);
+                    b.iter(|| tokenize(&input));
+                }
+            );
+        }
 
         benchmark_tokenize!(shebang, "#! testing");
         benchmark_tokenize!(template_literal, "`test${test}test`");
@@ -500,16 +2277,48 @@ mod bench {
         benchmark_tokenize!(comment_block, "/* testi*/");
         benchmark_tokenize!(comment_long_block, "/* testitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitestitesti*/");
         benchmark_tokenize!(sample, include_str!("../input.js"));
+        // Owning the tokens costs an allocation per token with text on top of
+        // `sample`.
+        _benchmark!(sample_owned,
+                    tokenize(include_str!("../input.js"))
+                        .iter()
+                        .map(Token::to_owned)
+                        .collect::<Vec<_>>());
+
+        // Brackets nested 10,000 deep, which should tokenize in linear time.
+        #[bench]
+        fn nested_parens(b: &mut Bencher) {
+            let input = format!("{}x{}", "(".repeat(10000), ")".repeat(10000));
+            b.iter(|| tokenize(&input));
+        }
+
+        benchmark_tokenize_long!(long_identifier, "", "a", "");
+        benchmark_tokenize_long!(long_non_ascii_identifier, "", "\u{e9}", "");
+        benchmark_tokenize_long!(long_number, "", "1", "");
+        benchmark_tokenize_long!(long_string, "'", "a", "'");
+        benchmark_tokenize_long!(long_escaped_string, "'", "\\'", "'");
+        benchmark_tokenize_long!(long_template, "`", "a", "`");
+        benchmark_tokenize_long!(long_regex, "/", "a", "/");
+        benchmark_tokenize_long!(long_line_comment, "//", "a", "");
+        benchmark_tokenize_long!(long_block_comment, "/*", "*", "*/");
     }
 
 
     benchmark_tokenize!(tokenize_ident, "$_very_Z_complex$$ident");
     benchmark_tokenize_blackspace!(tokenize_ident_blackspace, "$_very_Z_complex$$ident");
+
+    #[cfg(feature = "smallvec")]
+    benchmark_tokenize!(tokenize_short, "a = 1");
+    #[cfg(feature = "smallvec")]
+    _benchmark!(tokenize_small_short, tokenize_small("a = 1"));
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::iter;
+    use format::to_sexpr;
+    use test_support::{random_tokens, Rng, TokenStreamBuilder};
 
     #[test]
     fn tokenize_shebang() {
@@ -518,6 +2327,30 @@ mod tests {
         assert_eq!(tokens.len(), 0);
     }
 
+    #[test]
+    fn tokenize_leading_line_comment() {
+        let mut tokens = tokenize("// license header\ncode");
+        assert_eq!(tokens.remove(0), Token::LineComment("// license header"));
+        assert_eq!(tokens.remove(0), Token::Whitespace("\n"));
+        assert_eq!(tokens.remove(0), Token::Identifier("code"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn tokenize_trailing_whitespace() {
+        assert_eq!(tokenize("a \n"),
+                   vec![Token::Whitespace(""), Token::Identifier("a"), Token::Whitespace(" \n")]);
+        assert_eq!(tokenize("a"),
+                   vec![Token::Whitespace(""), Token::Identifier("a"), Token::Whitespace("")]);
+        assert_eq!(tokenize("a;\n\n"),
+                   vec![Token::Whitespace(""),
+                        Token::Identifier("a"),
+                        Token::Whitespace(""),
+                        Token::Semicolon,
+                        Token::Whitespace("\n\n")]);
+    }
+
     #[test]
     fn tokenize_template_literal_with_expression() {
         let mut tokens = tokenize("`test${test}test`");
@@ -529,34 +2362,377 @@ mod tests {
     }
 
     #[test]
-    fn tokenize_line_comment() {
-        let mut tokens = tokenize("// test");
-        assert_eq!(tokens.remove(0), Token::LineComment("// test"));
+    fn tokenize_nested_template_literal() {
+        let mut tokens = tokenize("`a${`b${c}`}d`");
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::TemplateLiteral("`a${`b${c}`}d`"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
         assert_eq!(tokens.len(), 0);
     }
 
+    fn nested_template(depth: usize) -> String {
+        let mut input = String::new();
+        for _ in 0..depth {
+            input.push_str("`${");
+        }
+        for _ in 0..depth {
+            input.push_str("}`");
+        }
+        input
+    }
+
     #[test]
-    fn tokenize_line_comment_complex() {
-        let mut tokens = tokenize("// CSS escapes http://www.w3.org/TR/CSS21/syndata.html#escaped-characters");
-        assert_eq!(tokens.remove(0),
-                   Token::LineComment("// CSS escapes http://www.w3.org/TR/CSS21/syndata.html#escaped-characters"));
-        assert_eq!(tokens.len(), 0);
+    fn tokenize_template_nesting_limit() {
+        let options = TokenizeOptions::default();
+        let input = nested_template(options.max_template_depth + 1);
+        assert_eq!(tokenize_with_options(&input, &options),
+                   Err(TokenizeError::TemplateNestingTooDeep { offset: 3 * options.max_template_depth }));
+
+        let input = nested_template(options.max_template_depth);
+        assert_eq!(tokenize_with_options(&input, &options).unwrap().len(), 3);
     }
 
     #[test]
-    fn tokenize_empty_string() {
-        let mut tokens = tokenize("\"\"");
+    fn tokenize_template_nesting_without_limit() {
+        let input = nested_template(100_000);
+        let mut tokens = tokenize(&input);
         assert_eq!(tokens.remove(0), Token::Whitespace(""));
-        assert_eq!(tokens.remove(0), Token::StringLiteral("\"\""));
+        assert_eq!(tokens.remove(0), Token::TemplateLiteral(&input[..]));
         assert_eq!(tokens.remove(0), Token::Whitespace(""));
         assert_eq!(tokens.len(), 0);
     }
 
     #[test]
-    fn tokenize_normal_string() {
-        let mut tokens = tokenize("\"test foobar\"");
+    fn tokenize_null_byte() {
+        let mut tokens = tokenize("a\0b");
         assert_eq!(tokens.remove(0), Token::Whitespace(""));
-        assert_eq!(tokens.remove(0), Token::StringLiteral("\"test foobar\""));
+        assert_eq!(tokens.remove(0), Token::Identifier("a"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::Unknown("\0"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::Identifier("b"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.len(), 0);
+
+        assert_eq!(tokenize_with_options("a\0b", &TokenizeOptions::default()),
+                   Err(TokenizeError::UnexpectedChar { offset: 1 }));
+    }
+
+    #[test]
+    fn tokenize_number_followed_by_identifier() {
+        let mut tokens = tokenize("3abc");
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::NumericLiteral("3"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::Identifier("abc"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.len(), 0);
+
+        let options = TokenizeOptions::default();
+        assert_eq!(tokenize_with_options("3abc", &options),
+                   Err(TokenizeError::InvalidNumericLiteral { offset: 0 }));
+        assert_eq!(tokenize_with_options("x = 0x1g", &options),
+                   Err(TokenizeError::InvalidNumericLiteral { offset: 4 }));
+        assert_eq!(tokenize_with_options("3 abc", &options).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn tokenize_bigint_literals() {
+        let options = TokenizeOptions::default();
+        for input in &["1n", "0n", "0x1fn", "0o7n", "0b1n", "1_000n"] {
+            let tokens = tokenize_with_options(input, &options).unwrap();
+            assert_eq!(tokens[1], Token::NumericLiteral(input), "{}", input);
+        }
+        assert_eq!(tokenize_with_options("x = 1n", &options).unwrap()[5],
+                   Token::NumericLiteral("1n"));
+        for input in &["1.5n", "1e3n", "01n", "0xn"] {
+            assert!(tokenize_with_options(input, &options).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn tokenize_numeric_separators() {
+        assert_eq!(to_sexpr(&tokenize("1_0 0x1_0 1_000.0_1e1_0")),
+                   r#"(num "1_0") (num "0x1_0") (num "1_000.0_1e1_0")"#);
+        assert_eq!(to_sexpr(&tokenize("_1")), r#"(ident "_1")"#);
+        assert_eq!(to_sexpr(&tokenize("1_")), r#"(num "1") (ident "_")"#);
+        assert_eq!(to_sexpr(&tokenize("1__0")), r#"(num "1") (ident "__0")"#);
+        assert_eq!(to_sexpr(&tokenize("0_1")), r#"(num "0") (ident "_1")"#);
+        assert_eq!(to_sexpr(&tokenize("0x_1")), r#"(num "0x") (ident "_1")"#);
+        assert_eq!(to_sexpr(&tokenize("0b_1")), r#"(num "0b") (ident "_1")"#);
+
+        let options = TokenizeOptions::default();
+        assert!(tokenize_with_options("1_0 + 0x1_0", &options).is_ok());
+        for input in &["1_", "0_1", "0x_1", "0b_1", "1._5", "1__0", "1_.5", "1_e5"] {
+            assert_eq!(tokenize_with_options(input, &options),
+                       Err(TokenizeError::InvalidNumericLiteral { offset: 0 }),
+                       "{}",
+                       input);
+        }
+    }
+
+    #[test]
+    fn numeric_separators_valid() {
+        for literal in &["1_000", "0xFF_FF", "1_0.5_5e1_0", "0b1_0", "0o7_7", "1e+1_0", "0",
+                         ".5_5", "0.1_2", "1."] {
+            assert_eq!(validate_numeric_separators(literal), Ok(()), "{}", literal);
+        }
+    }
+
+    #[test]
+    fn numeric_separators_invalid() {
+        let check = validate_numeric_separators;
+        assert_eq!(check("1__0"), Err(SeparatorError::Doubled { offset: 1 }));
+        assert_eq!(check("0x_1"), Err(SeparatorError::Leading { offset: 2 }));
+        assert_eq!(check("1_.5"), Err(SeparatorError::Trailing { offset: 1 }));
+        assert_eq!(check("1._5"), Err(SeparatorError::Leading { offset: 2 }));
+        assert_eq!(check("1_e5"), Err(SeparatorError::Trailing { offset: 1 }));
+        assert_eq!(check("1e_5"), Err(SeparatorError::Leading { offset: 2 }));
+        assert_eq!(check("1e-5_"), Err(SeparatorError::Trailing { offset: 4 }));
+        assert_eq!(check("0_1"), Err(SeparatorError::AfterLeadingZero { offset: 1 }));
+    }
+
+    #[test]
+    fn tokenize_number_member_access() {
+        // The first `.` after an integer is its decimal point.
+        assert_eq!(to_sexpr(&tokenize("1.toString")), r#"(num "1.") (ident "toString")"#);
+        assert_eq!(to_sexpr(&tokenize("1..toString")), r#"(num "1.") (dot) (ident "toString")"#);
+        assert_eq!(to_sexpr(&tokenize("1.0.toString")),
+                   r#"(num "1.0") (dot) (ident "toString")"#);
+
+        let options = TokenizeOptions::default();
+        assert_eq!(tokenize_with_options("1.toString", &options),
+                   Err(TokenizeError::InvalidNumericLiteral { offset: 0 }));
+        assert!(tokenize_with_options("1..toString", &options).is_ok());
+        assert!(tokenize_with_options("1.0.toString", &options).is_ok());
+    }
+
+    #[test]
+    fn tokenize_handler_emits_directives() {
+        let input = "@dec class A {}";
+        let mut on_unknown = |b, offset| {
+            assert_eq!(b, b'@');
+            let len = input[offset + 1..].find(' ').unwrap() + 1;
+            UnknownAction::Emit(Token::Unknown(&input[offset..offset + len]))
+        };
+        assert_eq!(to_sexpr(&tokenize_with_handler(input, &mut on_unknown).unwrap()),
+                   r#"(unknown "@dec") (keyword "class") (ident "A") (lbrace) (rbrace)"#);
+    }
+
+    #[test]
+    fn tokenize_handler_skips_and_fails() {
+        let mut skip = |_, _| UnknownAction::Skip(100);
+        assert_eq!(to_sexpr(&tokenize_with_handler("a @b c", &mut skip).unwrap()),
+                   r#"(ident "a") (ident "c")"#);
+        let mut skip_char = |_, _| UnknownAction::Skip(0);
+        assert_eq!(to_sexpr(&tokenize_with_handler("\u{2603}x", &mut skip_char).unwrap()),
+                   r#"(ident "x")"#);
+        assert_eq!(tokenize_with_handler("a @b", &mut |_, _| UnknownAction::Error),
+                   Err(TokenizeError::UnexpectedChar { offset: 2 }));
+    }
+
+    #[test]
+    fn tokenize_files_reports_file() {
+        let files = [("lib.js", "a = 1;\n"), ("app.js", "x = 1;\r\ny = 3abc")];
+        let err = tokenize_files(&files, &TokenizeOptions::default()).unwrap_err();
+        assert_eq!(err.file, Some("app.js"));
+        assert_eq!((err.line, err.column), (2, 5));
+        assert_eq!(err.to_string(), "app.js:2:5: identifier directly after number");
+        assert_eq!(tokenize_files(&files[..1], &TokenizeOptions::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn file_error_position() {
+        let error = TokenizeError::UnexpectedChar { offset: 8 };
+        let err = FileTokenizeError::new(None, "\u{e9}\ra\n\u{2603}@", error);
+        assert_eq!(err.to_string(), "3:2: unexpected character");
+    }
+
+    #[test]
+    fn error_snippet() {
+        let options = TokenizeOptions::default();
+        let input = "x = 1;\n\ty = /a\nb/;";
+        let error = tokenize_with_options(input, &options).unwrap_err();
+        assert_eq!(format_error_snippet(input, &error), "2 | \ty = /a\n  | \t    ^");
+
+        let input = "a\r\nb\rc\nd\re\nf\ng\nh\ni\nj = `x${`";
+        let error = tokenize_with_options(input, &options).unwrap_err();
+        assert_eq!(format_error_snippet(input, &error), "10 | j = `x${`\n   |     ^");
+    }
+
+    #[test]
+    fn max_token_len() {
+        let options = TokenizeOptions { max_token_len: Some(8), ..TokenizeOptions::default() };
+        let long_identifier = format!("var {} = 1;", "a".repeat(9));
+        assert_eq!(tokenize_with_options(&long_identifier, &options),
+                   Err(TokenizeError::TokenTooLong { offset: 4 }));
+        assert_eq!(tokenize_with_options("f(a, 'abcdefghi')", &options),
+                   Err(TokenizeError::TokenTooLong { offset: 5 }));
+        assert_eq!(tokenize_with_options("x = 1 /* comment */", &options),
+                   Err(TokenizeError::TokenTooLong { offset: 6 }));
+        assert_eq!(tokenize_with_options("#!/usr/bin/node", &options),
+                   Err(TokenizeError::TokenTooLong { offset: 0 }));
+    }
+
+    #[test]
+    fn max_token_len_allows_short_tokens() {
+        let options = TokenizeOptions { max_token_len: Some(8), ..TokenizeOptions::default() };
+        let input = "function f(abcdefgh) { return abcdefgh+12345678+'abcdef'; }";
+        assert_eq!(tokenize_with_options(input, &options), Ok(tokenize(input)));
+        assert!(tokenize_with_options(&"a ".repeat(100), &options).is_ok());
+    }
+
+    #[test]
+    fn max_token_len_ignores_whitespace() {
+        let options = TokenizeOptions { max_token_len: Some(8), ..TokenizeOptions::default() };
+        for input in &["a            b", "a\n\n\n\n\n\n\n\n\n\nb"] {
+            assert_eq!(tokenize_with_options(input, &options), Ok(tokenize(input)));
+        }
+    }
+
+    #[test]
+    fn tokenize_null_byte_in_string() {
+        let mut tokens = tokenize_with_options("'a\0b'", &TokenizeOptions::default()).unwrap();
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::StringLiteral("'a\0b'"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn tokenize_long_tokens() {
+        let long: String = iter::repeat('a').take(1 << 20).collect();
+        let tokens = tokenize(&long);
+        assert_eq!(tokens,
+                   vec![Token::Whitespace(""), Token::Identifier(&long), Token::Whitespace("")]);
+
+        let string = format!("'{}'", long);
+        assert_eq!(tokenize(&string)[1], Token::StringLiteral(&string));
+        let comment = format!("/*{}*/", long);
+        assert_eq!(tokenize(&comment), vec![Token::BlockComment(&comment)]);
+    }
+
+    #[test]
+    fn tokenize_ellipsis() {
+        assert_eq!(to_sexpr(&tokenize("f(...a, ..../b/)")),
+                   concat!(r#"(ident "f") (lparen) (ellipsis) (ident "a") (comma) "#,
+                           r#"(ellipsis) (dot) (slash) (ident "b") (slash) (rparen)"#));
+        assert_eq!(to_sexpr(&tokenize("x = [.../b/g ]")),
+                   r#"(ident "x") (eq) (lbracket) (ellipsis) (regex "/b/g") (rbracket)"#);
+    }
+
+    #[test]
+    fn tokenize_private_identifier() {
+        assert_eq!(to_sexpr(&tokenize("a.#x = #y")),
+                   r##"(ident "a") (dot) (private "#x") (eq) (private "#y")"##);
+        assert_eq!(to_sexpr(&tokenize("a # b")), r##"(ident "a") (unknown "#") (ident "b")"##);
+    }
+
+    #[test]
+    fn tokenize_private_brand_check() {
+        assert_eq!(tokenize("#x in obj"),
+                   vec![Token::Whitespace(""),
+                        Token::PrivateIdentifier("#x"),
+                        Token::Whitespace(" "),
+                        Token::Keyword("in"),
+                        Token::Whitespace(" "),
+                        Token::Identifier("obj"),
+                        Token::Whitespace("")]);
+        assert_eq!(to_sexpr(&tokenize("if (#x in obj)")),
+                   concat!(r#"(keyword "if") (lparen) "#,
+                           r##"(private "#x") (keyword "in") (ident "obj") (rparen)"##));
+    }
+
+    #[test]
+    fn tokenize_non_ascii_identifier() {
+        let mut tokens = tokenize("café à");
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::Identifier("café"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(" "));
+        assert_eq!(tokens.remove(0), Token::Identifier("à"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn tokenize_unknown_multibyte_char() {
+        let mut tokens = tokenize("a→b");
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::Identifier("a"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::Unknown("→"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::Identifier("b"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn template_braces() {
+        assert!(template_braces_balanced("`${b}`"));
+        assert!(template_braces_balanced("`${ {x:1} }`"));
+        assert!(template_braces_balanced("`a\\${b`"));
+        assert!(!template_braces_balanced("`${b`"));
+        assert!(!template_braces_balanced("`${ {x:1 }`"));
+        assert!(!template_braces_balanced("`a${`b${c}`"));
+    }
+
+    #[test]
+    fn tokenize_unclosed_template_substitution() {
+        assert_eq!(tokenize_with_options("x = `a${b`", &TokenizeOptions::default()),
+                   Err(TokenizeError::UnterminatedTemplateSubstitution { offset: 4 }));
+        assert_eq!(tokenize("x = `a${b`")[5], Token::TemplateLiteral("`a${b`"));
+    }
+
+    #[test]
+    fn template_substitution_count() {
+        assert_eq!(Token::TemplateLiteral("`a${x}b${y}c`").template_substitution_count(), 2);
+        assert_eq!(Token::TemplateLiteral("`a\\${x}`").template_substitution_count(), 0);
+        assert_eq!(Token::TemplateLiteral("`${ {a:1} }`").template_substitution_count(), 1);
+        assert_eq!(Token::TemplateLiteral("`${`${a}${b}`}`").template_substitution_count(), 1);
+        assert_eq!(Token::TemplateLiteral("`${'}'}`").template_substitution_count(), 1);
+        assert_eq!(Token::StringLiteral("'${x}'").template_substitution_count(), 0);
+    }
+
+    #[test]
+    fn numeric_kinds() {
+        assert_eq!(Token::NumericLiteral("10").numeric_kind(), Some(NumericKind::Decimal));
+        assert_eq!(Token::NumericLiteral("0.5").numeric_kind(), Some(NumericKind::Decimal));
+        assert_eq!(Token::NumericLiteral("0XfF").numeric_kind(), Some(NumericKind::Hex));
+        assert_eq!(Token::NumericLiteral("0o17").numeric_kind(), Some(NumericKind::Octal));
+        assert_eq!(Token::NumericLiteral("0B10").numeric_kind(), Some(NumericKind::Binary));
+        assert_eq!(Token::Identifier("x").numeric_kind(), None);
+    }
+
+    #[test]
+    fn tokenize_line_comment() {
+        let mut tokens = tokenize("// test");
+        assert_eq!(tokens.remove(0), Token::LineComment("// test"));
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn tokenize_line_comment_complex() {
+        let mut tokens = tokenize("// CSS escapes http://www.w3.org/TR/CSS21/syndata.html#escaped-characters");
+        assert_eq!(tokens.remove(0),
+                   Token::LineComment("// CSS escapes http://www.w3.org/TR/CSS21/syndata.html#escaped-characters"));
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn tokenize_empty_string() {
+        let mut tokens = tokenize("\"\"");
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::StringLiteral("\"\""));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn tokenize_normal_string() {
+        let mut tokens = tokenize("\"test foobar\"");
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::StringLiteral("\"test foobar\""));
         assert_eq!(tokens.remove(0), Token::Whitespace(""));
         assert_eq!(tokens.len(), 0);
     }
@@ -586,6 +2762,30 @@ mod tests {
         assert_eq!(tokens.len(), 0);
     }
 
+    #[test]
+    fn tokenize_regex_escapes() {
+        for input in &["/\\u0061/", "/\\u{61}/u", "/[\\/]/", "/a\\/b/g", "/[\\]/]/", "/[/]/"] {
+            let tokens = tokenize(input);
+            assert_eq!(tokens[1], Token::RegexLiteral(input), "{}", input);
+            assert_eq!(tokens.len(), 3, "{}", input);
+        }
+    }
+
+    #[test]
+    fn tokenize_regex_line_break() {
+        let options = TokenizeOptions::default();
+        assert_eq!(tokenize_with_options("x = /a\nb/", &options),
+                   Err(TokenizeError::UnterminatedRegex { offset: 4 }));
+        assert_eq!(tokenize_with_options("x = /a\\\r/", &options),
+                   Err(TokenizeError::UnterminatedRegex { offset: 4 }));
+        assert_eq!(tokenize_with_options("/a\u{2028}/", &options),
+                   Err(TokenizeError::UnterminatedRegex { offset: 0 }));
+
+        let tokens = tokenize_with_options("/a\\nb/", &options).unwrap();
+        assert_eq!(tokens[1], Token::RegexLiteral("/a\\nb/"));
+        assert_eq!(tokenize("/a\nb/")[1], Token::RegexLiteral("/a\nb/"));
+    }
+
     #[test]
     fn tokenize_modified_regex() {
         let mut tokens = tokenize("/te st/mgi");
@@ -596,6 +2796,32 @@ mod tests {
         assert_eq!(tokens.len(), 0);
     }
 
+    #[test]
+    fn tokenize_regex_followed_by_member() {
+        let tokens: Vec<_> = tokenize("/re/.test(x)")
+                                 .into_iter()
+                                 .filter(|token| *token != Token::Whitespace(""))
+                                 .collect();
+        assert_eq!(tokens,
+                   vec![Token::RegexLiteral("/re/"),
+                        Token::Dot,
+                        Token::Identifier("test"),
+                        Token::LeftParen,
+                        Token::Identifier("x"),
+                        Token::RightParen]);
+
+        let tokens = tokenize("/re/g.exec(y)");
+        assert_eq!(tokens[1], Token::RegexLiteral("/re/g"));
+        assert_eq!(tokens[3], Token::Dot);
+        assert_eq!(tokens[5], Token::Identifier("exec"));
+
+        assert_eq!(tokenize("/re/gi"),
+                   vec![Token::Whitespace(""),
+                        Token::RegexLiteral("/re/gi"),
+                        Token::Whitespace("")]);
+        assert_eq!(tokenize("x = /a/i;")[5], Token::RegexLiteral("/a/i"));
+    }
+
     #[test]
     fn tokenize_non_quote_escape_string() {
         let mut tokens = tokenize("\"\n\"");
@@ -649,6 +2875,752 @@ mod tests {
         assert_eq!(tokens.len(), 0);
     }
 
+    #[test]
+    fn round_trip_random_tokens() {
+        for seed in 1..201 {
+            let tokens = random_tokens(&mut Rng::new(seed), 40);
+            let rendered = tokens.iter()
+                                 .map(|token| token.to_string())
+                                 .collect::<Vec<_>>()
+                                 .join(" ");
+            let retokenized: Vec<_> = tokenize(&rendered)
+                                          .into_iter()
+                                          .filter(|token| !token.is_greyspace())
+                                          .collect();
+            assert_eq!(retokenized, tokens, "seed {}: {}", seed, rendered);
+        }
+    }
+
+    #[test]
+    fn display_reproduces_input() {
+        let input = "function f(a, b) {\n\treturn a >>= b !== `x${y}` /* c */;\n}";
+        let output: String = tokenize(input).iter().map(|token| token.to_string()).collect();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn tokenize_spanned_offsets() {
+        let mut tokens = tokenize_spanned("a += 'b'");
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Whitespace(""), span: 0..0 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Identifier("a"), span: 0..1 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Whitespace(" "), span: 1..2 });
+        assert_eq!(tokens.remove(0),
+                   Spanned { token: Token::UpdateAssignment(Operator::Add), span: 2..4 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Whitespace(" "), span: 4..5 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::StringLiteral("'b'"), span: 5..8 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Whitespace(""), span: 8..8 });
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn tokenize_spanned_partially() {
+        let input = "let a = 1;\nb = /c\nd/;";
+        let (tokens, error) = tokenize_spanned_partial(input);
+        assert_eq!(error, Some(TokenizeError::UnterminatedRegex { offset: 15 }));
+        assert_eq!(tokens, tokenize_spanned(&input[..15]));
+        assert_eq!(tokens[tokens.len() - 2], Spanned { token: Token::Equal, span: 13..14 });
+
+        let (tokens, error) = tokenize_spanned_partial("x = `a${b`");
+        assert_eq!(error, Some(TokenizeError::UnterminatedTemplateSubstitution { offset: 4 }));
+        assert_eq!(tokens.iter().filter(|spanned| !spanned.token.is_greyspace()).count(), 2);
+
+        assert_eq!(tokenize_spanned_partial("a + b"), (tokenize_spanned("a + b"), None));
+    }
+
+    #[test]
+    fn tokenize_spanned_with_offset() {
+        let input = "let a = 1;\nb = 'c';";
+        let window = tokenize_spanned_offset(&input[11..], 11, None);
+        let whole: Vec<_> = tokenize_spanned(input)
+                                .into_iter()
+                                .filter(|spanned| spanned.span.start >= 11)
+                                .collect();
+        assert_eq!(window[0], Spanned { token: Token::Whitespace(""), span: 11..11 });
+        assert_eq!(&window[1..], &whole[..]);
+    }
+
+    #[test]
+    fn tokenize_spanned_offset_in_block_comment() {
+        let state = Some(TokenizerState::BlockComment);
+        let mut tokens = tokenize_spanned_offset("comment */ a", 20, state);
+        assert_eq!(tokens.remove(0),
+                   Spanned { token: Token::BlockComment("comment */"), span: 20..30 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Whitespace(" "), span: 30..31 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Identifier("a"), span: 31..32 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Whitespace(""), span: 32..32 });
+        assert_eq!(tokens.len(), 0);
+
+        let tokens = tokenize_spanned_offset("still open", 5, state);
+        assert_eq!(tokens, vec![Spanned { token: Token::BlockComment("still open"), span: 5..15 }]);
+    }
+
+    #[test]
+    fn demote_late_shebang() {
+        let options = TokenizeOptions { demote_late_shebang: true, ..TokenizeOptions::default() };
+        let tokens = tokenize_spanned_offset_with_options("#!/usr/bin/env node\na", 40, None,
+                                                          &options)
+                         .unwrap();
+        assert_eq!(tokens[0],
+                   Spanned { token: Token::LineComment("#!/usr/bin/env node"), span: 40..59 });
+        assert_eq!(tokens[2], Spanned { token: Token::Identifier("a"), span: 60..61 });
+
+        let tokens = tokenize_spanned_offset_with_options("#!node", 0, None, &options).unwrap();
+        assert_eq!(tokens[0].token, Token::Shebang("#!node"));
+        let tokens = tokenize_spanned_offset("#!node", 40, None);
+        assert_eq!(tokens[0].token, Token::Shebang("#!node"));
+    }
+
+    #[test]
+    fn demote_shebang_in_concatenation() {
+        let input = "#!/bin/node\na();\n#!/bin/node\nb();";
+        let options = TokenizeOptions { demote_late_shebang: true, ..TokenizeOptions::default() };
+        let tokens = tokenize_with_options(input, &options).unwrap();
+        assert_eq!(tokens[0], Token::Shebang("#!/bin/node"));
+        assert!(tokens.contains(&Token::LineComment("#!/bin/node")));
+        assert_eq!(tokenize_with_options(input, &TokenizeOptions::default()),
+                   Err(TokenizeError::UnexpectedChar { offset: 17 }));
+        assert_eq!(tokenize_with_options("a #!b", &options),
+                   Err(TokenizeError::UnexpectedChar { offset: 2 }));
+        assert_eq!(tokenize_spanned_offset_with_options("a #", 10, None, &options),
+                   Err(TokenizeError::UnexpectedChar { offset: 12 }));
+    }
+
+    #[test]
+    fn tokenize_spanned_offset_in_string() {
+        let state = Some(TokenizerState::StringLiteral(b'\''));
+        let mut tokens = tokenize_spanned_offset("b\\'c' + d", 3, state);
+        assert_eq!(tokens.remove(0), Spanned { token: Token::StringLiteral("b\\'c'"), span: 3..8 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Whitespace(" "), span: 8..9 });
+        assert_eq!(tokens.remove(0), Spanned { token: Token::Plus, span: 9..10 });
+    }
+
+    fn operator(input: &str) -> Token {
+        let mut tokens = tokenize(input);
+        tokens.retain(|token| !token.is_greyspace());
+        assert_eq!(tokens.len(), 3, "{:?}", tokens);
+        tokens.remove(1)
+    }
+
+    #[test]
+    fn assignment_operators() {
+        for op in &["=", "+=", "-=", "*=", "/=", "%=", "**=", "<<=", ">>=", ">>>=", "&=", "^=",
+                    "|=", "&&=", "||=", "??="] {
+            let input = format!("a {} b", op);
+            let token = operator(&input);
+            assert_eq!(token.as_str(), *op);
+            assert!(token.is_assignment_operator(), "{}", op);
+        }
+    }
+
+    #[test]
+    fn non_assignment_operators() {
+        for op in &["==", "===", "!=", "!==", "<=", ">=", "=>", "+", "&&", "||", "&", "<<"] {
+            let input = format!("a {} b", op);
+            let token = operator(&input);
+            assert_eq!(token.as_str(), *op);
+            assert!(!token.is_assignment_operator(), "{}", op);
+        }
+    }
+
+    #[test]
+    fn tokenize_regex_after_keyword_operator() {
+        assert_eq!(to_sexpr(&tokenize("x in /re/g")),
+                   r#"(ident "x") (keyword "in") (regex "/re/g")"#);
+        assert_eq!(to_sexpr(&tokenize("a instanceof /re/")),
+                   r#"(ident "a") (keyword "instanceof") (regex "/re/")"#);
+        assert_eq!(to_sexpr(&tokenize("typeof /a/")), r#"(keyword "typeof") (regex "/a/")"#);
+        assert_eq!(to_sexpr(&tokenize("a instanceof B / 2")),
+                   r#"(ident "a") (keyword "instanceof") (ident "B") (slash) (num "2")"#);
+        assert_eq!(to_sexpr(&tokenize("a.in / b")),
+                   r#"(ident "a") (dot) (ident "in") (slash) (ident "b")"#);
+    }
+
+    #[test]
+    fn tokenize_regex_after_keyword_statement() {
+        for keyword in &["return", "throw", "case", "do", "else", "new", "yield"] {
+            let input = format!("{} /re/g", keyword);
+            assert_eq!(to_sexpr(&tokenize(&input)),
+                       format!(r#"(keyword "{}") (regex "/re/g")"#, keyword));
+        }
+        assert_eq!(to_sexpr(&tokenize("await /re/")), r#"(ident "await") (regex "/re/")"#);
+        assert_eq!(to_sexpr(&tokenize("a.b / c")),
+                   r#"(ident "a") (dot) (ident "b") (slash) (ident "c")"#);
+    }
+
+    #[test]
+    fn tokenize_question_mark_forms() {
+        assert_eq!(to_sexpr(&tokenize("a ? b : c")),
+                   r#"(ident "a") (question) (ident "b") (colon) (ident "c")"#);
+        assert_eq!(to_sexpr(&tokenize("a?.b")), r#"(ident "a") (optional-chain) (ident "b")"#);
+        assert_eq!(to_sexpr(&tokenize("a ?? b")), r#"(ident "a") (nullish) (ident "b")"#);
+        // Separated by a space, `? .` is a conditional, however invalid.
+        assert_eq!(to_sexpr(&tokenize("a ? .b : c")),
+                   r#"(ident "a") (question) (dot) (ident "b") (colon) (ident "c")"#);
+        assert_eq!(to_sexpr(&tokenize("cond ? a?.b : c")),
+                   concat!(r#"(ident "cond") (question) "#,
+                           r#"(ident "a") (optional-chain) (ident "b") (colon) (ident "c")"#));
+        assert_eq!(to_sexpr(&tokenize("a?.5:b")),
+                   r#"(ident "a") (question) (num ".5") (colon) (ident "b")"#);
+        assert_eq!(to_sexpr(&tokenize("a??.5")), r#"(ident "a") (nullish) (num ".5")"#);
+        assert_eq!(to_sexpr(&tokenize("a?b??c:d")),
+                   concat!(r#"(ident "a") (question) "#,
+                           r#"(ident "b") (nullish) (ident "c") (colon) (ident "d")"#));
+    }
+
+    #[test]
+    fn tokenize_async_arrow() {
+        // `async` and `await` are contextual, so they stay identifiers.
+        assert_eq!(to_sexpr(&tokenize("async x => await x")),
+                   r#"(ident "async") (ident "x") (arrow) (ident "await") (ident "x")"#);
+        assert_eq!(to_sexpr(&tokenize("async x => x / 2")),
+                   r#"(ident "async") (ident "x") (arrow) (ident "x") (slash) (num "2")"#);
+        assert_eq!(to_sexpr(&tokenize("x => /re/")), r#"(ident "x") (arrow) (regex "/re/")"#);
+        assert_eq!(to_sexpr(&tokenize("(x=>/re/g)")),
+                   r#"(lparen) (ident "x") (arrow) (regex "/re/g") (rparen)"#);
+    }
+
+    #[test]
+    fn tokenize_regex_starting_with_equal() {
+        // Where an expression is expected `/=` can only begin a regex.
+        assert_eq!(to_sexpr(&tokenize("a = /=x/")),
+                   r#"(ident "a") (eq) (regex "/=x/")"#);
+        assert_eq!(to_sexpr(&tokenize("f(/=/g)")),
+                   r#"(ident "f") (lparen) (regex "/=/g") (rparen)"#);
+        assert_eq!(to_sexpr(&tokenize("a /= b")),
+                   r#"(ident "a") (update "/=") (ident "b")"#);
+        // No semicolon is inserted before `/=`, so it divides `x`.
+        assert_eq!(to_sexpr(&tokenize("x\n/=2/")),
+                   r#"(ident "x") (update "/=") (num "2") (slash)"#);
+    }
+
+    #[test]
+    fn tokenize_division_after_template() {
+        assert!(!Token::TemplateLiteral("`x`").before_expression());
+
+        let expected = TokenStreamBuilder::new()
+                           .ws("").template("`x`").ws(" ")
+                           .token(Token::Slash).ws(" ")
+                           .ident("y").ws("")
+                           .build();
+        assert_eq!(tokenize("`x` / y"), expected);
+
+        let tokens = tokenize("f(`x`) / y");
+        assert_eq!(tokens[7], Token::RightParen);
+        assert_eq!(tokens[9], Token::Slash);
+    }
+
+    #[test]
+    fn option_presets() {
+        assert_eq!(TokenizeOptions::strict(), TokenizeOptions::default());
+        assert!(!TokenizeOptions::strict().lenient);
+        assert!(!TokenizeOptions::json().regex);
+        assert_eq!(TokenizeOptions::minified().max_template_depth, usize::MAX);
+        assert!(TokenizeOptions::jsx().jsx);
+        assert!(TokenizeOptions::typescript().type_annotations);
+    }
+
+    #[test]
+    fn tokenize_type_arguments() {
+        let input = "a: A<B<C>>";
+        let tokens = tokenize_with_options(input, &TokenizeOptions::typescript()).unwrap();
+        assert_eq!(tokens, tokenize(input));
+        assert_eq!(tokens[tokens.len() - 2], Token::BitShift(Operator::RightShift));
+    }
+
+    #[test]
+    fn tokenize_shifts_with_type_annotations() {
+        let input = "x = a >> 2; y = b >>> 1; z >>= 3;";
+        let tokens = tokenize_with_options(input, &TokenizeOptions::typescript()).unwrap();
+        assert_eq!(tokens, tokenize(input));
+        assert_eq!(tokens[7], Token::BitShift(Operator::RightShift));
+        assert_eq!(tokens[19], Token::BitShift(Operator::UnsignedRightShift));
+        assert!(tokens.contains(&Token::UpdateAssignment(Operator::RightShift)));
+    }
+
+    #[test]
+    fn tokenize_without_regex() {
+        let tokens = tokenize_with_options("[/a/ ]", &TokenizeOptions::json()).unwrap();
+        assert_eq!(tokens[3], Token::Slash);
+        assert_eq!(tokens[5], Token::Identifier("a"));
+        assert_eq!(tokens[7], Token::Slash);
+
+        let tokens = tokenize_with_options("[/a/ ]", &TokenizeOptions::default()).unwrap();
+        assert_eq!(tokens[3], Token::RegexLiteral("/a/"));
+    }
+
+    fn let_token(input: &str, treat_let_as_identifier: bool) -> Token {
+        let options = TokenizeOptions {
+            treat_let_as_identifier: treat_let_as_identifier,
+            ..TokenizeOptions::default()
+        };
+        tokenize_with_options(input, &options).unwrap().remove(1)
+    }
+
+    #[test]
+    fn tokenize_let_declaration() {
+        assert_eq!(let_token("let [a] = x", false), Token::Keyword("let"));
+        assert_eq!(let_token("let {a} = x", false), Token::Keyword("let"));
+        assert_eq!(let_token("let[a] = x", false), Token::Keyword("let"));
+        assert_eq!(let_token("let{a} = x", false), Token::Keyword("let"));
+    }
+
+    #[test]
+    fn tokenize_let_as_identifier() {
+        assert_eq!(let_token("let.foo", false), Token::Identifier("let"));
+        assert_eq!(let_token("let [a] = x", true), Token::Identifier("let"));
+        assert_eq!(let_token("let[a] = x", true), Token::Identifier("let"));
+        assert_eq!(let_token("let.foo", true), Token::Identifier("let"));
+    }
+
+    fn with_resource_management(input: &str, resource_management: bool) -> Vec<Token> {
+        let options = TokenizeOptions {
+            resource_management: resource_management,
+            ..TokenizeOptions::default()
+        };
+        tokenize_with_options(input, &options).unwrap()
+    }
+
+    #[test]
+    fn tokenize_using_declaration() {
+        let tokens = with_resource_management("using x = getResource()", true);
+        assert_eq!(tokens[1], Token::Keyword("using"));
+
+        let tokens = with_resource_management("async function f() {\n  await using y = g()\n}",
+                                              true);
+        assert_eq!(&tokens[13..17],
+                   &[Token::Keyword("await"),
+                     Token::Whitespace(" "),
+                     Token::Keyword("using"),
+                     Token::Whitespace(" ")]);
+    }
+
+    #[test]
+    fn tokenize_using_as_identifier() {
+        assert_eq!(with_resource_management("using x = getResource()", false)[1],
+                   Token::Identifier("using"));
+        assert_eq!(with_resource_management("using = 1", true)[1], Token::Identifier("using"));
+        assert_eq!(with_resource_management("a = using in b", true)[5],
+                   Token::Identifier("using"));
+        assert_eq!(with_resource_management("x = await using y", true)[5],
+                   Token::Identifier("await"));
+    }
+
+    #[test]
+    fn tokenize_optional_catch_binding() {
+        let expected = TokenStreamBuilder::new()
+                           .ws("").keyword("try").ws(" ")
+                           .lbrace().ws("").rbrace().ws(" ")
+                           .keyword("catch").ws(" ")
+                           .lbrace().ws("").rbrace().ws("")
+                           .build();
+        assert_eq!(tokenize("try {} catch {}"), expected);
+    }
+
+    #[test]
+    fn tokenize_catch_binding() {
+        assert_eq!(to_sexpr(&tokenize("try {} catch (e) {}")),
+                   concat!(r#"(keyword "try") (lbrace) (rbrace) "#,
+                           r#"(keyword "catch") (lparen) (ident "e") (rparen) (lbrace) (rbrace)"#));
+    }
+
+    #[test]
+    fn tokenize_else() {
+        assert_eq!(to_sexpr(&tokenize("if (x) {} else {}")),
+                   concat!(r#"(keyword "if") (lparen) (ident "x") (rparen) (lbrace) (rbrace) "#,
+                           r#"(keyword "else") (lbrace) (rbrace)"#));
+    }
+
+    #[test]
+    fn keyword_classification() {
+        let reserved = ["break", "case", "catch", "class", "const", "continue", "debugger",
+                        "default", "delete", "do", "else", "enum", "export", "extends", "false",
+                        "finally", "for", "function", "if", "import", "in", "instanceof", "new",
+                        "null", "return", "super", "switch", "this", "throw", "true", "try",
+                        "typeof", "var", "void", "while", "with", "let", "yield"];
+        for word in &reserved {
+            assert!(is_keyword(word), "{}", word);
+            assert!(!is_contextual_keyword(word), "{}", word);
+        }
+
+        let contextual = ["async", "await", "of", "get", "set", "static", "as", "satisfies",
+                          "from", "target", "meta", "implements", "interface", "package",
+                          "private", "protected", "public"];
+        for word in &contextual {
+            assert!(!is_keyword(word), "{}", word);
+            assert!(is_contextual_keyword(word), "{}", word);
+        }
+
+        let identifiers = ["undefined", "NaN", "arguments", "eval", "window", "foo", "If",
+                           "classes", "letter", "getter", "asynchronous"];
+        for word in &identifiers {
+            assert!(!is_keyword(word), "{}", word);
+            assert!(!is_contextual_keyword(word), "{}", word);
+        }
+    }
+
+    #[test]
+    fn tokenize_undefined_as_identifier() {
+        let mut tokens = tokenize("undefined");
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.remove(0), Token::Identifier("undefined"));
+        assert_eq!(tokens.remove(0), Token::Whitespace(""));
+        assert_eq!(tokens.len(), 0);
+    }
+
+    #[test]
+    fn blank_line_count() {
+        assert_eq!(Token::Whitespace("\n\n\n").blank_line_count(), 2);
+        assert_eq!(Token::Whitespace("\n").blank_line_count(), 0);
+        assert_eq!(Token::Whitespace("  \n  \n  ").blank_line_count(), 1);
+        assert_eq!(Token::Whitespace("\r\n\r\n").blank_line_count(), 1);
+        assert_eq!(Token::Whitespace("  ").blank_line_count(), 0);
+        assert_eq!(Token::LineComment("//\n\n").blank_line_count(), 0);
+    }
+
+    fn many_tokens() -> String {
+        let mut input = String::new();
+        for _ in 0..1000 {
+            input.push_str("a; ");
+        }
+        input
+    }
+
+    #[test]
+    fn tokenize_cancelled() {
+        let input = many_tokens();
+        let mut calls = 0;
+        let tokens = tokenize_with_budget(&input, &mut || {
+            calls += 1;
+            calls < 5
+        });
+        assert_eq!(tokens, None);
+        assert_eq!(calls, 5);
+    }
+
+    #[test]
+    fn tokenize_within_budget() {
+        let input = many_tokens();
+        let mut calls = 0;
+        let tokens = tokenize_with_budget(&input, &mut || {
+            calls += 1;
+            true
+        });
+        assert_eq!(tokens, Some(tokenize(&input)));
+        assert!(calls > 5);
+        assert_eq!(tokenize_with_budget("", &mut || false), Some(vec![]));
+    }
+
+    #[test]
+    fn same_kind() {
+        assert!(Token::Identifier("a").same_kind(&Token::Identifier("b")));
+        assert!(!Token::Identifier("a").same_kind(&Token::Keyword("b")));
+        assert!(Token::Semicolon.same_kind(&Token::Semicolon));
+        assert!(Token::Relational(Relational::Less)
+                    .same_kind(&Token::Relational(Relational::Less)));
+        assert!(!Token::Relational(Relational::Less)
+                     .same_kind(&Token::Relational(Relational::Greater)));
+    }
+
+    #[test]
+    fn token_ops_stable() {
+        let tokens = [Token::Whitespace(" "), Token::Shebang("#!"), Token::LineComment("//"),
+                      Token::BlockComment("/**/"), Token::NumericLiteral("1"),
+                      Token::StringLiteral("''"), Token::RegexLiteral("/a/"),
+                      Token::TemplateLiteral("``"), Token::Identifier("a"), Token::Keyword("if"),
+                      Token::PrivateIdentifier("#a"), Token::Unknown("@"),
+                      Token::DeIncrement(Operator::Add), Token::BitShift(Operator::LeftShift),
+                      Token::Relational(Relational::Less),
+                      Token::UpdateAssignment(Operator::Add), Token::Exponeniation, Token::Arrow,
+                      Token::Equal, Token::LogicalOr, Token::LogicalAnd,
+                      Token::NullishCoalescing, Token::OptionalChain, Token::BitwiseOr,
+                      Token::BitwiseXOR, Token::BitwiseAnd, Token::BitwiseNot, Token::Plus,
+                      Token::Minus, Token::Modulo, Token::Star, Token::Slash, Token::Semicolon,
+                      Token::LeftParen, Token::RightParen, Token::LeftBrace, Token::RightBrace,
+                      Token::LeftBracket, Token::RightBracket, Token::Dot, Token::Ellipsis,
+                      Token::Comma, Token::QuestionMark, Token::Colon, Token::ExclamationMark,
+                      Token::StatementEnd, Token::JsxFragmentStart, Token::JsxFragmentEnd,
+                      Token::Annotation("/*@a*/"), Token::JsxText("a")];
+        let ops: Vec<u8> = tokens.iter().map(|token| token.op() as u8).collect();
+        assert_eq!(ops, (0..50).collect::<Vec<u8>>());
+        assert_eq!(Token::Identifier("b").op(), TokenOp::Identifier);
+        assert_eq!(TokenOp::Semicolon as u8, 32);
+        assert_eq!(Token::DeIncrement(Operator::Subtract).op(), TokenOp::DeIncrement);
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn tokenize_small_matches_tokenize() {
+        let tokens = tokenize_small("a = 1");
+        assert_eq!(&tokens[..], &tokenize("a = 1")[..]);
+        assert!(!tokens.spilled());
+
+        let input = "function f(a, b) { return a + b * 2; }";
+        let tokens = tokenize_small(input);
+        assert_eq!(&tokens[..], &tokenize(input)[..]);
+        assert!(tokens.spilled());
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn tokenize_repl_small_is_inline() {
+        let (tokens, unterminated) = tokenize_repl_small("x = \"ab");
+        assert_eq!(&tokens[..], &tokenize("x = \"ab")[..]);
+        assert!(unterminated);
+        assert!(!tokens.spilled());
+    }
+
+    #[test]
+    fn tokenize_repl_incomplete_input() {
+        assert_eq!(tokenize_repl("\"abc"), (tokenize("\"abc"), true));
+        assert_eq!(tokenize_repl("\"abc").0[1], Token::StringLiteral("\"abc"));
+        assert_eq!(tokenize_repl("\"abc\""), (tokenize("\"abc\""), false));
+        assert_eq!(tokenize_repl("'a\\'").1, true);
+        assert_eq!(tokenize_repl("a +"), (tokenize("a +"), false));
+        assert_eq!(tokenize_repl("x = `a${b").1, true);
+        assert_eq!(tokenize_repl("x = `a${`b`").1, true);
+        assert_eq!(tokenize_repl("x = `a${b}`").1, false);
+        assert_eq!(tokenize_repl("x = /[/").1, true);
+        assert_eq!(tokenize_repl("x = /a\\/").1, true);
+        assert_eq!(tokenize_repl("x = /a/g").1, false);
+        assert_eq!(tokenize_repl("/* a").1, true);
+        assert_eq!(tokenize_repl("/*/").1, true);
+        assert_eq!(tokenize_repl("// a").1, false);
+        assert_eq!(tokenize_repl("a # b"), (tokenize("a # b"), false));
+    }
+
+    #[test]
+    fn spanned_debug() {
+        let tokens = tokenize_spanned("if (a)");
+        assert_eq!(format!("{:?}", tokens[1]), "Keyword(\"if\")@0..2");
+        assert_eq!(format!("{:?}", tokens[5]), "Identifier(\"a\")@4..5");
+        assert_eq!(format!("{:?}", tokens[7]), "RightParen@5..6");
+    }
+
+    #[test]
+    fn minify_keeps_license_comments() {
+        let tokens = tokenize_minify("/*! (c) 2024 */\nvar a = 1; /* internal */ // x\n\n b()");
+        assert_eq!(tokens,
+                   TokenStreamBuilder::new()
+                       .token(Token::BlockComment("/*! (c) 2024 */"))
+                       .ws("\n")
+                       .keyword("var")
+                       .ws(" ")
+                       .ident("a")
+                       .eq()
+                       .num("1")
+                       .token(Token::Semicolon)
+                       .ws("\n")
+                       .ident("b")
+                       .token(Token::LeftParen)
+                       .token(Token::RightParen)
+                       .build());
+        assert!(Token::BlockComment("/*!*/").is_license_comment());
+        assert!(!Token::BlockComment("/* ! */").is_license_comment());
+    }
+
+    #[test]
+    fn minify_drops_comments() {
+        assert_eq!(tokenize_minify("a /* internal */ + b"),
+                   vec![Token::Identifier("a"), Token::Plus, Token::Identifier("b")]);
+        assert_eq!(tokenize_minify("a /* line\r\nbreak */ b"),
+                   vec![Token::Identifier("a"),
+                        Token::Whitespace("\r\n"),
+                        Token::Identifier("b")]);
+    }
+
+    #[test]
+    fn minify_keeps_separating_spaces() {
+        assert_eq!(tokenize_minify("var a = 1"),
+                   vec![Token::Keyword("var"),
+                        Token::Whitespace(" "),
+                        Token::Identifier("a"),
+                        Token::Equal,
+                        Token::NumericLiteral("1")]);
+        assert_eq!(tokenize_minify("a + +b"),
+                   vec![Token::Identifier("a"),
+                        Token::Plus,
+                        Token::Whitespace(" "),
+                        Token::Plus,
+                        Token::Identifier("b")]);
+        fn meaningful(input: &str) -> Vec<Token> {
+            tokenize(input).into_iter().filter(|token| !token.is_greyspace()).collect()
+        }
+        for input in &["var a = 1", "a + +b", "a - -b", "return typeof x", "x = a /* c */ in b",
+                       "1 .toString()", "a = b\nc = d"] {
+            let minified: String = tokenize_minify(input).iter().map(Token::as_str).collect();
+            assert_eq!(meaningful(&minified), meaningful(input));
+        }
+    }
+
+    #[test]
+    fn minify_avoids_html_like_comments() {
+        let minify = |input| tokenize_minify(input).iter().map(Token::as_str).collect::<String>();
+        assert_eq!(minify("x-- > y"), "x-- >y");
+        assert_eq!(minify("a < !--b"), "a< !--b");
+    }
+
+    #[test]
+    fn owned_tokens_match_borrowed() {
+        let tokens = tokenize(include_str!("../input.js"));
+        let owned: Vec<OwnedToken> = tokens.iter().map(Token::to_owned).collect();
+        assert_eq!(owned.len(), tokens.len());
+        for (owned, token) in owned.iter().zip(&tokens) {
+            assert_eq!(owned.as_str(), token.as_str());
+            assert_eq!(owned.op, token.op());
+        }
+    }
+
+    #[test]
+    fn emit_statement_boundaries() {
+        let options = TokenizeOptions { emit_statement_boundaries: true, ..unlimited_options() };
+        let input = "a = 1\nfor (;;) b();";
+        let tokens = tokenize_with_options(input, &options).unwrap();
+        let expected: Vec<Token> = mark_statement_ends(tokenize_spanned(input))
+                                       .into_iter()
+                                       .map(|spanned| spanned.token)
+                                       .collect();
+        assert_eq!(tokens, expected);
+        assert_eq!(tokens.iter().filter(|&token| *token == Token::StatementEnd).count(), 2);
+        assert!(!tokenize(input).contains(&Token::StatementEnd));
+    }
+
+    fn jsx_tokens(input: &str) -> Vec<Token> {
+        tokenize_with_options(input, &TokenizeOptions::jsx())
+            .unwrap()
+            .into_iter()
+            .filter(|token| !token.is_greyspace())
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_jsx_fragments() {
+        assert_eq!(jsx_tokens("<>text</>"),
+                   vec![Token::JsxFragmentStart,
+                        Token::JsxText("text"),
+                        Token::JsxFragmentEnd]);
+        assert_eq!(jsx_tokens("x = <>{x}</>;"),
+                   vec![Token::Identifier("x"),
+                        Token::Equal,
+                        Token::JsxFragmentStart,
+                        Token::LeftBrace,
+                        Token::Identifier("x"),
+                        Token::RightBrace,
+                        Token::JsxFragmentEnd,
+                        Token::Semicolon]);
+        let tokens = jsx_tokens("<><div/></>");
+        assert_eq!(tokens[0], Token::JsxFragmentStart);
+        assert_eq!(tokens[1], Token::Relational(Relational::Less));
+        assert_eq!(tokens[tokens.len() - 1], Token::JsxFragmentEnd);
+        let tokens = jsx_tokens("<><>a</> </>");
+        assert_eq!(tokens.iter().filter(|&token| *token == Token::JsxFragmentEnd).count(), 2);
+    }
+
+    #[test]
+    fn tokenize_jsx_children_as_text() {
+        assert_eq!(jsx_tokens("x = <>Don't</>;"),
+                   vec![Token::Identifier("x"),
+                        Token::Equal,
+                        Token::JsxFragmentStart,
+                        Token::JsxText("Don't"),
+                        Token::JsxFragmentEnd,
+                        Token::Semicolon]);
+        assert_eq!(jsx_tokens("<>see http://a.b</>"),
+                   vec![Token::JsxFragmentStart,
+                        Token::JsxText("see http://a.b"),
+                        Token::JsxFragmentEnd]);
+        assert_eq!(jsx_tokens("<>a {b ? {c: 1} : d} e</>"),
+                   vec![Token::JsxFragmentStart,
+                        Token::JsxText("a "),
+                        Token::LeftBrace,
+                        Token::Identifier("b"),
+                        Token::QuestionMark,
+                        Token::LeftBrace,
+                        Token::Identifier("c"),
+                        Token::Colon,
+                        Token::NumericLiteral("1"),
+                        Token::RightBrace,
+                        Token::Colon,
+                        Token::Identifier("d"),
+                        Token::RightBrace,
+                        Token::JsxText(" e"),
+                        Token::JsxFragmentEnd]);
+    }
+
+    #[test]
+    fn tokenize_jsx_elements() {
+        assert_eq!(jsx_tokens("x = <a href=\"/\">it's <b/></a>;"),
+                   vec![Token::Identifier("x"),
+                        Token::Equal,
+                        Token::Relational(Relational::Less),
+                        Token::Identifier("a"),
+                        Token::Identifier("href"),
+                        Token::Equal,
+                        Token::StringLiteral("\"/\""),
+                        Token::Relational(Relational::Greater),
+                        Token::JsxText("it's "),
+                        Token::Relational(Relational::Less),
+                        Token::Identifier("b"),
+                        Token::Slash,
+                        Token::Relational(Relational::Greater),
+                        Token::Relational(Relational::Less),
+                        Token::Slash,
+                        Token::Identifier("a"),
+                        Token::Relational(Relational::Greater),
+                        Token::Semicolon]);
+    }
+
+    #[test]
+    fn jsx_fragments_need_jsx() {
+        assert_eq!(jsx_tokens("a << b <= c < d"),
+                   vec![Token::Identifier("a"),
+                        Token::BitShift(Operator::LeftShift),
+                        Token::Identifier("b"),
+                        Token::Relational(Relational::LessOrEqual),
+                        Token::Identifier("c"),
+                        Token::Relational(Relational::Less),
+                        Token::Identifier("d")]);
+        assert!(!tokenize("<>a</>").contains(&Token::JsxFragmentStart));
+        assert!(!tokenize("<>a</>").contains(&Token::JsxFragmentEnd));
+    }
+
+    #[test]
+    fn same_shape() {
+        assert!(tokens_same_shape(&tokenize("a = 1;"), &tokenize("b  =  2;")));
+        assert!(!tokens_same_shape(&tokenize("a = 1;"), &tokenize("a = b;")));
+        assert!(!tokens_same_shape(&tokenize("a = 1;"), &tokenize("a = 1")));
+    }
+
+    #[test]
+    fn comment_text() {
+        assert_eq!(Token::LineComment("// a").comment_text(), Some(" a"));
+        assert_eq!(Token::BlockComment("/* b */").comment_text(), Some(" b "));
+        assert_eq!(Token::BlockComment("/**/").comment_text(), Some(""));
+        assert_eq!(Token::BlockComment("/* c").comment_text(), Some(" c"));
+        assert_eq!(Token::StringLiteral("'// d'").comment_text(), None);
+    }
+
+    #[test]
+    fn annotation_comments() {
+        let options = TokenizeOptions {
+            annotation_comments: vec![String::from("@__NO_SIDE_EFFECTS__"),
+                                      String::from("@__KEEP__")],
+            ..TokenizeOptions::default()
+        };
+        let input = "/* @__NO_SIDE_EFFECTS__ */ function f() {} /*@__KEEP__*/ /* @__PURE__ */";
+        let tokens = tokenize_with_options(input, &options).unwrap();
+        assert_eq!(tokens[0], Token::Annotation("/* @__NO_SIDE_EFFECTS__ */"));
+        assert_eq!(tokens[0].annotation_name(), Some("@__NO_SIDE_EFFECTS__"));
+        assert!(tokens[0].is_greyspace());
+        assert_eq!(tokens[tokens.len() - 3].annotation_name(), Some("@__KEEP__"));
+        assert_eq!(tokens[tokens.len() - 1], Token::BlockComment("/* @__PURE__ */"));
+        assert_eq!(tokens[tokens.len() - 1].annotation_name(), None);
+
+        // Annotations must stand alone in their comment.
+        let tokens = tokenize_with_options("/* @__KEEP__ because */", &options).unwrap();
+        assert_eq!(tokens, vec![Token::BlockComment("/* @__KEEP__ because */")]);
+        assert!(!tokenize("/*@__KEEP__*/").contains(&Token::Annotation("/*@__KEEP__*/")));
+    }
+
     #[test]
     fn tokenize_block_comment() {
         let mut tokens = tokenize("/* test * * * */");
@@ -0,0 +1,113 @@
+use tokenizer::{is_keyword, Spanned, Token};
+
+/// An analysis pass over spanned tokens, driven by `walk`. Each method is
+/// called with the index of the token it is given, so a visitor that keeps
+/// the token slice can look at the tokens around it. All methods do
+/// nothing by default.
+pub trait TokenVisitor<'a> {
+    /// Called for every token, before the method for its kind.
+    fn visit_token(&mut self, _index: usize, _spanned: &Spanned<'a>) {}
+
+    /// Called for identifiers that aren't reserved words.
+    fn visit_identifier(&mut self, _index: usize, _spanned: &Spanned<'a>) {}
+
+    /// Called for reserved words, whether the tokenizer made them a
+    /// `Token::Keyword` or, as when they touch punctuation, a
+    /// `Token::Identifier`.
+    fn visit_keyword(&mut self, _index: usize, _spanned: &Spanned<'a>) {}
+
+    fn visit_string(&mut self, _index: usize, _spanned: &Spanned<'a>) {}
+
+    fn visit_template(&mut self, _index: usize, _spanned: &Spanned<'a>) {}
+
+    fn visit_number(&mut self, _index: usize, _spanned: &Spanned<'a>) {}
+
+    fn visit_regex(&mut self, _index: usize, _spanned: &Spanned<'a>) {}
+
+    /// Called for line and block comments, including annotations.
+    fn visit_comment(&mut self, _index: usize, _spanned: &Spanned<'a>) {}
+}
+
+/// Calls `visitor`'s methods for each of `tokens` in order.
+pub fn walk<'a, V: TokenVisitor<'a>>(tokens: &[Spanned<'a>], visitor: &mut V) {
+    for (index, spanned) in tokens.iter().enumerate() {
+        visitor.visit_token(index, spanned);
+        match spanned.token {
+            Token::Identifier(s) if is_keyword(s) => visitor.visit_keyword(index, spanned),
+            Token::Identifier(_) => visitor.visit_identifier(index, spanned),
+            Token::Keyword(_) => visitor.visit_keyword(index, spanned),
+            Token::StringLiteral(_) => visitor.visit_string(index, spanned),
+            Token::TemplateLiteral(_) => visitor.visit_template(index, spanned),
+            Token::NumericLiteral(_) => visitor.visit_number(index, spanned),
+            Token::RegexLiteral(_) => visitor.visit_regex(index, spanned),
+            Token::LineComment(_) | Token::BlockComment(_) | Token::Annotation(_) => {
+                visitor.visit_comment(index, spanned)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::tokenize_spanned;
+
+    #[derive(Default)]
+    struct Counter {
+        tokens: usize,
+        identifiers: usize,
+        keywords: usize,
+        strings: usize,
+        templates: usize,
+        comments: usize,
+    }
+
+    impl<'a> TokenVisitor<'a> for Counter {
+        fn visit_token(&mut self, _index: usize, _spanned: &Spanned<'a>) {
+            self.tokens += 1;
+        }
+
+        fn visit_identifier(&mut self, _index: usize, _spanned: &Spanned<'a>) {
+            self.identifiers += 1;
+        }
+
+        fn visit_keyword(&mut self, _index: usize, _spanned: &Spanned<'a>) {
+            self.keywords += 1;
+        }
+
+        fn visit_string(&mut self, _index: usize, _spanned: &Spanned<'a>) {
+            self.strings += 1;
+        }
+
+        fn visit_template(&mut self, _index: usize, _spanned: &Spanned<'a>) {
+            self.templates += 1;
+        }
+
+        fn visit_comment(&mut self, _index: usize, _spanned: &Spanned<'a>) {
+            self.comments += 1;
+        }
+    }
+
+    #[test]
+    fn counting_visitor() {
+        let tokens = tokenize_spanned("// f\nfunction f(a) { return a + 'b' + `c`; } /* g */");
+        let mut counter = Counter::default();
+        walk(&tokens, &mut counter);
+        assert_eq!(counter.tokens, tokens.len());
+        assert_eq!(counter.identifiers, 3);
+        assert_eq!(counter.keywords, 2);
+        assert_eq!(counter.strings, 1);
+        assert_eq!(counter.templates, 1);
+        assert_eq!(counter.comments, 2);
+    }
+
+    #[test]
+    fn keywords_touching_punctuation() {
+        let tokens = tokenize_spanned("if(a){return}");
+        let mut counter = Counter::default();
+        walk(&tokens, &mut counter);
+        assert_eq!(counter.keywords, 2);
+        assert_eq!(counter.identifiers, 1);
+    }
+}
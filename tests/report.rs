@@ -0,0 +1,49 @@
+#![feature(plugin)]
+
+#![plugin(clippy)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+// The binary is built next to the directory holding this test executable.
+fn binary() -> PathBuf {
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.join("parsejs")
+}
+
+fn field(line: &str, name: &str) -> String {
+    let prefix = format!("{}=", name);
+    line.split(' ')
+        .find(|part| part.starts_with(&prefix))
+        .map(|part| part[prefix.len()..].to_owned())
+        .unwrap_or_else(|| panic!("no {} in {:?}", name, line))
+}
+
+#[test]
+fn report_fixtures() {
+    let output = Command::new(binary())
+                     .current_dir(env!("CARGO_MANIFEST_DIR"))
+                     .args(&["--report", "tests/fixtures/add.js", "tests/fixtures/greet.js"])
+                     .output()
+                     .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("tests/fixtures/add.js: lines=4 tokens=14 identifiers=5 hash="));
+    assert!(lines[1].starts_with("tests/fixtures/greet.js: lines=2 "));
+
+    let tokens: usize = field(lines[1], "tokens").parse().unwrap();
+    let identifiers: usize = field(lines[1], "identifiers").parse().unwrap();
+    assert!(identifiers > 0 && identifiers < tokens);
+    for line in &lines {
+        assert_eq!(field(line, "hash").len(), 16);
+    }
+    assert!(field(lines[0], "hash") != field(lines[1], "hash"));
+}